@@ -0,0 +1,217 @@
+//! Alarms: playlists started at a time of day, with the volume rising
+//! from silence.
+
+use chrono::{Datelike, Timelike};
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+use crate::app::config_dir;
+
+/// How long an alarm takes to bring the volume up
+const ALARM_FADE_IN: Duration = Duration::from_secs(60);
+
+/// Playlist started at a time of day. Recurring alarms come from
+/// `<config>/alarms`, one per line: `HH:MM [days] playlist`, where days
+/// follow cron's day-of-week field (0-7 from Sunday, lists and ranges,
+/// `*` for every day; the default). Alarms set from the prompt fire once.
+#[derive(Clone, Debug)]
+pub(crate) struct Alarm {
+    pub(crate) hour: u32,
+    pub(crate) minute: u32,
+    /// One bit per weekday, bit 0 is Sunday
+    days: u8,
+    /// Playlist path or name
+    pub(crate) playlist: String,
+    pub(crate) once: bool,
+}
+
+impl Alarm {
+    pub(crate) fn parse(line: &str, once: bool) -> Result<Self, String> {
+        let mut fields = line.split_whitespace();
+        let time = fields.next().ok_or("orario mancante")?;
+        let (hour, minute) = time
+            .split_once(':')
+            .and_then(|(h, m)| Some((h.parse().ok()?, m.parse().ok()?)))
+            .filter(|&(h, m)| h < 24 && m < 60)
+            .ok_or_else(|| format!("orario non valido: {}", time))?;
+        let rest: Vec<&str> = fields.collect();
+        let (days, playlist) = match rest.split_first() {
+            Some((first, tail)) if !tail.is_empty() => match Self::parse_days(first) {
+                Some(days) => (days, tail.join(" ")),
+                None => (0x7f, rest.join(" ")),
+            },
+            _ => (0x7f, rest.join(" ")),
+        };
+        if playlist.is_empty() {
+            return Err("playlist mancante".to_string());
+        }
+        Ok(Self {
+            hour,
+            minute,
+            days,
+            playlist,
+            once,
+        })
+    }
+
+    /// `*`, `1-5`, `0,6`... into a weekday mask
+    fn parse_days(field: &str) -> Option<u8> {
+        if field == "*" {
+            return Some(0x7f);
+        }
+        let day = |d: &str| d.parse::<u32>().ok().filter(|&d| d <= 7).map(|d| d % 7);
+        let mut days = 0u8;
+        for part in field.split(',') {
+            let (first, last) = match part.split_once('-') {
+                Some((first, last)) => (day(first)?, day(last)?),
+                None => (day(part)?, day(part)?),
+            };
+            // Ranges may wrap past Saturday, e.g. 5-1 is Friday to Monday
+            let mut d = first;
+            loop {
+                days |= 1 << d;
+                if d == last {
+                    break;
+                }
+                d = (d + 1) % 7;
+            }
+        }
+        Some(days)
+    }
+
+    /// Recurring alarms; unreadable lines are skipped
+    fn load() -> Vec<Self> {
+        config_dir()
+            .and_then(|dir| fs::read_to_string(dir.join("alarms")).ok())
+            .map(|content| {
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .filter_map(|line| Self::parse(line, false).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// `weekday` counts from Sunday = 0
+    fn matches(&self, weekday: u32, hour: u32, minute: u32) -> bool {
+        self.days & (1 << weekday) != 0 && self.hour == hour && self.minute == minute
+    }
+}
+
+/// The alarms set, and the fade-in of the one that went off
+#[derive(Default)]
+pub(crate) struct Alarms {
+    alarms: Vec<Alarm>,
+    /// Day of the year, hour and minute alarms were last checked for
+    checked: Option<(u32, u32, u32)>,
+    /// Fade-in running: when it started and the volume it leads to
+    fade_in: Option<(Instant, f32)>,
+}
+
+impl Alarms {
+    pub(crate) fn load() -> Self {
+        Self {
+            alarms: Alarm::load(),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn push(&mut self, alarm: Alarm) {
+        self.alarms.push(alarm);
+    }
+
+    /// Alarms set from the prompt that haven't gone off yet
+    pub(crate) fn pending(&self) -> impl Iterator<Item = &Alarm> {
+        self.alarms.iter().filter(|alarm| alarm.once)
+    }
+
+    /// The alarm set for the minute of `now`, once per minute; alarms that
+    /// fire once are dropped as they come due
+    pub(crate) fn due(&mut self, now: chrono::DateTime<chrono::Local>) -> Option<Alarm> {
+        if self.alarms.is_empty() {
+            return None;
+        }
+        let minute = (now.ordinal(), now.hour(), now.minute());
+        if self.checked == Some(minute) {
+            return None;
+        }
+        self.checked = Some(minute);
+
+        let weekday = now.weekday().num_days_from_sunday();
+        let due = self
+            .alarms
+            .iter()
+            .find(|alarm| alarm.matches(weekday, now.hour(), now.minute()))
+            .cloned();
+        self.alarms
+            .retain(|alarm| !(alarm.once && alarm.matches(weekday, now.hour(), now.minute())));
+        due
+    }
+
+    pub(crate) fn start_fade_in(&mut self, target: f32) {
+        self.fade_in = Some((Instant::now(), target));
+    }
+
+    /// Leaves the volume where it is, e.g. once the user changes it
+    pub(crate) fn stop_fade_in(&mut self) {
+        self.fade_in = None;
+    }
+
+    /// Volume the running fade-in is at, rising along a quadratic curve,
+    /// which sounds more even than a linear one at low levels
+    pub(crate) fn fade_in_volume(&mut self) -> Option<f32> {
+        let (start, target) = self.fade_in?;
+        let progress = (start.elapsed().as_secs_f32() / ALARM_FADE_IN.as_secs_f32()).min(1.0);
+        if progress >= 1.0 {
+            self.fade_in = None;
+        }
+        Some(target * progress * progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn days_follow_cron_and_wrap_past_saturday() {
+        assert_eq!(Alarm::parse_days("*"), Some(0x7f));
+        assert_eq!(Alarm::parse_days("0,6"), Some(0b100_0001));
+        assert_eq!(Alarm::parse_days("7"), Some(0b1));
+        assert_eq!(Alarm::parse_days("5-1"), Some(0b110_0011));
+        assert_eq!(Alarm::parse_days("8"), None);
+    }
+
+    #[test]
+    fn playlist_may_start_with_a_number() {
+        let alarm = Alarm::parse("7:30 1-5 Sveglia dolce", false).unwrap();
+        assert_eq!((alarm.hour, alarm.minute), (7, 30));
+        assert_eq!(alarm.playlist, "Sveglia dolce");
+        let alarm = Alarm::parse("7:30 2024 hits", false).unwrap();
+        assert_eq!(alarm.playlist, "2024 hits");
+        assert!(Alarm::parse("24:00 mattino", false).is_err());
+        assert!(Alarm::parse("7:30", false).is_err());
+    }
+
+    #[test]
+    fn alarms_fire_once_per_minute_and_once_alarms_go() {
+        let mut alarms = Alarms::default();
+        alarms.push(Alarm::parse("7:30 mattino", false).unwrap());
+        alarms.push(Alarm::parse("7:30 extra", true).unwrap());
+        let at = chrono::Local
+            .with_ymd_and_hms(2026, 3, 2, 7, 30, 0)
+            .unwrap();
+        assert_eq!(alarms.due(at).unwrap().playlist, "mattino");
+        assert!(alarms.due(at).is_none());
+        assert_eq!(alarms.pending().count(), 0);
+        let later = chrono::Local
+            .with_ymd_and_hms(2026, 3, 2, 7, 31, 0)
+            .unwrap();
+        assert!(alarms.due(later).is_none());
+    }
+}
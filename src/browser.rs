@@ -0,0 +1,552 @@
+//! Files on disk: tags, the browser's listing, integrity checks and
+//! archives.
+
+use lofty::{
+    file::{AudioFile, FileType, TaggedFileExt},
+    tag::{Accessor, ItemKey},
+};
+use rodio::Source;
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use crate::app::App;
+use crate::player::AudioPlayer;
+
+/// Tag fields read from an audio file
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TrackTags {
+    pub(crate) track: Option<u32>,
+    pub(crate) disc: Option<u32>,
+    pub(crate) title: Option<String>,
+    pub(crate) artist: Option<String>,
+    pub(crate) album: Option<String>,
+    pub(crate) year: Option<u32>,
+    pub(crate) has_picture: bool,
+    /// MusicBrainz release id, the key for covers from the Cover Art Archive
+    pub(crate) release_id: Option<String>,
+    pub(crate) genre: Option<String>,
+}
+
+impl TrackTags {
+    /// Reads the primary tag, falling back to any tag in the file.
+    /// Untagged or unreadable files give empty tags.
+    pub(crate) fn read(path: &PathBuf) -> Self {
+        lofty::read_from_path(path)
+            .map(|file| Self::from_file(&file))
+            .unwrap_or_default()
+    }
+
+    /// "Artist – Title – Album" with the parts that are tagged, or `None`
+    /// without a title
+    pub(crate) fn full_name(&self) -> Option<String> {
+        let title = self.title.as_ref()?;
+        let parts: Vec<&str> = [self.artist.as_ref(), Some(title), self.album.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        Some(parts.join(" – "))
+    }
+
+    /// "Artist – Title", falling back to the title or the file name
+    pub(crate) fn display_name(&self, path: &Path) -> String {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => format!("{} – {}", artist, title),
+            (_, Some(title)) => title.clone(),
+            _ => path
+                .file_stem()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn from_file(tagged_file: &lofty::file::TaggedFile) -> Self {
+        let Some(tag) = tagged_file
+            .primary_tag()
+            .or_else(|| tagged_file.first_tag())
+        else {
+            return Self::default();
+        };
+
+        Self {
+            track: tag.track(),
+            disc: tag.disk(),
+            title: tag.title().map(|s| s.into_owned()),
+            artist: tag.artist().map(|s| s.into_owned()),
+            album: tag.album().map(|s| s.into_owned()),
+            year: tag.year(),
+            has_picture: !tag.pictures().is_empty(),
+            release_id: tag
+                .get_string(&ItemKey::MusicBrainzReleaseId)
+                .map(str::to_string),
+            genre: tag.genre().map(|s| s.into_owned()),
+        }
+    }
+}
+
+/// Files probed beyond each side of the visible browser rows
+pub(crate) const SCAN_PAGE: usize = 100;
+
+/// Details probed in the background for a file listed in the browser
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FileInfo {
+    pub(crate) duration: Option<Duration>,
+    pub(crate) tags: TrackTags,
+    /// Format as stored, e.g. "FLAC 24 bit 96 kHz 2304 kbps"
+    pub(crate) codec: Option<String>,
+}
+
+impl FileInfo {
+    /// Reads tags and duration in one pass over the file's headers
+    pub(crate) fn probe(path: &PathBuf) -> Self {
+        let Ok(tagged_file) = lofty::read_from_path(path) else {
+            return Self::default();
+        };
+        let properties = tagged_file.properties();
+        let duration = properties.duration();
+        let format = match tagged_file.file_type() {
+            FileType::Mpeg => "MP3".to_string(),
+            FileType::Flac => "FLAC".to_string(),
+            FileType::Vorbis => "Ogg Vorbis".to_string(),
+            FileType::Opus => "Opus".to_string(),
+            FileType::Mp4 => "MP4".to_string(),
+            FileType::Wav => "WAV".to_string(),
+            FileType::Aiff => "AIFF".to_string(),
+            FileType::Ape => "APE".to_string(),
+            FileType::WavPack => "WavPack".to_string(),
+            FileType::Aac => "AAC".to_string(),
+            other => format!("{:?}", other),
+        };
+        let mut codec = vec![format];
+        codec.extend(properties.bit_depth().map(|bits| format!("{} bit", bits)));
+        codec.extend(
+            properties
+                .sample_rate()
+                .map(|rate| format!("{} kHz", rate as f32 / 1000.0)),
+        );
+        codec.extend(
+            properties
+                .audio_bitrate()
+                .filter(|&kbps| kbps > 0)
+                .map(|kbps| format!("{} kbps", kbps)),
+        );
+        Self {
+            duration: (!duration.is_zero()).then_some(duration),
+            tags: TrackTags::from_file(&tagged_file),
+            codec: Some(codec.join(" ")),
+        }
+    }
+
+    /// Probes `paths` on a worker thread, sending each result as it is ready.
+    /// The worker stops as soon as the receiver is dropped.
+    pub(crate) fn scan(paths: Vec<PathBuf>) -> mpsc::Receiver<(PathBuf, FileInfo)> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for path in paths {
+                let info = Self::probe(&path);
+                if sender.send((path, info)).is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+}
+
+/// Result of decoding a file end to end: corrupt frames, truncation and, for
+/// FLAC, the MD5 of the audio against the one in STREAMINFO
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Integrity {
+    pub(crate) problems: Vec<String>,
+    pub(crate) md5_verified: bool,
+}
+
+impl Integrity {
+    /// Missing audio tolerated against the length the file declares
+    const TOLERANCE: Duration = Duration::from_millis(500);
+
+    pub(crate) fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Decodes the whole file, so it's meant to run off the UI thread.
+    /// FLAC and MP3 use their decoders directly, which report bad frames;
+    /// other formats only get the truncation check.
+    fn check(path: &PathBuf) -> Self {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        let in_archive = Archive::locate(path).is_some();
+        let result = match ext.as_str() {
+            "flac" if !in_archive => Self::check_flac(path),
+            "mp3" if !in_archive => Self::check_mp3(path),
+            _ => Self::check_decoded(path),
+        };
+        result.unwrap_or_else(|e| Self {
+            problems: vec![format!("non decodificabile: {}", e)],
+            md5_verified: false,
+        })
+    }
+
+    fn check_flac(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = claxon::FlacReader::open(path)?;
+        let info = reader.streaminfo();
+        let mut integrity = Self::default();
+        // The signature covers the samples as little-endian bytes, interleaved
+        let bytes = info.bits_per_sample.div_ceil(8) as usize;
+        let mut context = md5::Context::new();
+        let mut data = Vec::new();
+        let mut frames = 0u64;
+        let mut blocks = reader.blocks();
+        let mut buffer = Vec::new();
+        let complete = loop {
+            match blocks.read_next_or_eof(buffer) {
+                Ok(Some(block)) => {
+                    data.clear();
+                    for i in 0..block.duration() {
+                        for channel in 0..block.channels() {
+                            data.extend_from_slice(
+                                &block.sample(channel, i).to_le_bytes()[..bytes],
+                            );
+                        }
+                    }
+                    context.consume(&data);
+                    frames += block.duration() as u64;
+                    buffer = block.into_buffer();
+                }
+                Ok(None) => break true,
+                Err(e) => {
+                    integrity.problems.push(format!(
+                        "frame corrotto a {}: {}",
+                        App::format_duration(Self::frames(frames, info.sample_rate)),
+                        e
+                    ));
+                    break false;
+                }
+            }
+        };
+        if let Some(expected) = info.samples {
+            integrity.check_length(frames, expected, info.sample_rate);
+        }
+        // An all-zero signature means the encoder didn't compute one
+        if complete && info.md5sum != [0; 16] {
+            if context.compute().0 == info.md5sum {
+                integrity.md5_verified = true;
+            } else {
+                integrity
+                    .problems
+                    .push("MD5 dell'audio diverso da quello dichiarato".to_string());
+            }
+        }
+        Ok(integrity)
+    }
+
+    fn check_mp3(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        use symphonia::core::{
+            codecs::DecoderOptions, errors::Error, formats::FormatOptions, io::MediaSourceStream,
+            meta::MetadataOptions, probe::Hint,
+        };
+
+        let stream = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension("mp3");
+        let mut format = symphonia::default::get_probe()
+            .format(
+                &hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )?
+            .format;
+        let track = format.default_track().ok_or("nessuna traccia audio")?;
+        let track_id = track.id;
+        let sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
+        // Known only when the file has a Xing/Info header
+        let expected = track.codec_params.n_frames;
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())?;
+
+        let mut integrity = Self::default();
+        let mut frames = 0u64;
+        let mut corrupt = 0usize;
+        let mut first_corrupt = None;
+        loop {
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => {
+                    integrity.problems.push(format!(
+                        "lettura interrotta a {}: {}",
+                        App::format_duration(Self::frames(frames, sample_rate)),
+                        e
+                    ));
+                    break;
+                }
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+            match decoder.decode(&packet) {
+                Ok(buffer) => frames += buffer.frames() as u64,
+                Err(Error::DecodeError(_)) => {
+                    corrupt += 1;
+                    first_corrupt.get_or_insert(frames);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if let Some(first) = first_corrupt {
+            integrity.problems.push(format!(
+                "{} frame corrotti, il primo a {}",
+                corrupt,
+                App::format_duration(Self::frames(first, sample_rate))
+            ));
+        }
+        if let Some(expected) = expected {
+            integrity.check_length(frames, expected, sample_rate);
+        }
+        Ok(integrity)
+    }
+
+    /// Through the player's own decoders, against the length in the tags'
+    /// audio properties
+    fn check_decoded(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        let source = AudioPlayer::open_source(path)?;
+        let channels = source.channels().max(1) as u64;
+        let sample_rate = source.sample_rate();
+        let frames = source.count() as u64 / channels;
+        let mut integrity = Self::default();
+        let declared = lofty::read_from_path(path)
+            .ok()
+            .map(|file| file.properties().duration())
+            .filter(|duration| !duration.is_zero());
+        if let Some(declared) = declared {
+            let expected = (declared.as_secs_f64() * sample_rate as f64) as u64;
+            integrity.check_length(frames, expected, sample_rate);
+        }
+        Ok(integrity)
+    }
+
+    fn check_length(&mut self, frames: u64, expected: u64, sample_rate: u32) {
+        let missing = Self::frames(expected.saturating_sub(frames), sample_rate);
+        if missing > Self::TOLERANCE {
+            self.problems.push(format!(
+                "troncato: mancano {:.1} s su {}",
+                missing.as_secs_f64(),
+                App::format_duration(Self::frames(expected, sample_rate))
+            ));
+        }
+    }
+
+    fn frames(frames: u64, sample_rate: u32) -> Duration {
+        Duration::from_secs_f64(frames as f64 / sample_rate.max(1) as f64)
+    }
+
+    /// Checks `paths` on a worker thread, sending each result as it is ready.
+    /// The worker stops as soon as the receiver is dropped.
+    pub(crate) fn scan(paths: Vec<PathBuf>) -> mpsc::Receiver<(PathBuf, Integrity)> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for path in paths {
+                let integrity = Self::check(&path);
+                if sender.send((path, integrity)).is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+}
+
+/// Archive formats the browser can enter like a folder
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    pub(crate) fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Archive browsed as a virtual folder. Files inside are addressed as
+/// `<archive>/<entry>`, e.g. `~/Download/album.zip/CD1/01.flac`, and read
+/// into memory when played, without extracting anything to disk.
+pub(crate) struct Archive {
+    pub(crate) path: PathBuf,
+    kind: ArchiveKind,
+}
+
+impl Archive {
+    /// The archive `path` is or lies in, with the entry path inside it
+    /// (empty for the archive itself)
+    pub(crate) fn locate(path: &Path) -> Option<(Archive, PathBuf)> {
+        path.ancestors().find_map(|ancestor| {
+            let kind = ArchiveKind::of(ancestor)?;
+            let inner = path.strip_prefix(ancestor).ok()?.to_path_buf();
+            ancestor.is_file().then(|| {
+                let archive = Archive {
+                    path: ancestor.to_path_buf(),
+                    kind,
+                };
+                (archive, inner)
+            })
+        })
+    }
+
+    fn open_zip(&self) -> io::Result<zip::ZipArchive<File>> {
+        zip::ZipArchive::new(File::open(&self.path)?).map_err(io::Error::other)
+    }
+
+    fn open_tar(&self) -> io::Result<tar::Archive<Box<dyn Read>>> {
+        let file = BufReader::new(File::open(&self.path)?);
+        let reader: Box<dyn Read> = match self.kind {
+            ArchiveKind::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+            _ => Box::new(file),
+        };
+        Ok(tar::Archive::new(reader))
+    }
+
+    /// Tar entry path without the `./` prefix many archivers add
+    fn tar_path(path: &Path) -> PathBuf {
+        path.components()
+            .filter(|c| matches!(c, std::path::Component::Normal(_)))
+            .collect()
+    }
+
+    /// Paths of the regular files stored in the archive
+    fn files(&self) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        if self.kind == ArchiveKind::Zip {
+            let mut zip = self.open_zip()?;
+            for i in 0..zip.len() {
+                let entry = zip.by_index(i).map_err(io::Error::other)?;
+                if entry.is_file() {
+                    files.extend(entry.enclosed_name());
+                }
+            }
+        } else {
+            for entry in self.open_tar()?.entries()? {
+                let entry = entry?;
+                if entry.header().entry_type().is_file() {
+                    files.push(Self::tar_path(&entry.path()?));
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Files and subfolders directly inside the folder `inner`, as virtual
+    /// paths; subfolders are flagged true
+    pub(crate) fn list(&self, inner: &Path) -> io::Result<Vec<(PathBuf, bool)>> {
+        let dir = if inner.as_os_str().is_empty() {
+            self.path.clone()
+        } else {
+            self.path.join(inner)
+        };
+        let mut seen = HashSet::new();
+        let mut listing = Vec::new();
+        for file in self.files()? {
+            let Ok(rest) = file.strip_prefix(inner) else {
+                continue;
+            };
+            let mut components = rest.components();
+            let Some(first) = components.next() else {
+                continue;
+            };
+            let path = dir.join(first);
+            if seen.insert(path.clone()) {
+                listing.push((path, components.next().is_some()));
+            }
+        }
+        Ok(listing)
+    }
+
+    /// Contents of the file at `inner`
+    pub(crate) fn read(&self, inner: &Path) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        if self.kind == ArchiveKind::Zip {
+            let mut zip = self.open_zip()?;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i).map_err(io::Error::other)?;
+                if entry.enclosed_name().as_deref() == Some(inner) {
+                    entry.read_to_end(&mut data)?;
+                    return Ok(data);
+                }
+            }
+        } else {
+            for entry in self.open_tar()?.entries()? {
+                let mut entry = entry?;
+                if Self::tar_path(&entry.path()?) == inner {
+                    entry.read_to_end(&mut data)?;
+                    return Ok(data);
+                }
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} non trovato in {}", inner.display(), self.path.display()),
+        ))
+    }
+}
+
+/// Puts back the most recently trashed file that was at `path`
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+pub(crate) fn restore_from_trash(path: &Path) -> Result<(), String> {
+    let item = trash::os_limited::list()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|item| item.original_path() == path)
+        .max_by_key(|item| item.time_deleted)
+        .ok_or("file non trovato nel cestino")?;
+    trash::os_limited::restore_all([item]).map_err(|e| e.to_string())
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+fn restore_from_trash(_path: &Path) -> Result<(), String> {
+    Err("ripristino dal cestino non supportato su questo sistema".to_string())
+}
+
+/// File extensions shown in the browser and played
+pub(crate) const AUDIO_EXTENSIONS: [&str; 8] =
+    ["mp3", "flac", "wav", "ogg", "m4a", "opus", "dsf", "dff"];
+
+/// Formats whose tags can be written (ReplayGain/R128, MusicBrainz completion)
+pub(crate) const TAG_WRITE_EXTENSIONS: [&str; 3] = ["mp3", "flac", "opus"];
@@ -0,0 +1,269 @@
+//! Audio CDs through cdparanoia: the table of contents, playback and
+//! ripping to FLAC.
+
+use std::{
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+use crate::metadata::MusicBrainz;
+use crate::player::{ExternalDecoder, PcmLayout, RawFormat};
+
+/// Track of an audio CD, its position in sectors from the start of the disc
+#[derive(Clone, Debug)]
+pub(crate) struct CdTrack {
+    pub(crate) number: u32,
+    start: u32,
+    sectors: u32,
+    pub(crate) title: Option<String>,
+}
+
+impl CdTrack {
+    pub(crate) fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.sectors as f64 / AudioCd::SECTORS_PER_SECOND as f64)
+    }
+
+    /// `title`, else "Traccia N"
+    pub(crate) fn name(&self) -> String {
+        self.title
+            .clone()
+            .unwrap_or_else(|| format!("Traccia {}", self.number))
+    }
+}
+
+/// Audio CD in a drive, read with `cdparanoia`. Names come from a
+/// MusicBrainz lookup by table of contents, when the disc is known there;
+/// ripping also needs the `flac` encoder.
+#[derive(Clone, Debug)]
+pub(crate) struct AudioCd {
+    pub(crate) device: PathBuf,
+    pub(crate) tracks: Vec<CdTrack>,
+    pub(crate) artist: Option<String>,
+    pub(crate) album: Option<String>,
+}
+
+impl AudioCd {
+    const DEVICES: [&str; 2] = ["/dev/cdrom", "/dev/sr0"];
+    const SECTORS_PER_SECOND: u32 = 75;
+    /// The first track starts after two seconds of lead-in, which the
+    /// MusicBrainz table of contents counts and cdparanoia doesn't
+    const LEAD_IN: u32 = 150;
+    /// Red Book audio, as `cdparanoia -r` writes it
+    const LAYOUT: PcmLayout = PcmLayout {
+        format: RawFormat::S16Le,
+        sample_rate: 44100,
+        channels: 2,
+    };
+
+    /// The first drive holding an audio disc, with names when MusicBrainz
+    /// has them; a failed lookup leaves the tracks numbered
+    pub(crate) fn detect() -> Result<Self, String> {
+        let mut last_error = "nessun lettore CD trovato".to_string();
+        for device in Self::DEVICES.iter().map(Path::new) {
+            if !device.exists() {
+                continue;
+            }
+            match Self::read_toc(device) {
+                Ok(tracks) => {
+                    let mut cd = Self {
+                        device: device.to_path_buf(),
+                        tracks,
+                        artist: None,
+                        album: None,
+                    };
+                    let _ = cd.lookup_names();
+                    return Ok(cd);
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    fn read_toc(device: &Path) -> Result<Vec<CdTrack>, String> {
+        let output = process::Command::new("cdparanoia")
+            .arg("-d")
+            .arg(device)
+            .arg("-Q")
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::null())
+            .output()
+            .map_err(|e| format!("cdparanoia non disponibile: {}", e))?;
+        // The table goes to stderr
+        let tracks = Self::parse_toc(&String::from_utf8_lossy(&output.stderr));
+        if tracks.is_empty() {
+            return Err(format!("nessun disco audio in {}", device.display()));
+        }
+        Ok(tracks)
+    }
+
+    /// Rows of `cdparanoia -Q`'s table: `  1.    16503 [03:40.03]        0 [00:00.00]  no  no  2`
+    fn parse_toc(text: &str) -> Vec<CdTrack> {
+        text.lines()
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                let number = words.next()?.strip_suffix('.')?.parse().ok()?;
+                let sectors = words.next()?.parse().ok()?;
+                words.next()?;
+                let start = words.next()?.parse().ok()?;
+                Some(CdTrack {
+                    number,
+                    start,
+                    sectors,
+                    title: None,
+                })
+            })
+            .collect()
+    }
+
+    /// MusicBrainz's `toc` parameter: first and last track, the lead-out,
+    /// then each track's start
+    fn musicbrainz_toc(&self) -> String {
+        let (Some(first), Some(last)) = (self.tracks.first(), self.tracks.last()) else {
+            return String::new();
+        };
+        let lead_out = last.start + last.sectors + Self::LEAD_IN;
+        let mut toc = vec![first.number, last.number, lead_out];
+        toc.extend(self.tracks.iter().map(|track| track.start + Self::LEAD_IN));
+        toc.iter().map(u32::to_string).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Names the disc and its tracks after the first release with as many
+    /// tracks on a disc matching the table of contents
+    fn lookup_names(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let agent = MusicBrainz::agent();
+        let response = MusicBrainz::get_json(
+            &agent,
+            &format!("{}/discid/-", MusicBrainz::API),
+            &[
+                ("toc", &self.musicbrainz_toc()),
+                ("inc", "artist-credits recordings"),
+            ],
+        )?;
+        let release = response["releases"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find_map(|release| {
+                let medium = release["media"].as_array()?.iter().find(|medium| {
+                    medium["tracks"]
+                        .as_array()
+                        .is_some_and(|tracks| tracks.len() == self.tracks.len())
+                })?;
+                Some((release, medium))
+            });
+        let Some((release, medium)) = release else {
+            return Err("disco non trovato su MusicBrainz".into());
+        };
+        self.album = release["title"].as_str().map(str::to_string);
+        self.artist = release["artist-credit"].as_array().map(|credits| {
+            credits
+                .iter()
+                .map(|credit| {
+                    format!(
+                        "{}{}",
+                        credit["name"].as_str().unwrap_or_default(),
+                        credit["joinphrase"].as_str().unwrap_or_default()
+                    )
+                })
+                .collect()
+        });
+        let titles = medium["tracks"].as_array().into_iter().flatten();
+        for (track, entry) in self.tracks.iter_mut().zip(titles) {
+            track.title = entry["title"].as_str().map(str::to_string);
+        }
+        Ok(())
+    }
+
+    /// Reads `track` as raw PCM, for the player
+    pub(crate) fn decoder(&self, track: &CdTrack) -> ExternalDecoder {
+        let command = ["cdparanoia", "-q", "-d", "{}", "-r", "--"]
+            .into_iter()
+            .map(str::to_string)
+            .chain([track.number.to_string(), "-".to_string()])
+            .collect();
+        ExternalDecoder::new(Self::LAYOUT, command)
+    }
+
+    /// "Artist - Album", or just "CD audio" for an unknown disc
+    pub(crate) fn title(&self) -> String {
+        match (&self.artist, &self.album) {
+            (Some(artist), Some(album)) => format!("{} - {}", artist, album),
+            (None, Some(album)) => album.clone(),
+            _ => "CD audio".to_string(),
+        }
+    }
+
+    /// Rips every track to `<dir>/<title>/NN - Name.flac`, tagged, and
+    /// returns the folder. Slow: run it on a worker thread.
+    pub(crate) fn rip(&self, dir: &Path) -> Result<PathBuf, String> {
+        let folder = dir.join(Self::file_name(&self.title()));
+        std::fs::create_dir_all(&folder).map_err(|e| e.to_string())?;
+        for track in &self.tracks {
+            let file = folder.join(format!(
+                "{:02} - {}.flac",
+                track.number,
+                Self::file_name(&track.name())
+            ));
+            self.rip_track(track, &file)
+                .map_err(|e| format!("traccia {}: {}", track.number, e))?;
+        }
+        Ok(folder)
+    }
+
+    /// `cdparanoia -w` piped into `flac`
+    fn rip_track(&self, track: &CdTrack, file: &Path) -> Result<(), String> {
+        let mut reader = process::Command::new("cdparanoia")
+            .arg("-q")
+            .arg("-d")
+            .arg(&self.device)
+            .args(["-w", "--", &track.number.to_string(), "-"])
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::null())
+            .spawn()
+            .map_err(|e| format!("cdparanoia non disponibile: {}", e))?;
+        let audio = reader.stdout.take().ok_or("cdparanoia senza uscita")?;
+        let mut tags = vec![
+            format!("TITLE={}", track.name()),
+            format!("TRACKNUMBER={}", track.number),
+        ];
+        tags.extend(
+            self.artist
+                .as_ref()
+                .map(|artist| format!("ARTIST={}", artist)),
+        );
+        tags.extend(self.album.as_ref().map(|album| format!("ALBUM={}", album)));
+        let encoded = process::Command::new("flac")
+            .args(["-s", "-f", "--best", "-o"])
+            .arg(file)
+            .args(tags.iter().flat_map(|tag| ["-T", tag]))
+            .arg("-")
+            .stdin(audio)
+            .stderr(process::Stdio::piped())
+            .output();
+        let read = reader.wait().map_err(|e| e.to_string())?;
+        let encoded = encoded.map_err(|e| format!("flac non disponibile: {}", e))?;
+        if !read.success() {
+            return Err("lettura del disco non riuscita".to_string());
+        }
+        if !encoded.status.success() {
+            return Err(String::from_utf8_lossy(&encoded.stderr).trim().to_string());
+        }
+        Ok(())
+    }
+
+    /// `name` without the characters file systems reject
+    fn file_name(name: &str) -> String {
+        name.chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c => c,
+            })
+            .collect::<String>()
+            .trim()
+            .trim_start_matches('.')
+            .to_string()
+    }
+}
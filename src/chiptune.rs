@@ -0,0 +1,464 @@
+//! Game music: the sound chips of old consoles and computers emulated by
+//! Game_Music_Emu, and Commodore 64 SID tunes through `sidplayfp`. A file
+//! holds several subtunes; their names and lengths come from the format's
+//! own metadata where it has them.
+
+use flate2::read::GzDecoder;
+use game_music_emu::GameMusicEmu;
+use rodio::{Source, source::SeekError};
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::player::ExternalDecoder;
+
+/// Formats played by Game_Music_Emu, and SID
+const GME_EXTENSIONS: [&str; 11] = [
+    "nsf", "nsfe", "spc", "vgm", "vgz", "gbs", "ay", "hes", "kss", "sap", "gym",
+];
+const SID_EXTENSIONS: [&str; 2] = ["sid", "psid"];
+
+/// One of the tunes in a file
+#[derive(Clone, Debug)]
+pub(crate) struct Subtune {
+    pub(crate) title: Option<String>,
+    /// Playing time, the fade-out included
+    pub(crate) length: Duration,
+    /// The last part of `length`, faded to silence
+    fade: Duration,
+}
+
+impl Subtune {
+    /// Most game music loops forever: unless the file says otherwise, a
+    /// tune plays two and a half minutes and fades out over the last eight
+    /// seconds, as Game_Music_Emu's players do
+    const DEFAULT_LENGTH: Duration = Duration::from_secs(150);
+    const DEFAULT_FADE: Duration = Duration::from_secs(8);
+
+    fn untimed(title: Option<String>) -> Self {
+        Self {
+            title,
+            length: Self::DEFAULT_LENGTH,
+            fade: Self::DEFAULT_FADE,
+        }
+    }
+
+    /// `play` milliseconds, then `fade` more fading out
+    fn timed(title: Option<String>, play: Duration, fade: Duration) -> Self {
+        Self {
+            title,
+            length: play + fade,
+            fade,
+        }
+    }
+}
+
+/// A game music file read for playback: the emulator's input and what the
+/// header says about its subtunes
+pub(crate) struct Chiptune {
+    pub(crate) path: PathBuf,
+    data: Vec<u8>,
+    sid: bool,
+    pub(crate) game: Option<String>,
+    pub(crate) author: Option<String>,
+    pub(crate) subtunes: Vec<Subtune>,
+    /// Subtune played when the file is played as a whole
+    pub(crate) start: usize,
+}
+
+impl Chiptune {
+    const SAMPLE_RATE: u32 = 44100;
+
+    pub(crate) fn is_chiptune(path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .is_some_and(|e| {
+                GME_EXTENSIONS.contains(&e.as_str()) || SID_EXTENSIONS.contains(&e.as_str())
+            })
+    }
+
+    pub(crate) fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_data(path, fs::read(path)?)
+    }
+
+    /// `data` is the file's content; `path` names it and picks the format
+    pub(crate) fn from_data(
+        path: &Path,
+        mut data: Vec<u8>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // Compressed VGM: Game_Music_Emu reads it only when built with zlib
+        if data.starts_with(&[0x1F, 0x8B]) {
+            let mut inflated = Vec::new();
+            GzDecoder::new(data.as_slice()).read_to_end(&mut inflated)?;
+            data = inflated;
+        }
+        let mut tune = Self {
+            path: path.to_path_buf(),
+            data,
+            sid: false,
+            game: None,
+            author: None,
+            subtunes: Vec::new(),
+            start: 0,
+        };
+        match tune.data.get(..4) {
+            Some(b"PSID" | b"RSID") => tune.read_sid(),
+            Some(b"NESM") => tune.read_nsf(),
+            Some(b"NSFE") => tune.read_nsfe(),
+            Some(b"SNES") => tune.read_spc(),
+            Some(b"Vgm ") => tune.read_vgm(),
+            Some([b'G', b'B', b'S', _]) => tune.read_gbs(),
+            _ => {}
+        }
+        if !tune.sid {
+            // The emulator knows how many tracks every format has
+            let emu = GameMusicEmu::from_data(&tune.data, Self::SAMPLE_RATE)?;
+            tune.subtunes
+                .resize_with(emu.track_count(), || Subtune::untimed(None));
+        }
+        if tune.subtunes.is_empty() {
+            return Err("nessun brano nel file".into());
+        }
+        tune.start = tune.start.min(tune.subtunes.len() - 1);
+        Ok(tune)
+    }
+
+    /// The emulator playing `subtune`, or `None` for a SID tune, which
+    /// `sid_decoder` plays
+    pub(crate) fn source(&self, subtune: usize) -> Option<ChiptuneSource> {
+        if self.sid {
+            return None;
+        }
+        let emu = GameMusicEmu::from_data(&self.data, Self::SAMPLE_RATE).ok()?;
+        emu.start_track(subtune).ok()?;
+        let info = self.subtunes.get(subtune)?;
+        Some(ChiptuneSource {
+            emu,
+            subtune,
+            buffer: vec![0; ChiptuneSource::CHUNK],
+            position: ChiptuneSource::CHUNK,
+            played: 0,
+            length: ChiptuneSource::samples(info.length),
+            fade: ChiptuneSource::samples(info.fade),
+        })
+    }
+
+    /// `sidplayfp` writing `subtune` as WAV for its length
+    pub(crate) fn sid_decoder(&self, subtune: usize) -> Option<ExternalDecoder> {
+        let length = self.subtunes.get(subtune).filter(|_| self.sid)?.length;
+        let command = [
+            "sidplayfp".to_string(),
+            "-q".to_string(),
+            format!("-f{}", Self::SAMPLE_RATE),
+            format!("-o{}", subtune + 1),
+            format!("-t{}", length.as_secs()),
+            "-w/dev/stdout".to_string(),
+            "{}".to_string(),
+        ];
+        Some(ExternalDecoder::wav(command.to_vec()))
+    }
+
+    /// "Title", "Title — Game" or "Traccia N"
+    pub(crate) fn subtune_name(&self, subtune: usize) -> String {
+        let title = self.subtunes.get(subtune).and_then(|s| s.title.clone());
+        match (title, &self.game) {
+            (Some(title), Some(game)) if self.subtunes.len() > 1 => {
+                format!("{} — {}", title, game)
+            }
+            (Some(title), _) => title,
+            (None, Some(game)) if self.subtunes.len() == 1 => game.clone(),
+            _ => format!("Traccia {}", subtune + 1),
+        }
+    }
+
+    /// PSID/RSID: big-endian song count and 1-based start song, then name
+    /// and author in 32-byte fields
+    fn read_sid(&mut self) {
+        self.sid = true;
+        let songs = self.u16_be(0x0E).unwrap_or(1).max(1) as usize;
+        self.start = self.u16_be(0x10).unwrap_or(1).saturating_sub(1) as usize;
+        self.game = self.text(0x16, 32);
+        self.author = self.text(0x36, 32);
+        self.subtunes = vec![Subtune::untimed(None); songs];
+    }
+
+    /// NSF: song count, 1-based start song, then name, artist and copyright
+    fn read_nsf(&mut self) {
+        self.start = self
+            .data
+            .get(7)
+            .map_or(0, |&s| s.saturating_sub(1) as usize);
+        self.game = self.text(0x0E, 32);
+        self.author = self.text(0x2E, 32);
+    }
+
+    /// NSFe: chunks of `length, id, data`. `INFO` has the 0-based start
+    /// song, `time` and `fade` the milliseconds of each track, `tlbl` and
+    /// `auth` the names.
+    fn read_nsfe(&mut self) {
+        let (mut times, mut fades, mut labels) = (Vec::new(), Vec::new(), Vec::new());
+        let (mut start, mut names) = (0, Vec::new());
+        let mut offset = 4;
+        while let (Some(length), Some(id)) =
+            (self.u32_le(offset), self.data.get(offset + 4..offset + 8))
+        {
+            let data = offset + 8;
+            let Some(chunk) = self.data.get(data..data + length as usize) else {
+                break;
+            };
+            match id {
+                b"INFO" => start = chunk.get(9).copied().unwrap_or(0) as usize,
+                b"time" => times = Self::millis(chunk),
+                b"fade" => fades = Self::millis(chunk),
+                b"tlbl" => labels = Self::strings(chunk),
+                b"auth" => names = Self::strings(chunk),
+                b"NEND" => break,
+                _ => {}
+            }
+            offset = data + length as usize;
+        }
+        self.start = start;
+        let mut names = names
+            .into_iter()
+            .map(|name| (!name.is_empty()).then_some(name));
+        self.game = names.next().flatten();
+        self.author = names.next().flatten();
+        let count = times.len().max(labels.len());
+        self.subtunes = (0..count)
+            .map(|i| {
+                let title = labels.get(i).filter(|label| !label.is_empty()).cloned();
+                match times.get(i).copied().flatten() {
+                    Some(play) => Subtune::timed(
+                        title,
+                        play,
+                        fades
+                            .get(i)
+                            .copied()
+                            .flatten()
+                            .unwrap_or(Subtune::DEFAULT_FADE),
+                    ),
+                    None => Subtune::untimed(title),
+                }
+            })
+            .collect();
+    }
+
+    /// SPC: one tune. The ID666 tag (byte 0x23 set to 26) stores the length
+    /// in seconds and the fade in milliseconds, as text or as binary.
+    fn read_spc(&mut self) {
+        let tagged = self.data.get(0x23) == Some(&26);
+        let title = tagged.then(|| self.text(0x2E, 32)).flatten();
+        if !tagged {
+            self.subtunes = vec![Subtune::untimed(None)];
+            return;
+        }
+        self.game = self.text(0x4E, 32);
+        let fields = self.data.get(0xA9..0xB1).unwrap_or_default();
+        let textual = fields.iter().all(|&b| b == 0 || b.is_ascii_digit());
+        let (seconds, fade_ms, artist) = if textual {
+            let number = |range: std::ops::Range<usize>| {
+                self.text(range.start, range.len())
+                    .and_then(|text| text.parse::<u64>().ok())
+            };
+            (number(0xA9..0xAC), number(0xAC..0xB1), 0xB1)
+        } else {
+            let seconds = self
+                .data
+                .get(0xA9..0xAC)
+                .map(|b| u64::from(b[0]) | u64::from(b[1]) << 8 | u64::from(b[2]) << 16);
+            (seconds, self.u32_le(0xAC).map(u64::from), 0xB0)
+        };
+        self.author = self.text(artist, 32);
+        self.subtunes = vec![match seconds.filter(|&s| s > 0) {
+            Some(seconds) => Subtune::timed(
+                title,
+                Duration::from_secs(seconds),
+                fade_ms.map_or(Subtune::DEFAULT_FADE, Duration::from_millis),
+            ),
+            None => Subtune::untimed(title),
+        }];
+    }
+
+    /// VGM: one tune of a sample count at 44.1 kHz; a looping one plays the
+    /// loop twice and fades. The GD3 tag holds UTF-16 names.
+    fn read_vgm(&mut self) {
+        let samples = |offset| {
+            self.u32_le(offset)
+                .map(|n| Duration::from_secs_f64(n as f64 / 44100.0))
+                .unwrap_or_default()
+        };
+        let (total, looped) = (samples(0x18), samples(0x20));
+        let mut names = self
+            .u32_le(0x14)
+            .filter(|&gd3| gd3 > 0)
+            .and_then(|gd3| self.data.get(0x14 + gd3 as usize..))
+            .filter(|tag| tag.starts_with(b"Gd3 "))
+            .map(|tag| Self::utf16_strings(tag.get(12..).unwrap_or_default()))
+            .unwrap_or_default()
+            .into_iter();
+        // English and Japanese of each: track, game, system, author
+        let mut name = |skip| names.nth(skip).filter(|name: &String| !name.is_empty());
+        let title = name(0);
+        self.game = name(1);
+        self.author = name(3);
+        self.subtunes = vec![if total.is_zero() {
+            Subtune::untimed(title)
+        } else if looped.is_zero() {
+            Subtune::timed(title, total, Duration::ZERO)
+        } else {
+            Subtune::timed(title, total + looped, Subtune::DEFAULT_FADE)
+        }];
+    }
+
+    /// GBS: 1-based start song, then title and author in 32-byte fields
+    fn read_gbs(&mut self) {
+        self.start = self
+            .data
+            .get(5)
+            .map_or(0, |&s| s.saturating_sub(1) as usize);
+        self.game = self.text(0x10, 32);
+        self.author = self.text(0x30, 32);
+    }
+
+    fn u16_be(&self, offset: usize) -> Option<u16> {
+        let bytes = self.data.get(offset..offset + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32_le(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Fixed-size text field, cut at the first NUL; `None` when blank
+    fn text(&self, offset: usize, len: usize) -> Option<String> {
+        let field = self.data.get(offset..offset + len)?;
+        let end = field.iter().position(|&b| b == 0).unwrap_or(len);
+        let text = String::from_utf8_lossy(&field[..end]).trim().to_string();
+        (!text.is_empty() && text != "<?>").then_some(text)
+    }
+
+    /// NUL-separated strings
+    fn strings(chunk: &[u8]) -> Vec<String> {
+        chunk
+            .split(|&b| b == 0)
+            .map(|s| String::from_utf8_lossy(s).trim().to_string())
+            .collect()
+    }
+
+    fn utf16_strings(tag: &[u8]) -> Vec<String> {
+        let units: Vec<u16> = tag
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        units
+            .split(|&u| u == 0)
+            .map(|s| String::from_utf16_lossy(s).trim().to_string())
+            .collect()
+    }
+
+    /// Little-endian milliseconds, negative when unknown
+    fn millis(chunk: &[u8]) -> Vec<Option<Duration>> {
+        chunk
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .map(|ms| u64::try_from(ms).ok().map(Duration::from_millis))
+            .collect()
+    }
+}
+
+/// A subtune emulated for its length, fading out at the end. Seeking back
+/// restarts the tune, and any seek renders the audio up to the target:
+/// the emulator only runs forward.
+pub(crate) struct ChiptuneSource {
+    emu: GameMusicEmu,
+    subtune: usize,
+    buffer: Vec<i16>,
+    position: usize,
+    /// Samples of both channels so far, and in all
+    played: u64,
+    length: u64,
+    fade: u64,
+}
+
+impl ChiptuneSource {
+    const CHUNK: usize = 2048;
+
+    fn samples(duration: Duration) -> u64 {
+        (duration.as_secs_f64() * Chiptune::SAMPLE_RATE as f64) as u64 * 2
+    }
+
+    fn fill(&mut self) -> bool {
+        if self.emu.track_ended() || self.emu.play(self.buffer.len(), &mut self.buffer).is_err() {
+            return false;
+        }
+        self.position = 0;
+        true
+    }
+}
+
+impl Iterator for ChiptuneSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.played >= self.length {
+            return None;
+        }
+        if self.position == self.buffer.len() && !self.fill() {
+            return None;
+        }
+        let sample = self.buffer[self.position] as f32 / 32768.0;
+        self.position += 1;
+        self.played += 1;
+        let left = self.length - self.played;
+        Some(if left < self.fade {
+            sample * left as f32 / self.fade as f32
+        } else {
+            sample
+        })
+    }
+}
+
+impl Source for ChiptuneSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        Chiptune::SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f64(
+            self.length as f64 / 2.0 / Chiptune::SAMPLE_RATE as f64,
+        ))
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let target = Self::samples(pos).min(self.length);
+        if target < self.played {
+            self.emu
+                .start_track(self.subtune)
+                .map_err(|e| SeekError::Other(Box::new(e)))?;
+            self.played = 0;
+            self.position = self.buffer.len();
+        }
+        while self.played < target {
+            if self.position == self.buffer.len() && !self.fill() {
+                break;
+            }
+            let skip = (self.buffer.len() - self.position).min((target - self.played) as usize);
+            self.position += skip;
+            self.played += skip as u64;
+        }
+        Ok(())
+    }
+}
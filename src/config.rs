@@ -0,0 +1,94 @@
+//! Startup defaults from `<config>/config.toml`. Every key is optional and
+//! command line flags win over them; without the file nothing changes.
+//!
+//! ```toml
+//! volume = 60
+//! start_dir = "~/Musica"
+//! spectrum_bars = 48
+//! fft_size = 4096
+//! repeat = "all"
+//! theme = "high-contrast"
+//! leader = ";"
+//!
+//! [spectrum]
+//! colors = ["blue", "cyan", "white"]
+//! thresholds = [0.4, 0.8]
+//! gradient = true
+//!
+//! [keys]
+//! "ò" = "n"
+//!
+//! [bindings]
+//! NextTrack = ["l", "<TrackNext>"]
+//! TogglePlayback = "<PlayPause>"
+//! "Seek(-30)" = "H"
+//! ```
+
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use crate::app::config_dir;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Percent, as `--volume`
+    pub(crate) volume: Option<f32>,
+    /// Folder the browser opens in, and root of the library
+    pub(crate) start_dir: Option<PathBuf>,
+    pub(crate) spectrum_bars: Option<usize>,
+    /// Samples per spectrum analysis, a power of two
+    pub(crate) fft_size: Option<usize>,
+    /// `off`, `all` or `one`, until the session journal has its own
+    pub(crate) repeat: Option<String>,
+    /// As `--theme`
+    pub(crate) theme: Option<String>,
+    pub(crate) spectrum: SpectrumConfig,
+    pub(crate) leader: Option<char>,
+    /// Key remaps as in the keymap file, applied over it
+    pub(crate) keys: HashMap<char, char>,
+    /// Action name to the key or keys that trigger it, written as in the
+    /// macros file
+    pub(crate) bindings: HashMap<String, Keys>,
+}
+
+/// One key or a list of them
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Keys {
+    pub(crate) fn into_vec(self) -> Vec<String> {
+        match self {
+            Keys::One(key) => vec![key],
+            Keys::Many(keys) => keys,
+        }
+    }
+}
+
+/// Spectrum colors, as `--spectrum-colors` and the flags after it
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct SpectrumConfig {
+    pub(crate) colors: Option<[String; 3]>,
+    pub(crate) thresholds: Option<[f32; 2]>,
+    pub(crate) gradient: Option<bool>,
+    pub(crate) textured: Option<bool>,
+}
+
+impl Config {
+    /// No file means the defaults; a file that doesn't parse is an error,
+    /// like a bad flag
+    pub(crate) fn load() -> Result<Self, String> {
+        let Some(path) = config_dir().map(|dir| dir.join("config.toml")) else {
+            return Ok(Self::default());
+        };
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Ok(Self::default());
+        };
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
@@ -0,0 +1,411 @@
+//! Control socket: other programs drive the player by writing one command
+//! per line to the Unix socket given with `--control`, e.g.
+//! `echo duck | socat - UNIX-CONNECT:/run/player.sock`. Each command gets
+//! `ok` or `errore: ...` back.
+//!
+//! The same commands can come over TCP from the address given with
+//! `--control-listen`, for a LAN or a tunnel. There a client has to send
+//! `auth TOKEN` first, with the token in the `--control-token-file` file,
+//! and with `--control-cert` and `--control-key` the connection is TLS.
+//! The token in `--control-guest-token-file` lets guests in: they can ask
+//! what is playing and request tracks, which wait in the queue view until
+//! approved from the player.
+
+use crate::app::App;
+use rustls::{
+    ServerConfig, ServerConnection, StreamOwned,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+/// What `--control-listen` and the flags going with it ask for
+#[derive(Clone)]
+pub(crate) struct NetworkAccess {
+    pub(crate) address: String,
+    pub(crate) token: String,
+    pub(crate) guest_token: Option<String>,
+    /// PEM certificate chain and private key, for TLS
+    pub(crate) tls: Option<(PathBuf, PathBuf)>,
+}
+
+/// Leaves the tokens out
+impl std::fmt::Debug for NetworkAccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NetworkAccess")
+            .field("address", &self.address)
+            .field("tls", &self.tls)
+            .finish_non_exhaustive()
+    }
+}
+
+/// How long a wrong token waits for its answer, so tokens can't be tried
+/// quickly
+const AUTH_DELAY: Duration = Duration::from_secs(1);
+
+/// What a client may do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Role {
+    Full,
+    /// Only `status` and `request`, and the requests wait for approval
+    Guest,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ControlCommand {
+    /// Lowers the music for an announcement, until `Unduck` or for the
+    /// given time
+    Duck(Option<Duration>),
+    Unduck,
+    /// Answered from the serving thread, never sent to the app
+    Status,
+    /// Adds a track to the queue
+    Request(PathBuf),
+}
+
+impl ControlCommand {
+    fn parse(line: &str) -> Result<Self, String> {
+        // The path may have spaces in it
+        if let Some(track) = line.trim().strip_prefix("request ") {
+            return Ok(ControlCommand::Request(PathBuf::from(track.trim())));
+        }
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("duck"), None) => Ok(ControlCommand::Duck(None)),
+            (Some("duck"), Some(seconds)) => seconds
+                .parse::<f64>()
+                .ok()
+                .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+                .map(|seconds| ControlCommand::Duck(Some(Duration::from_secs_f64(seconds))))
+                .ok_or_else(|| format!("durata non valida: {}", seconds)),
+            (Some("unduck"), None) => Ok(ControlCommand::Unduck),
+            (Some("status"), None) => Ok(ControlCommand::Status),
+            _ => Err(format!("comando sconosciuto: {}", line.trim())),
+        }
+    }
+
+    fn allowed(&self, role: Role) -> bool {
+        role == Role::Full || matches!(self, ControlCommand::Status | ControlCommand::Request(_))
+    }
+}
+
+/// What the serving threads share with the app
+#[derive(Clone)]
+struct Link {
+    sender: Sender<(Role, ControlCommand)>,
+    /// Answer to `status`, kept up to date by the app
+    now_playing: Arc<Mutex<String>>,
+}
+
+/// Listens on the socket on its own thread, one more per client; the app
+/// takes the commands with `poll`. The socket file is removed on drop.
+pub(crate) struct ControlSocket {
+    /// None for a network listener
+    path: Option<PathBuf>,
+    receiver: Receiver<(Role, ControlCommand)>,
+    now_playing: Arc<Mutex<String>>,
+}
+
+impl ControlSocket {
+    pub(crate) fn bind(path: PathBuf) -> io::Result<Self> {
+        // A socket left behind by a crash would make the bind fail; anything
+        // else at the path is someone's file and stays
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            if !metadata.file_type().is_socket() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} esiste e non è un socket", path.display()),
+                ));
+            }
+            if UnixStream::connect(&path).is_err() {
+                fs::remove_file(&path)?;
+            }
+        }
+        let listener = UnixListener::bind(&path)?;
+        let (link, mut control) = Self::link();
+        thread::spawn(move || {
+            for client in listener.incoming().flatten() {
+                let link = link.clone();
+                thread::spawn(move || Self::serve(client, link, &[]));
+            }
+        });
+        control.path = Some(path);
+        Ok(control)
+    }
+
+    /// Takes clients over TCP, which have to authenticate first
+    pub(crate) fn listen(access: &NetworkAccess) -> io::Result<Self> {
+        let tls = match &access.tls {
+            Some((cert, key)) => Some(Self::tls_config(cert, key)?),
+            None => None,
+        };
+        let listener = TcpListener::bind(&access.address)?;
+        let mut tokens = vec![(Role::Full, access.token.clone())];
+        tokens.extend(access.guest_token.clone().map(|token| (Role::Guest, token)));
+        let tokens: Arc<[(Role, String)]> = tokens.into();
+        let (link, control) = Self::link();
+        thread::spawn(move || {
+            for client in listener.incoming().flatten() {
+                let (link, tokens, tls) = (link.clone(), tokens.clone(), tls.clone());
+                thread::spawn(move || match tls {
+                    Some(config) => {
+                        if let Ok(connection) = ServerConnection::new(config) {
+                            let stream = StreamOwned::new(connection, client);
+                            Self::serve(stream, link, &tokens);
+                        }
+                    }
+                    None => Self::serve(client, link, &tokens),
+                });
+            }
+        });
+        Ok(control)
+    }
+
+    fn link() -> (Link, Self) {
+        let (sender, receiver) = mpsc::channel();
+        let now_playing = Arc::new(Mutex::new(String::new()));
+        let link = Link {
+            sender,
+            now_playing: now_playing.clone(),
+        };
+        let control = Self {
+            path: None,
+            receiver,
+            now_playing,
+        };
+        (link, control)
+    }
+
+    fn tls_config(cert: &Path, key: &Path) -> io::Result<Arc<ServerConfig>> {
+        let invalid = |what: &str, path: &Path, e: &dyn std::fmt::Display| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} {}: {}", what, path.display(), e),
+            )
+        };
+        let certs = CertificateDer::pem_file_iter(cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| invalid("certificato", cert, &e))?;
+        let private_key =
+            PrivateKeyDer::from_pem_file(key).map_err(|e| invalid("chiave", key, &e))?;
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| {
+                builder
+                    .with_no_client_auth()
+                    .with_single_cert(certs, private_key)
+            })
+            .map_err(|e| invalid("certificato", cert, &e))?;
+        Ok(Arc::new(config))
+    }
+
+    /// Answers one client's commands. With `tokens` nothing but `auth` is
+    /// taken until the client sent one of them, which gives it its role;
+    /// without, the client has full control.
+    fn serve(stream: impl Read + Write, link: Link, tokens: &[(Role, String)]) {
+        let mut stream = BufReader::new(stream);
+        let mut role = tokens.is_empty().then_some(Role::Full);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if !matches!(stream.read_line(&mut line), Ok(read) if read > 0) {
+                return;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match (role, line.trim().strip_prefix("auth ")) {
+                (None, Some(given)) => {
+                    // Every token is compared, so the time taken doesn't
+                    // tell which one came close
+                    let matched = tokens
+                        .iter()
+                        .filter(|(_, token)| same_token(token, given))
+                        .map(|(role, _)| *role)
+                        .fold(None, |found, role| found.or(Some(role)));
+                    let Some(matched) = matched else {
+                        thread::sleep(AUTH_DELAY);
+                        let _ = writeln!(stream.get_mut(), "errore: token non valido");
+                        let _ = stream.get_mut().flush();
+                        return;
+                    };
+                    role = Some(matched);
+                    "ok".to_string()
+                }
+                (None, None) => "errore: autenticazione richiesta (auth TOKEN)".to_string(),
+                (Some(role), _) => match ControlCommand::parse(&line) {
+                    Ok(command) if !command.allowed(role) => {
+                        "errore: comando non consentito agli ospiti".to_string()
+                    }
+                    Ok(ControlCommand::Status) => match link.now_playing.lock() {
+                        Ok(now_playing) => format!("ok {}", now_playing),
+                        Err(_) => return,
+                    },
+                    Ok(ControlCommand::Request(track)) if !track.is_file() => {
+                        format!("errore: file non trovato: {}", track.display())
+                    }
+                    Ok(ControlCommand::Request(track)) if !App::is_audio_file(&track) => {
+                        format!("errore: non è un file audio: {}", track.display())
+                    }
+                    Ok(command) => match link.sender.send((role, command)) {
+                        Ok(()) => "ok".to_string(),
+                        Err(_) => return,
+                    },
+                    Err(e) => format!("errore: {}", e),
+                },
+            };
+            let replies = stream.get_mut();
+            if writeln!(replies, "{}", reply).is_err() || replies.flush().is_err() {
+                return;
+            }
+        }
+    }
+
+    pub(crate) fn poll(&self) -> Option<(Role, ControlCommand)> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// What `status` answers from now on
+    pub(crate) fn set_now_playing(&self, text: &str) {
+        if let Ok(mut now_playing) = self.now_playing.lock()
+            && *now_playing != text
+        {
+            *now_playing = text.to_string();
+        }
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Compares every byte whatever the first difference, so the time taken
+/// says nothing about how much of a guess was right
+fn same_token(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("control-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn bind_leaves_other_files_alone() {
+        let path = scratch("notes.txt");
+        fs::write(&path, "da non perdere").unwrap();
+        assert!(ControlSocket::bind(path.clone()).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "da non perdere");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bind_replaces_a_stale_socket() {
+        let path = scratch("stale.sock");
+        drop(UnixListener::bind(&path).unwrap());
+        let control = ControlSocket::bind(path.clone()).unwrap();
+        assert!(UnixStream::connect(&path).is_ok());
+        drop(control);
+        assert!(!path.exists());
+    }
+
+    /// Sends `lines` to a client served with `tokens`, and reads the replies
+    fn talk(tokens: &[(Role, &str)], lines: &str) -> (String, ControlSocket) {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        let (link, control) = ControlSocket::link();
+        control.set_now_playing("in pausa");
+        let tokens: Vec<(Role, String)> = tokens
+            .iter()
+            .map(|(role, token)| (*role, token.to_string()))
+            .collect();
+        let serving = thread::spawn(move || ControlSocket::serve(server, link, &tokens));
+        client.write_all(lines.as_bytes()).unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        serving.join().unwrap();
+        let mut replies = String::new();
+        client.read_to_string(&mut replies).unwrap();
+        (replies, control)
+    }
+
+    const TOKENS: [(Role, &str); 2] = [(Role::Full, "segreto"), (Role::Guest, "ospite")];
+
+    #[test]
+    fn network_clients_authenticate_first() {
+        let (replies, control) = talk(&TOKENS, "duck\nauth segreto\nduck\n");
+        assert_eq!(
+            replies,
+            "errore: autenticazione richiesta (auth TOKEN)\nok\nok\n"
+        );
+        assert_eq!(
+            control.poll(),
+            Some((Role::Full, ControlCommand::Duck(None)))
+        );
+        assert_eq!(control.poll(), None);
+    }
+
+    #[test]
+    fn a_wrong_token_ends_the_connection() {
+        let (replies, control) = talk(&TOKENS, "auth sbagliato\nduck\n");
+        assert_eq!(replies, "errore: token non valido\n");
+        assert_eq!(control.poll(), None);
+    }
+
+    #[test]
+    fn guests_can_only_look_and_request() {
+        let track = scratch("richiesta.mp3");
+        fs::write(&track, []).unwrap();
+        let lines = format!("auth ospite\nduck\nstatus\nrequest {}\n", track.display());
+        let (replies, control) = talk(&TOKENS, &lines);
+        fs::remove_file(&track).unwrap();
+        assert_eq!(
+            replies,
+            "ok\nerrore: comando non consentito agli ospiti\nok in pausa\nok\n"
+        );
+        assert_eq!(
+            control.poll(),
+            Some((Role::Guest, ControlCommand::Request(track)))
+        );
+        assert_eq!(control.poll(), None);
+    }
+
+    #[test]
+    fn requests_must_be_audio_files() {
+        let (replies, control) = talk(&[], "request /nessuna/traccia.mp3\n");
+        assert_eq!(replies, "errore: file non trovato: /nessuna/traccia.mp3\n");
+        assert_eq!(control.poll(), None);
+    }
+
+    #[test]
+    fn tokens_must_match_whole() {
+        assert!(same_token("segreto", "segreto"));
+        assert!(!same_token("segreto", "segret"));
+        assert!(!same_token("segreto", "segreta"));
+    }
+}
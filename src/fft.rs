@@ -0,0 +1,961 @@
+//! Signal analysis: the spectrum shown by the visualizer, tempo, loudness,
+//! album continuity and waveforms.
+
+use lofty::{file::TaggedFileExt, tag::ItemKey};
+use rodio::Source;
+use rustfft::num_complex::Complex;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::app::App;
+use crate::browser::TAG_WRITE_EXTENSIONS;
+use crate::metadata::{TagChange, TagUpdate};
+use crate::player::{AudioPlayer, Biquad};
+
+/// Tempo of a track: its BPM tag when there is one, otherwise estimated from
+/// the autocorrelation of the onset envelope of the first minute. Decodes
+/// audio, so it's meant to run off the UI thread.
+pub(crate) fn detect_bpm(path: &PathBuf) -> Result<f32, Box<dyn std::error::Error>> {
+    let tagged = lofty::read_from_path(path).ok().and_then(|file| {
+        let tag = file.primary_tag().or_else(|| file.first_tag())?;
+        [ItemKey::Bpm, ItemKey::IntegerBpm]
+            .iter()
+            .filter_map(|key| tag.get_string(key))
+            .find_map(|bpm| bpm.trim().parse::<f32>().ok())
+            .filter(|&bpm| bpm > 0.0)
+    });
+    if let Some(bpm) = tagged {
+        return Ok(bpm);
+    }
+
+    const HOP: usize = 256;
+    const RANGE: (f32, f32) = (60.0, 180.0);
+    let source = AudioPlayer::open_source(path)?;
+    let channels = source.channels().max(1) as usize;
+    let sample_rate = source.sample_rate();
+    let limit = sample_rate as usize * 60 * channels;
+
+    // Energy of each hop, then its positive differences as onset strength
+    let mut energies = Vec::new();
+    let mut energy = 0.0f32;
+    for (i, sample) in source.take(limit).enumerate() {
+        energy += sample * sample;
+        if (i + 1) % (HOP * channels) == 0 {
+            energies.push(energy.sqrt());
+            energy = 0.0;
+        }
+    }
+    let onsets: Vec<f32> = energies
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).max(0.0))
+        .collect();
+
+    let hops_per_minute = 60.0 * sample_rate as f32 / HOP as f32;
+    let min_lag = (hops_per_minute / RANGE.1) as usize;
+    let max_lag = (hops_per_minute / RANGE.0) as usize + 1;
+    if onsets.len() < max_lag * 4 {
+        return Err("traccia troppo corta per stimare il tempo".into());
+    }
+    let correlation = |lag: usize| -> f32 {
+        onsets
+            .iter()
+            .zip(&onsets[lag..])
+            .map(|(a, b)| a * b)
+            .sum::<f32>()
+            / (onsets.len() - lag) as f32
+    };
+    let scores: Vec<f32> = (min_lag - 1..=max_lag + 1).map(correlation).collect();
+    // The first of equal peaks: multiples of the beat correlate as well
+    let best =
+        (1..scores.len() - 1).fold(1, |best, i| if scores[i] > scores[best] { i } else { best });
+    if scores[best] <= 0.0 {
+        return Err("tempo non rilevato".into());
+    }
+    // Parabolic fit around the peak for a lag between hops
+    let (left, peak, right) = (scores[best - 1], scores[best], scores[best + 1]);
+    let curvature = left - 2.0 * peak + right;
+    let offset = if curvature < 0.0 {
+        0.5 * (left - right) / curvature
+    } else {
+        0.0
+    };
+    let lag = (min_lag - 1 + best) as f32 + offset;
+    Ok((hops_per_minute / lag * 10.0).round() / 10.0)
+}
+
+/// Integrated loudness (ITU-R BS.1770 / EBU R128 gating) and sample peak of a track
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct LoudnessScan {
+    lufs: f64,
+    peak: f32,
+}
+
+impl LoudnessScan {
+    /// ReplayGain 2.0 reference level
+    const REPLAYGAIN_REFERENCE: f64 = -18.0;
+    /// Reference level of the Opus R128_* tags
+    const R128_REFERENCE: f64 = -23.0;
+
+    /// Decodes the whole file, so it's meant to run off the UI thread
+    pub(crate) fn scan(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        let source = AudioPlayer::open_source(path)?;
+        let channels = source.channels().max(1) as usize;
+        let sample_rate = source.sample_rate();
+
+        // 5.1 layout: LFE is ignored, surrounds weigh +1.5 dB
+        let weights: Vec<f64> = (0..channels)
+            .map(|ch| match (channels, ch) {
+                (6, 3) => 0.0,
+                (6, 4) | (6, 5) => 1.41,
+                _ => 1.0,
+            })
+            .collect();
+        let mut filters = vec![Biquad::k_weighting(sample_rate); channels];
+
+        // Gating blocks are 400 ms with 75% overlap, built from 100 ms steps
+        let step_frames = (sample_rate as usize / 10).max(1);
+        let mut step_power = 0.0;
+        let mut step_pos = 0;
+        let mut steps: VecDeque<f64> = VecDeque::with_capacity(4);
+        let mut blocks = Vec::new();
+        let mut peak = 0.0f32;
+        let mut channel = 0;
+
+        for sample in source {
+            peak = peak.max(sample.abs());
+            let [shelf, high_pass] = &mut filters[channel];
+            let filtered = high_pass.process(shelf.process(sample as f64));
+            step_power += weights[channel] * filtered * filtered;
+
+            channel += 1;
+            if channel == channels {
+                channel = 0;
+                step_pos += 1;
+                if step_pos == step_frames {
+                    if steps.len() == 4 {
+                        steps.pop_front();
+                    }
+                    steps.push_back(step_power / step_frames as f64);
+                    if steps.len() == 4 {
+                        blocks.push(steps.iter().sum::<f64>() / 4.0);
+                    }
+                    step_power = 0.0;
+                    step_pos = 0;
+                }
+            }
+        }
+
+        let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+        let gated_mean = |threshold: f64| {
+            let gated: Vec<f64> = blocks
+                .iter()
+                .copied()
+                .filter(|&p| loudness(p) > threshold)
+                .collect();
+            (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+        };
+
+        let absolute = gated_mean(-70.0).ok_or("traccia troppo breve o silenziosa")?;
+        let integrated = gated_mean(loudness(absolute) - 10.0).unwrap_or(absolute);
+
+        Ok(Self {
+            lufs: loudness(integrated),
+            peak,
+        })
+    }
+
+    fn replaygain(&self) -> f64 {
+        Self::REPLAYGAIN_REFERENCE - self.lufs
+    }
+
+    /// Track gain/peak tags for `path`. Opus files get R128_TRACK_GAIN
+    /// (Q7.8 dB relative to -23 LUFS), as the spec asks players to ignore
+    /// REPLAYGAIN_* there.
+    pub(crate) fn tag_update(&self, path: &Path) -> Result<TagUpdate, Box<dyn std::error::Error>> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        if !TAG_WRITE_EXTENSIONS.contains(&ext.as_str()) {
+            return Err(format!("formato .{} non supportato", ext).into());
+        }
+
+        let items = if ext == "opus" {
+            let gain = ((Self::R128_REFERENCE - self.lufs) * 256.0).round();
+            let gain = gain.clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+            vec![(
+                ItemKey::Unknown("R128_TRACK_GAIN".to_string()),
+                gain.to_string(),
+            )]
+        } else {
+            vec![
+                (
+                    ItemKey::ReplayGainTrackGain,
+                    format!("{:.2} dB", self.replaygain()),
+                ),
+                (ItemKey::ReplayGainTrackPeak, format!("{:.6}", self.peak)),
+            ]
+        };
+
+        Ok(TagUpdate {
+            summary: format!(
+                "{:.1} LUFS: scrivere ReplayGain {:+.2} dB (picco {:.3}) in \"{}\"?",
+                self.lufs,
+                self.replaygain(),
+                self.peak,
+                path.file_name().unwrap_or_default().to_string_lossy()
+            ),
+            files: vec![TagChange {
+                path: path.to_path_buf(),
+                items,
+                picture: None,
+            }],
+        })
+    }
+}
+
+/// Start and end of a decoded track, as needed to judge the transitions
+/// between consecutive album tracks
+struct TrackEdges {
+    name: String,
+    sample_rate: u32,
+    channels: u16,
+    leading_silence: Duration,
+    trailing_silence: Duration,
+    /// First mono frames after the leading silence
+    head: Vec<f32>,
+    /// Last mono frames before the trailing silence
+    tail: Vec<f32>,
+}
+
+impl TrackEdges {
+    /// Level below which a sample counts as digital silence (-60 dBFS)
+    const SILENCE: f32 = 0.001;
+    /// Length of the head and tail kept for comparison
+    const EDGE: Duration = Duration::from_millis(100);
+
+    /// Decodes the whole file, so it's meant to run off the UI thread
+    fn scan(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        let source = AudioPlayer::open_source(path)?;
+        let channels = source.channels().max(1);
+        let sample_rate = source.sample_rate();
+        let edge = (Self::EDGE.as_secs_f64() * sample_rate as f64) as usize;
+
+        let mut leading = 0usize;
+        let mut heard = false;
+        let mut head = Vec::with_capacity(edge);
+        // Audio up to the last loud frame, and the quiet frames since then
+        let mut tail: VecDeque<f32> = VecDeque::with_capacity(edge);
+        let mut quiet: VecDeque<f32> = VecDeque::with_capacity(edge);
+        let mut quiet_frames = 0usize;
+
+        let mut frame = Vec::with_capacity(channels as usize);
+        for sample in source {
+            frame.push(sample);
+            if frame.len() < channels as usize {
+                continue;
+            }
+            let mono = frame.iter().sum::<f32>() / channels as f32;
+            let loud = frame.iter().any(|s| s.abs() > Self::SILENCE);
+            frame.clear();
+
+            if !heard {
+                if !loud {
+                    leading += 1;
+                    continue;
+                }
+                heard = true;
+            }
+            if head.len() < edge {
+                head.push(mono);
+            }
+            if loud {
+                tail.extend(quiet.drain(..));
+                tail.push_back(mono);
+                while tail.len() > edge {
+                    tail.pop_front();
+                }
+                quiet_frames = 0;
+            } else {
+                if quiet.len() == edge {
+                    quiet.pop_front();
+                }
+                quiet.push_back(mono);
+                quiet_frames += 1;
+            }
+        }
+
+        let frames = |count: usize| Duration::from_secs_f64(count as f64 / sample_rate as f64);
+        Ok(Self {
+            name: path
+                .file_stem()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            sample_rate,
+            channels,
+            leading_silence: frames(leading),
+            trailing_silence: frames(quiet_frames),
+            head,
+            tail: tail.into(),
+        })
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        if samples.is_empty() {
+            return 0.0;
+        }
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    /// Normalized correlation of two equally long blocks, 1.0 for identical audio
+    fn correlation(a: &[f32], b: &[f32]) -> f32 {
+        let len = a.len().min(b.len());
+        let (a, b) = (&a[a.len() - len..], &b[..len]);
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let energy =
+            (a.iter().map(|x| x * x).sum::<f32>() * b.iter().map(|y| y * y).sum::<f32>()).sqrt();
+        if energy > 0.0 { dot / energy } else { 0.0 }
+    }
+}
+
+/// One album transition that won't play seamlessly
+pub(crate) struct GapIssue {
+    pub(crate) from: String,
+    pub(crate) to: String,
+    pub(crate) problem: String,
+}
+
+/// Checks the boundaries between consecutive tracks of an album
+pub(crate) struct GapReport {
+    pub(crate) album: String,
+    pub(crate) transitions: usize,
+    pub(crate) issues: Vec<GapIssue>,
+}
+
+impl GapReport {
+    /// Edges louder than this (-30 dBFS) mean the music runs on across the
+    /// boundary; quieter ones are ordinary fade-outs where a pause is fine
+    const CONTINUOUS_LEVEL: f32 = 0.03;
+    /// Silence tolerated inside a continuous transition
+    const MAX_GAP: Duration = Duration::from_millis(20);
+    /// Correlation above which the next track repeats the end of the previous one
+    const OVERLAP_CORRELATION: f32 = 0.95;
+
+    /// Scans `tracks` in play order; decodes everything, so run it off the UI thread
+    pub(crate) fn scan(
+        album: String,
+        tracks: &[PathBuf],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let edges = tracks
+            .iter()
+            .map(TrackEdges::scan)
+            .collect::<Result<Vec<_>, _>>()?;
+        let issues = edges
+            .windows(2)
+            .filter_map(|pair| {
+                Self::check(&pair[0], &pair[1]).map(|problem| GapIssue {
+                    from: pair[0].name.clone(),
+                    to: pair[1].name.clone(),
+                    problem,
+                })
+            })
+            .collect();
+        Ok(Self {
+            album,
+            transitions: edges.len().saturating_sub(1),
+            issues,
+        })
+    }
+
+    fn check(from: &TrackEdges, to: &TrackEdges) -> Option<String> {
+        if (from.sample_rate, from.channels) != (to.sample_rate, to.channels) {
+            return Some(format!(
+                "formato diverso ({} Hz/{} ch → {} Hz/{} ch)",
+                from.sample_rate, from.channels, to.sample_rate, to.channels
+            ));
+        }
+        let continuous = TrackEdges::rms(&from.tail) > Self::CONTINUOUS_LEVEL
+            && TrackEdges::rms(&to.head) > Self::CONTINUOUS_LEVEL;
+        if !continuous {
+            return None;
+        }
+        if TrackEdges::correlation(&from.tail, &to.head) > Self::OVERLAP_CORRELATION {
+            return Some("audio sovrapposto: l'inizio ripete la fine della traccia prima".into());
+        }
+        let gap = from.trailing_silence + to.leading_silence;
+        (gap > Self::MAX_GAP).then(|| {
+            format!(
+                "pausa di {} ms nel brano continuo (padding dell'encoder?)",
+                gap.as_millis()
+            )
+        })
+    }
+}
+
+/// Loudness envelope of a whole file: peak and mean square of every 10 ms
+/// block, all channels together
+#[derive(Clone)]
+pub(crate) struct Waveform {
+    pub(crate) name: String,
+    pub(crate) duration: Duration,
+    blocks: Vec<(f32, f32)>,
+}
+
+impl Waveform {
+    const BLOCK: Duration = Duration::from_millis(10);
+    /// Levels below this (dBFS) draw as nothing
+    pub(crate) const FLOOR_DB: f32 = -60.0;
+
+    /// Decodes the whole file, so it's meant to run off the UI thread.
+    /// Setting `cancel` stops the decode at the next block.
+    pub(crate) fn scan(
+        path: &PathBuf,
+        cancel: &AtomicBool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let source = AudioPlayer::open_source(path)?;
+        let channels = source.channels().max(1) as usize;
+        let sample_rate = source.sample_rate();
+        let block = (Self::BLOCK.as_secs_f64() * sample_rate as f64) as usize * channels;
+
+        let mut blocks = Vec::new();
+        let (mut peak, mut sum, mut count) = (0.0f32, 0.0f32, 0usize);
+        let mut samples = 0u64;
+        for sample in source {
+            peak = peak.max(sample.abs());
+            sum += sample * sample;
+            count += 1;
+            samples += 1;
+            if count == block {
+                blocks.push((peak, sum / count as f32));
+                (peak, sum, count) = (0.0, 0.0, 0);
+                if cancel.load(Ordering::Relaxed) {
+                    return Err("analisi annullata".into());
+                }
+            }
+        }
+        if count > 0 {
+            blocks.push((peak, sum / count as f32));
+        }
+        Ok(Self {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            duration: Duration::from_secs_f64(
+                samples as f64 / channels as f64 / sample_rate as f64,
+            ),
+            blocks,
+        })
+    }
+
+    fn db(level: f32) -> f32 {
+        (20.0 * level.max(1e-6).log10()).max(Self::FLOOR_DB)
+    }
+
+    /// Peak and RMS of the whole file, in dBFS
+    pub(crate) fn levels(&self) -> (f32, f32) {
+        let peak = self.blocks.iter().map(|b| b.0).fold(0.0, f32::max);
+        let mean = self.blocks.iter().map(|b| b.1).sum::<f32>() / self.blocks.len().max(1) as f32;
+        (Self::db(peak), Self::db(mean.sqrt()))
+    }
+
+    /// Peak and RMS in dBFS of the span covered by each of `columns` columns,
+    /// `length` being the time the full width stands for. Columns past the
+    /// end of the file are `None`.
+    pub(crate) fn columns(&self, columns: usize, length: Duration) -> Vec<Option<(f32, f32)>> {
+        let per_column = length.as_secs_f64() / Self::BLOCK.as_secs_f64() / columns as f64;
+        (0..columns)
+            .map(|c| {
+                let start = (c as f64 * per_column) as usize;
+                let end = (((c + 1) as f64 * per_column) as usize).max(start + 1);
+                let span = self.blocks.get(start..end.min(self.blocks.len()))?;
+                if span.is_empty() {
+                    return None;
+                }
+                let peak = span.iter().map(|b| b.0).fold(0.0, f32::max);
+                let mean = span.iter().map(|b| b.1).sum::<f32>() / span.len() as f32;
+                Some((Self::db(peak), Self::db(mean.sqrt())))
+            })
+            .collect()
+    }
+
+    /// Where the sections of the track start, for chaptering live sets and
+    /// long mixes: after every silence of at least `SECTION_SILENCE`, and
+    /// where the loudness of the `SECTION_WINDOW` seconds after a point
+    /// differs from that of the ones before by `SECTION_JUMP_DB` or more (the
+    /// biggest change around). Boundaries closer than `MIN_SECTION` to
+    /// another or to either end of the track are dropped.
+    pub(crate) fn sections(&self) -> Vec<Duration> {
+        const SILENCE_DB: f32 = -45.0;
+        const SECTION_SILENCE: Duration = Duration::from_secs(2);
+        const SECTION_WINDOW: usize = 8;
+        const SECTION_JUMP_DB: f32 = 6.0;
+        const MIN_SECTION: Duration = Duration::from_secs(20);
+
+        let block_time = |block: usize| Self::BLOCK * block as u32;
+        let mut starts = Vec::new();
+
+        let min_silence = SECTION_SILENCE.as_millis() as usize / Self::BLOCK.as_millis() as usize;
+        let mut silent = 0;
+        for (i, &(_, mean_square)) in self.blocks.iter().enumerate() {
+            if Self::db(mean_square.sqrt()) < SILENCE_DB {
+                silent += 1;
+                continue;
+            }
+            if silent >= min_silence {
+                starts.push(block_time(i));
+            }
+            silent = 0;
+        }
+
+        // Loudness of every second, then the change across each second
+        let per_second = (1000 / Self::BLOCK.as_millis()) as usize;
+        let seconds: Vec<f32> = self
+            .blocks
+            .chunks(per_second)
+            .map(|chunk| {
+                let mean = chunk.iter().map(|b| b.1).sum::<f32>() / chunk.len() as f32;
+                Self::db(mean.sqrt())
+            })
+            .collect();
+        let average = |span: &[f32]| span.iter().sum::<f32>() / span.len() as f32;
+        let jumps: Vec<f32> = (0..seconds.len())
+            .map(|i| {
+                if i < SECTION_WINDOW || i + SECTION_WINDOW > seconds.len() {
+                    return 0.0;
+                }
+                let before = average(&seconds[i - SECTION_WINDOW..i]);
+                let after = average(&seconds[i..i + SECTION_WINDOW]);
+                (after - before).abs()
+            })
+            .collect();
+        for (i, &jump) in jumps.iter().enumerate() {
+            let from = i.saturating_sub(SECTION_WINDOW);
+            let around = &jumps[from..(i + SECTION_WINDOW).min(jumps.len())];
+            // The first of equal maxima, so a plateau gives one boundary
+            let largest = around.iter().copied().fold(0.0, f32::max);
+            let first = around.iter().position(|&other| other == largest);
+            if jump >= SECTION_JUMP_DB && jump == largest && first == Some(i - from) {
+                starts.push(Duration::from_secs(i as u64));
+            }
+        }
+
+        // Silences first: a jump around one is the same boundary, less precise
+        let mut sections: Vec<Duration> = Vec::new();
+        for start in starts {
+            let apart = |other: Duration| start.abs_diff(other) >= MIN_SECTION;
+            if apart(Duration::ZERO)
+                && start + MIN_SECTION <= self.duration
+                && sections.iter().all(|&other| apart(other))
+            {
+                sections.push(start);
+            }
+        }
+        sections.sort();
+        sections
+    }
+
+    /// Average difference of the RMS envelopes in dB, block by block over the
+    /// common length: close to zero for a transparent transcode
+    pub(crate) fn envelope_difference(&self, other: &Waveform) -> f32 {
+        let common = self.blocks.len().min(other.blocks.len());
+        if common == 0 {
+            return 0.0;
+        }
+        self.blocks
+            .iter()
+            .zip(&other.blocks)
+            .map(|(a, b)| (Self::db(a.1.sqrt()) - Self::db(b.1.sqrt())).abs())
+            .sum::<f32>()
+            / common as f32
+    }
+}
+
+/// Number of samples per channel in each spectrum analysis, unless
+/// `fft_size` in the config says otherwise
+pub(crate) const FFT_SIZE: usize = 2048;
+
+/// Frequency range covered by the spectrum bars
+const SPECTRUM_MIN_FREQ: f32 = 60.0;
+const SPECTRUM_MAX_FREQ: f32 = 16000.0;
+
+/// How spectrum bars are spread over the frequency range
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum FrequencyScale {
+    Log,
+    Mel,
+    Linear,
+}
+
+impl FrequencyScale {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            FrequencyScale::Log => FrequencyScale::Mel,
+            FrequencyScale::Mel => FrequencyScale::Linear,
+            FrequencyScale::Linear => FrequencyScale::Log,
+        }
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            FrequencyScale::Log => "Log",
+            FrequencyScale::Mel => "Mel",
+            FrequencyScale::Linear => "Lineare",
+        }
+    }
+
+    fn to_mel(freq: f32) -> f32 {
+        2595.0 * (1.0 + freq / 700.0).log10()
+    }
+
+    fn from_mel(mel: f32) -> f32 {
+        700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+    }
+
+    /// Frequency at position `t` (0.0-1.0) along the axis
+    fn frequency_at(&self, t: f32) -> f32 {
+        let (min, max) = (SPECTRUM_MIN_FREQ, SPECTRUM_MAX_FREQ);
+        match self {
+            FrequencyScale::Log => min * (max / min).powf(t),
+            FrequencyScale::Mel => {
+                let (min_mel, max_mel) = (Self::to_mel(min), Self::to_mel(max));
+                Self::from_mel(min_mel + (max_mel - min_mel) * t)
+            }
+            FrequencyScale::Linear => min + (max - min) * t,
+        }
+    }
+
+    /// Frequencies covered by bar `bar` of `bars`, in Hz
+    pub(crate) fn band(&self, bar: usize, bars: usize) -> (f32, f32) {
+        (
+            self.frequency_at(bar as f32 / bars as f32),
+            self.frequency_at((bar + 1) as f32 / bars as f32),
+        )
+    }
+
+    /// Position (0.0-1.0) of `freq` along the axis, inverse of `frequency_at`
+    pub(crate) fn position_of(&self, freq: f32) -> f32 {
+        let (min, max) = (SPECTRUM_MIN_FREQ, SPECTRUM_MAX_FREQ);
+        match self {
+            FrequencyScale::Log => (freq / min).ln() / (max / min).ln(),
+            FrequencyScale::Mel => {
+                let (min_mel, max_mel) = (Self::to_mel(min), Self::to_mel(max));
+                (Self::to_mel(freq) - min_mel) / (max_mel - min_mel)
+            }
+            FrequencyScale::Linear => (freq - min) / (max - min),
+        }
+    }
+}
+
+/// Pink noise reference over the spectrum, to judge the tonal balance of a
+/// track and of the EQ: pink noise has the same energy in every octave,
+/// which makes its bars fall by 3 dB per octave, roughly the average slope
+/// of recorded music
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum TiltOverlay {
+    #[default]
+    Off,
+    /// The pink slope drawn over the bars, at the track's level
+    Reference,
+    /// Each band's distance from the pink slope, in place of the bars
+    Deviation,
+}
+
+impl TiltOverlay {
+    pub(crate) fn next(self) -> Self {
+        match self {
+            TiltOverlay::Off => TiltOverlay::Reference,
+            TiltOverlay::Reference => TiltOverlay::Deviation,
+            TiltOverlay::Deviation => TiltOverlay::Off,
+        }
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            TiltOverlay::Off => "OFF",
+            TiltOverlay::Reference => "Riferimento rosa",
+            TiltOverlay::Deviation => "Scarto dal rosa",
+        }
+    }
+}
+
+/// Slope of pink noise on the bars, which average the bins of their band
+const PINK_SLOPE: f32 = -3.0;
+
+/// The visualizer's spectrum, from the samples the player captures
+impl App {
+    /// Overall level meter: RMS in dBFS mapped from -60..0 dB onto 0..1,
+    /// smoothed with a fast attack and a slow release
+    fn update_energy(&mut self, samples: &[f32]) {
+        let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+        let level = ((20.0 * rms.max(1e-6).log10() + 60.0) / 60.0).clamp(0.0, 1.0);
+        let follow = if level > self.energy { 0.3 } else { 0.05 };
+        self.energy += (level - self.energy) * follow;
+    }
+
+    pub(crate) fn analyze_audio(&mut self) {
+        let started = Instant::now();
+        self.compute_spectrum();
+        self.profiler.record_fft(started.elapsed());
+    }
+
+    fn compute_spectrum(&mut self) {
+        let fft_size = self.fft_size;
+        let channels = if self.stereo_spectrum {
+            self.audio_player.get_channel_samples(fft_size).to_vec()
+        } else {
+            vec![self.audio_player.get_audio_samples(fft_size)]
+        };
+
+        if channels.iter().any(|samples| samples.len() < fft_size) {
+            return;
+        }
+
+        self.update_energy(&channels.concat());
+
+        let bands: Vec<Vec<Option<f32>>> = channels
+            .iter()
+            .map(|samples| self.band_magnitudes(samples))
+            .collect();
+        let max_magnitude = bands
+            .iter()
+            .flatten()
+            .flatten()
+            .fold(0.0f32, |max, &magnitude| max.max(magnitude));
+
+        // Auto gain: follow the loudest band quickly upwards and slowly downwards,
+        // so quiet passages fill the display without every frame hitting the top
+        let follow = if max_magnitude > self.spectrum_peak {
+            0.5
+        } else {
+            0.02
+        };
+        self.spectrum_peak += (max_magnitude - self.spectrum_peak) * follow;
+
+        let normalization_factor = if self.spectrum_peak > 0.0 {
+            1.0 / self.spectrum_peak
+        } else {
+            1.0
+        };
+
+        Self::smooth_bars(&mut self.histogram, &bands[0], normalization_factor);
+        if let Some(right) = bands.get(1) {
+            Self::smooth_bars(&mut self.histogram_right, right, normalization_factor);
+        }
+        self.update_band_levels(&bands[0]);
+    }
+
+    /// Follows the level of each band in dB, slowly enough that the pink
+    /// reference and the deviations hold still
+    fn update_band_levels(&mut self, bands: &[Option<f32>]) {
+        self.band_levels.resize(bands.len(), None);
+        for (level, band) in self.band_levels.iter_mut().zip(bands) {
+            let Some(magnitude) = band else {
+                continue;
+            };
+            let db = 20.0 * magnitude.max(1e-9).log10();
+            *level = Some(match *level {
+                Some(level) => level * 0.9 + db * 0.1,
+                None => db,
+            });
+        }
+    }
+
+    /// Octaves above 1 kHz of the middle of each bar
+    fn band_octaves(&self) -> Vec<f32> {
+        let bars = self.band_levels.len();
+        (0..bars)
+            .map(|bar| {
+                let (low, high) = self.frequency_scale.band(bar, bars);
+                ((low * high).sqrt() / 1000.0).log2()
+            })
+            .collect()
+    }
+
+    /// Level of the pink slope in each band, in dB, placed where it fits
+    /// the track's bands best
+    fn pink_reference(&self) -> Vec<Option<f32>> {
+        let octaves = self.band_octaves();
+        let offsets: Vec<f32> = self
+            .band_levels
+            .iter()
+            .zip(&octaves)
+            .filter_map(|(level, octave)| Some(level.as_ref()? - PINK_SLOPE * octave))
+            .collect();
+        if offsets.is_empty() {
+            return vec![None; octaves.len()];
+        }
+        let offset = offsets.iter().sum::<f32>() / offsets.len() as f32;
+        self.band_levels
+            .iter()
+            .zip(&octaves)
+            .map(|(level, octave)| level.map(|_| offset + PINK_SLOPE * octave))
+            .collect()
+    }
+
+    /// The pink reference as bar heights, for drawing over the bars
+    pub(crate) fn pink_reference_heights(&self) -> Vec<Option<f32>> {
+        let normalization_factor = if self.spectrum_peak > 0.0 {
+            1.0 / self.spectrum_peak
+        } else {
+            1.0
+        };
+        self.pink_reference()
+            .into_iter()
+            .map(|db| db.map(|db| Self::bar_height(10f32.powf(db / 20.0), normalization_factor)))
+            .collect()
+    }
+
+    /// How far each band is above (positive) or below the pink reference, in dB
+    pub(crate) fn pink_deviations(&self) -> Vec<Option<f32>> {
+        self.band_levels
+            .iter()
+            .zip(self.pink_reference())
+            .map(|(level, reference)| Some(level.as_ref()? - reference?))
+            .collect()
+    }
+
+    /// The track's own slope in dB per octave, by least squares; pink
+    /// noise gives `PINK_SLOPE`, brighter tracks less steep
+    pub(crate) fn spectral_slope(&self) -> Option<f32> {
+        let points: Vec<(f32, f32)> = self
+            .band_octaves()
+            .into_iter()
+            .zip(&self.band_levels)
+            .filter_map(|(octave, level)| Some((octave, (*level)?)))
+            .collect();
+        if points.len() < 2 {
+            return None;
+        }
+        let n = points.len() as f32;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f32>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f32>() / n;
+        let covariance: f32 = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+        let variance: f32 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        (variance > 0.0).then(|| covariance / variance)
+    }
+
+    /// Windowed FFT of `samples`, averaged into one magnitude per bar
+    /// (None for bars that cover no FFT bin)
+    fn band_magnitudes(&mut self, samples: &[f32]) -> Vec<Option<f32>> {
+        let fft_size = self.fft_size;
+        let mut buffer: Vec<Complex<f32>> = samples[..fft_size]
+            .iter()
+            .map(|&s| Complex::new(s, 0.0))
+            .collect();
+
+        for (i, sample) in buffer.iter_mut().enumerate() {
+            let window =
+                0.5 * (1.0 - (2.0 * std::f32::consts::PI * i as f32 / fft_size as f32).cos());
+            *sample *= window;
+        }
+
+        let fft = self.fft_planner.plan_fft_forward(fft_size);
+        fft.process(&mut buffer);
+
+        let num_bars = self.histogram.len();
+        let sample_rate = self.audio_player.get_sample_rate() as f32;
+        let freq_per_bin = sample_rate / fft_size as f32;
+
+        (0..num_bars)
+            .map(|i| {
+                let (freq_start, freq_end) = self.frequency_scale.band(i, num_bars);
+
+                let bin_start = (freq_start / freq_per_bin) as usize;
+                let bin_end = ((freq_end / freq_per_bin).min((fft_size / 2) as f32)) as usize;
+
+                let mut magnitude = 0.0;
+                let mut count = 0;
+
+                for bin in bin_start..bin_end {
+                    if bin < buffer.len() {
+                        magnitude += (buffer[bin].re * buffer[bin].re
+                            + buffer[bin].im * buffer[bin].im)
+                            .sqrt();
+                        count += 1;
+                    }
+                }
+
+                (count > 0).then(|| magnitude / count as f32)
+            })
+            .collect()
+    }
+
+    /// Height (0..1) of a bar for a band magnitude
+    fn bar_height(magnitude: f32, normalization_factor: f32) -> f32 {
+        let mut magnitude = magnitude * normalization_factor;
+
+        magnitude *= 0.8;
+
+        magnitude = magnitude.powf(0.7);
+
+        magnitude.clamp(0.0, 1.0)
+    }
+
+    /// Scales band magnitudes into 0..1 and blends them into the displayed bars
+    fn smooth_bars(histogram: &mut [f32], bands: &[Option<f32>], normalization_factor: f32) {
+        for (bar, band) in histogram.iter_mut().zip(bands) {
+            if let Some(magnitude) = band {
+                let magnitude = Self::bar_height(*magnitude, normalization_factor);
+
+                let smoothing = 0.7;
+                *bar = *bar * smoothing + magnitude * (1.0 - smoothing);
+                *bar = bar.clamp(0.05, 0.95);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Waveform of `spans` of (seconds, RMS level) played one after another
+    fn waveform(spans: &[(u32, f32)]) -> Waveform {
+        let per_second = (1000 / Waveform::BLOCK.as_millis()) as usize;
+        let blocks: Vec<(f32, f32)> = spans
+            .iter()
+            .flat_map(|&(seconds, level)| {
+                std::iter::repeat_n((level, level * level), seconds as usize * per_second)
+            })
+            .collect();
+        let seconds = spans.iter().map(|&(seconds, _)| seconds).sum::<u32>();
+        Waveform {
+            name: String::new(),
+            duration: Duration::from_secs(seconds as u64),
+            blocks,
+        }
+    }
+
+    #[test]
+    fn even_track_has_no_sections() {
+        assert!(waveform(&[(180, 0.1)]).sections().is_empty());
+    }
+
+    #[test]
+    fn section_starts_after_a_silence() {
+        let sections = waveform(&[(60, 0.1), (3, 0.0), (60, 0.1)]).sections();
+        assert_eq!(sections, [Duration::from_secs(63)]);
+    }
+
+    #[test]
+    fn section_starts_at_a_jump_in_loudness() {
+        // -40 dBFS, then -20
+        let sections = waveform(&[(60, 0.01), (60, 0.1)]).sections();
+        assert_eq!(sections, [Duration::from_secs(60)]);
+    }
+
+    #[test]
+    fn sections_near_the_ends_are_dropped() {
+        let sections = waveform(&[(10, 0.1), (3, 0.0), (100, 0.1), (3, 0.0), (10, 0.1)]).sections();
+        assert!(sections.is_empty());
+    }
+}
@@ -0,0 +1,726 @@
+//! Listening history and what is derived from it: the heatmap, play
+//! statistics imported from other players and the daily mix.
+
+use chrono::{Datelike, Timelike};
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::app::{App, Rng, data_dir};
+use crate::browser::TrackTags;
+
+/// Seconds since the epoch as ISO 8601 local time, with the UTC offset
+fn format_timestamp(secs: i64) -> String {
+    chrono::DateTime::from_timestamp(secs, 0)
+        .map(|at| {
+            at.with_timezone(&chrono::Local)
+                .format("%Y-%m-%dT%H:%M:%S%:z")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+pub(crate) fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// One play of a track
+#[derive(Clone, Debug)]
+pub(crate) struct HistoryEntry {
+    /// Seconds since the epoch
+    played_at: i64,
+    path: PathBuf,
+    /// Length of the track, when known
+    duration: Option<Duration>,
+}
+
+/// Export formats for the listening history
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// Listening history, appended to `<data>/history.tsv` as
+/// `played_at<TAB>duration_secs<TAB>path` lines. Skips go in the same file
+/// as `skip<TAB>at<TAB>path` lines, which `entries` passes over.
+pub(crate) struct PlayHistory {
+    file: Option<PathBuf>,
+}
+
+impl PlayHistory {
+    pub(crate) fn open() -> Self {
+        Self {
+            file: data_dir().map(|dir| dir.join("history.tsv")),
+        }
+    }
+
+    /// Times `path` was played in this player
+    pub(crate) fn play_count(&self, path: &Path) -> usize {
+        self.entries(None, None)
+            .map(|entries| entries.iter().filter(|e| e.path == path).count())
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn record(&self, path: &Path, duration: Option<Duration>) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut out = OpenOptions::new().create(true).append(true).open(file)?;
+        writeln!(
+            out,
+            "{}\t{}\t{}",
+            unix_now(),
+            duration
+                .map(|d| d.as_secs().to_string())
+                .unwrap_or_default(),
+            path.display()
+        )
+    }
+
+    pub(crate) fn record_skip(&self, path: &Path) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut out = OpenOptions::new().create(true).append(true).open(file)?;
+        writeln!(out, "skip\t{}\t{}", unix_now(), path.display())
+    }
+
+    /// Times each track was skipped
+    pub(crate) fn skip_counts(&self) -> io::Result<HashMap<PathBuf, u32>> {
+        let mut counts = HashMap::new();
+        for path in self.read()?.lines().filter_map(Self::skip_path) {
+            *counts.entry(PathBuf::from(path)).or_default() += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Forgets every skip, keeping the plays
+    pub(crate) fn reset_skips(&self) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let content: String = self
+            .read()?
+            .lines()
+            .filter(|line| Self::skip_path(line).is_none())
+            .map(|line| format!("{}\n", line))
+            .collect();
+        fs::write(file, content)
+    }
+
+    fn skip_path(line: &str) -> Option<&str> {
+        let mut fields = line.splitn(3, '\t');
+        (fields.next()? == "skip").then_some(())?;
+        fields.nth(1)
+    }
+
+    /// The whole file; a missing one is empty
+    fn read(&self) -> io::Result<String> {
+        let Some(file) = &self.file else {
+            return Ok(String::new());
+        };
+        match fs::read_to_string(file) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Entries played in `from..to` (seconds since the epoch), oldest first.
+    /// Malformed lines are skipped.
+    pub(crate) fn entries(
+        &self,
+        from: Option<i64>,
+        to: Option<i64>,
+    ) -> io::Result<Vec<HistoryEntry>> {
+        Ok(self
+            .read()?
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let played_at: i64 = fields.next()?.parse().ok()?;
+                let duration = fields.next()?.parse().ok().map(Duration::from_secs);
+                let path = PathBuf::from(fields.next()?);
+                Some(HistoryEntry {
+                    played_at,
+                    path,
+                    duration,
+                })
+            })
+            .filter(|e| from.is_none_or(|from| e.played_at >= from))
+            .filter(|e| to.is_none_or(|to| e.played_at < to))
+            .collect())
+    }
+
+    /// Writes the history and per-track statistics to `output`.
+    /// CSV has one row per play with the track's play count in the range;
+    /// JSON has the plays and the statistics as separate arrays.
+    pub(crate) fn export(
+        &self,
+        output: &Path,
+        format: ExportFormat,
+        from: Option<i64>,
+        to: Option<i64>,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let entries = self.entries(from, to)?;
+        let mut play_counts: HashMap<&Path, usize> = HashMap::new();
+        for entry in &entries {
+            *play_counts.entry(&entry.path).or_default() += 1;
+        }
+
+        let content = match format {
+            ExportFormat::Csv => {
+                let quote = |field: &str| format!("\"{}\"", field.replace('"', "\"\""));
+                let mut csv = String::from("played_at,path,duration_s,play_count\n");
+                for entry in &entries {
+                    csv += &format!(
+                        "{},{},{},{}\n",
+                        format_timestamp(entry.played_at),
+                        quote(&entry.path.display().to_string()),
+                        entry
+                            .duration
+                            .map(|d| d.as_secs().to_string())
+                            .unwrap_or_default(),
+                        play_counts[entry.path.as_path()]
+                    );
+                }
+                csv
+            }
+            ExportFormat::Json => {
+                let plays: Vec<_> = entries
+                    .iter()
+                    .map(|entry| {
+                        serde_json::json!({
+                            "played_at": format_timestamp(entry.played_at),
+                            "path": entry.path.display().to_string(),
+                            "duration_s": entry.duration.map(|d| d.as_secs()),
+                        })
+                    })
+                    .collect();
+                let mut statistics: Vec<_> = play_counts.iter().collect();
+                statistics.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+                let statistics: Vec<_> = statistics
+                    .into_iter()
+                    .map(|(path, plays)| {
+                        serde_json::json!({
+                            "path": path.display().to_string(),
+                            "plays": plays,
+                        })
+                    })
+                    .collect();
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "from": from.map(format_timestamp),
+                    "to": to.map(format_timestamp),
+                    "plays": plays,
+                    "statistics": statistics,
+                }))?
+            }
+        };
+        fs::write(output, content)?;
+        Ok(entries.len())
+    }
+}
+
+/// Listening time by day of the week and hour of the day (local time), from
+/// the history. A play counts its whole length at the hour it started.
+pub(crate) struct ListeningHeatmap {
+    /// Monday first
+    pub(crate) time: [[Duration; 24]; 7],
+    pub(crate) plays: usize,
+}
+
+impl ListeningHeatmap {
+    pub(crate) const DAYS: [&str; 7] = ["Lun", "Mar", "Mer", "Gio", "Ven", "Sab", "Dom"];
+
+    pub(crate) fn from_history(entries: &[HistoryEntry]) -> Self {
+        let mut time = [[Duration::ZERO; 24]; 7];
+        for entry in entries {
+            let Some(at) = chrono::DateTime::from_timestamp(entry.played_at, 0) else {
+                continue;
+            };
+            let at = at.with_timezone(&chrono::Local);
+            let day = at.weekday().num_days_from_monday() as usize;
+            time[day][at.hour() as usize] += entry.duration.unwrap_or_default();
+        }
+        Self {
+            time,
+            plays: entries.len(),
+        }
+    }
+
+    pub(crate) fn total(&self) -> Duration {
+        self.time.iter().flatten().sum()
+    }
+
+    /// Busiest (day, hour), if anything was played at all
+    pub(crate) fn peak(&self) -> Option<(usize, usize)> {
+        (0..7)
+            .flat_map(|day| (0..24).map(move |hour| (day, hour)))
+            .max_by_key(|&(day, hour)| self.time[day][hour])
+            .filter(|&(day, hour)| !self.time[day][hour].is_zero())
+    }
+}
+
+/// Play counts and ratings imported from other players, kept in
+/// `<data>/stats.tsv` as `plays<TAB>rating<TAB>path` lines. Ratings are 0-100.
+/// Plays made in this player are counted from the history instead.
+#[derive(Default)]
+pub(crate) struct TrackStats {
+    file: Option<PathBuf>,
+    stats: HashMap<PathBuf, (u32, Option<u8>)>,
+}
+
+impl TrackStats {
+    pub(crate) fn load() -> Self {
+        let file = data_dir().map(|dir| dir.join("stats.tsv"));
+        let stats = file
+            .as_ref()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| {
+                        let mut fields = line.splitn(3, '\t');
+                        let plays = fields.next()?.parse().ok()?;
+                        let rating = fields.next()?.parse().ok();
+                        Some((PathBuf::from(fields.next()?), (plays, rating)))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { file, stats }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut entries: Vec<_> = self.stats.iter().collect();
+        entries.sort();
+        let content: String = entries
+            .into_iter()
+            .map(|(path, (plays, rating))| {
+                format!(
+                    "{}\t{}\t{}\n",
+                    plays,
+                    rating.map(|r| r.to_string()).unwrap_or_default(),
+                    path.display()
+                )
+            })
+            .collect();
+        fs::write(file, content)
+    }
+
+    pub(crate) fn get(&self, path: &Path) -> (u32, Option<u8>) {
+        self.stats.get(path).copied().unwrap_or((0, None))
+    }
+
+    /// Keeps the highest play count, so importing the same export twice
+    /// doesn't double it; a rating already set is not overwritten
+    fn merge(&mut self, path: PathBuf, plays: u32, rating: Option<u8>) {
+        let entry = self.stats.entry(path).or_insert((0, None));
+        entry.0 = entry.0.max(plays);
+        entry.1 = entry.1.or(rating);
+    }
+}
+
+/// Play count/rating of one track, as exported by another player
+#[derive(Debug, Default)]
+struct ImportedStat {
+    /// Absolute, or relative to the other player's music folder
+    path: Option<PathBuf>,
+    artist: Option<String>,
+    title: Option<String>,
+    plays: u32,
+    /// 0-100
+    rating: Option<u8>,
+}
+
+/// Players whose statistics can be imported
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ImportSource {
+    /// iTunes/Music "Library.xml"
+    ITunes,
+    /// Dump of MPD's sticker table:
+    /// `sqlite3 sticker.sql "select uri,name,value from sticker where type='song'"`
+    Mpd,
+    /// Text export from foobar2000 (e.g. Copy Command with
+    /// `%path%|%play_count%|%rating%`), `|` or tab separated
+    Foobar,
+}
+
+impl ImportSource {
+    pub(crate) fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "itunes" => Some(ImportSource::ITunes),
+            "mpd" => Some(ImportSource::Mpd),
+            "foobar" | "foobar2000" => Some(ImportSource::Foobar),
+            _ => None,
+        }
+    }
+
+    fn read(self, file: &Path) -> io::Result<Vec<ImportedStat>> {
+        let content = fs::read_to_string(file)?;
+        Ok(match self {
+            ImportSource::ITunes => Self::read_itunes(&content),
+            ImportSource::Mpd => Self::read_mpd(&content),
+            ImportSource::Foobar => Self::read_foobar(&content),
+        })
+    }
+
+    fn read_itunes(content: &str) -> Vec<ImportedStat> {
+        // Value of `<key>name</key><type>value</type>` inside one track's dict
+        fn value<'a>(dict: &'a str, name: &str) -> Option<&'a str> {
+            let key = format!("<key>{}</key>", name);
+            let rest = &dict[dict.find(&key)? + key.len()..];
+            let start = rest.find('>')? + 1;
+            let end = start + rest[start..].find('<')?;
+            Some(&rest[start..end])
+        }
+
+        content
+            .split("<key>Track ID</key>")
+            .skip(1)
+            .filter_map(|dict| {
+                let location = value(dict, "Location")?;
+                let path = location
+                    .strip_prefix("file://localhost")
+                    .or_else(|| location.strip_prefix("file://"))?;
+                Some(ImportedStat {
+                    path: Some(PathBuf::from(percent_decode(&xml_unescape(path)))),
+                    artist: value(dict, "Artist").map(xml_unescape),
+                    title: value(dict, "Name").map(xml_unescape),
+                    plays: value(dict, "Play Count")
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(0),
+                    rating: value(dict, "Rating")
+                        .and_then(|v| v.parse::<u8>().ok())
+                        .map(|r| r.min(100)),
+                })
+            })
+            .collect()
+    }
+
+    fn read_mpd(content: &str) -> Vec<ImportedStat> {
+        let mut by_uri: HashMap<&str, ImportedStat> = HashMap::new();
+        for line in content.lines() {
+            let mut fields = line.splitn(3, '|');
+            let (Some(uri), Some(name), Some(value)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let stat = by_uri.entry(uri).or_insert_with(|| ImportedStat {
+                path: Some(PathBuf::from(uri)),
+                ..Default::default()
+            });
+            match name.to_lowercase().as_str() {
+                // MPD clients rate 0-10
+                "rating" => stat.rating = value.parse::<u8>().ok().map(|r| r.min(10) * 10),
+                "playcount" | "play_count" => stat.plays = value.parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+        by_uri.into_values().collect()
+    }
+
+    fn read_foobar(content: &str) -> Vec<ImportedStat> {
+        content
+            .lines()
+            .filter_map(|line| {
+                let separator = if line.contains('\t') { '\t' } else { '|' };
+                let mut fields = line.split(separator).map(str::trim);
+                let path = fields.next().filter(|p| !p.is_empty())?;
+                // A header line has no numeric play count
+                let plays = fields.next()?.parse().ok()?;
+                // foobar2000 rates 1-5 ("?" when unrated)
+                let rating = fields
+                    .next()
+                    .and_then(|r| r.parse::<u8>().ok())
+                    .map(|r| r.min(5) * 20);
+                Some(ImportedStat {
+                    path: Some(PathBuf::from(path)),
+                    plays,
+                    rating,
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Decodes `%XX` escapes of a file URL path
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Audio files under the music root, used to map imported entries to the
+/// local library when their paths don't exist here
+pub(crate) struct LibraryIndex {
+    by_name: HashMap<std::ffi::OsString, Vec<PathBuf>>,
+}
+
+impl LibraryIndex {
+    pub(crate) const MAX_DEPTH: usize = 8;
+
+    fn build(root: &Path) -> Self {
+        let mut files = Vec::new();
+        Self::walk(root, Self::MAX_DEPTH, &mut files);
+        let mut by_name: HashMap<std::ffi::OsString, Vec<PathBuf>> = HashMap::new();
+        for file in files {
+            if let Some(name) = file.file_name() {
+                by_name.entry(name.to_os_string()).or_default().push(file);
+            }
+        }
+        Self { by_name }
+    }
+
+    pub(crate) fn walk(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.is_dir() {
+                if depth > 0 {
+                    Self::walk(&path, depth - 1, files);
+                }
+            } else if App::is_audio_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    /// The path itself if it exists, else the library file with the same name
+    /// sharing the most trailing folders with it, else (without a usable path)
+    /// a file whose artist/title tags match
+    fn resolve(&self, stat: &ImportedStat) -> Option<PathBuf> {
+        if let Some(path) = &stat.path {
+            if path.is_absolute() && path.is_file() {
+                return Some(path.clone());
+            }
+            if let Some(candidates) = path.file_name().and_then(|n| self.by_name.get(n)) {
+                let shared = |candidate: &PathBuf| {
+                    candidate
+                        .components()
+                        .rev()
+                        .zip(path.components().rev())
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                };
+                return candidates.iter().max_by_key(|c| shared(c)).cloned();
+            }
+        }
+
+        let title = stat.title.as_ref()?.to_lowercase();
+        let artist = stat.artist.as_ref().map(|a| a.to_lowercase());
+        self.by_name
+            .iter()
+            .filter(|(name, _)| name.to_string_lossy().to_lowercase().contains(&title))
+            .flat_map(|(_, paths)| paths)
+            .find(|path| {
+                let tags = TrackTags::read(path);
+                tags.title.is_some_and(|t| t.to_lowercase() == title)
+                    && (artist.is_none() || tags.artist.map(|a| a.to_lowercase()) == artist)
+            })
+            .cloned()
+    }
+}
+
+/// Imports `file` into the stats store; returns (matched, unmatched) entries
+pub(crate) fn import_stats(
+    source: ImportSource,
+    file: &Path,
+    music_root: &Path,
+) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    let imported = source.read(file)?;
+    if imported.is_empty() {
+        return Err("nessuna voce riconosciuta nel file".into());
+    }
+    let library = LibraryIndex::build(music_root);
+    let mut stats = TrackStats::load();
+    let mut matched = 0;
+    for stat in &imported {
+        if stat.plays == 0 && stat.rating.is_none() {
+            continue;
+        }
+        if let Some(path) = library.resolve(stat) {
+            stats.merge(path, stat.plays, stat.rating);
+            matched += 1;
+        }
+    }
+    stats.save()?;
+    Ok((matched, imported.len() - matched))
+}
+
+/// Playlist rebuilt once a day from the library under the music root:
+/// favourites (rated or often played, less skipped), tracks added in the
+/// last month and tracks not heard for months, taken in turns. It is saved as
+/// `<data>/Daily Mix.m3u` with the day on a `#DAILYMIX:` line, so it lists
+/// and opens like any other playlist.
+pub(crate) struct DailyMix;
+
+impl DailyMix {
+    pub(crate) const NAME: &str = "Daily Mix";
+    const LENGTH: usize = 30;
+    /// Files modified this recently count as newly added
+    const RECENT: Duration = Duration::from_secs(30 * 86_400);
+    /// Tracks last played longer ago than this (in seconds) are forgotten
+    const FORGOTTEN: i64 = 90 * 86_400;
+    /// Forgotten tracks skipped this often were left out on purpose
+    const SKIPPED_OUT: u32 = 3;
+
+    pub(crate) fn path() -> Option<PathBuf> {
+        Some(data_dir()?.join(format!("{}.m3u", Self::NAME)))
+    }
+
+    /// Local date as `AAAA-MM-GG`
+    fn today() -> String {
+        let today = chrono::Local::now().date_naive();
+        format!(
+            "{:04}-{:02}-{:02}",
+            today.year(),
+            today.month(),
+            today.day()
+        )
+    }
+
+    /// Whether the saved mix was built today
+    pub(crate) fn is_current(path: &Path) -> bool {
+        let marker = format!("#DAILYMIX:{}", Self::today());
+        fs::read_to_string(path).is_ok_and(|content| content.lines().any(|line| line == marker))
+    }
+
+    /// Builds today's mix and saves it; the same day always gives the same mix
+    pub(crate) fn build(music_root: &Path, path: &Path) -> io::Result<usize> {
+        let mut files = Vec::new();
+        LibraryIndex::walk(music_root, LibraryIndex::MAX_DEPTH, &mut files);
+
+        let mut plays: HashMap<PathBuf, u32> = HashMap::new();
+        let mut last_played: HashMap<PathBuf, i64> = HashMap::new();
+        for entry in PlayHistory::open().entries(None, None)? {
+            *plays.entry(entry.path.clone()).or_default() += 1;
+            let last = last_played.entry(entry.path).or_default();
+            *last = (*last).max(entry.played_at);
+        }
+        let skips = PlayHistory::open().skip_counts()?;
+        let skipped = |file: &PathBuf| skips.get(file).copied().unwrap_or(0);
+        let stats = TrackStats::load();
+        // A skip outweighs a play: the track was started, and not wanted
+        let score = |file: &PathBuf| {
+            let (imported, rating) = stats.get(file);
+            let played = plays.get(file).copied().unwrap_or(0) + imported;
+            (rating.unwrap_or(0) as u32 * 10 + played).saturating_sub(2 * skipped(file))
+        };
+
+        let mut favourites: Vec<PathBuf> = files.iter().filter(|f| score(f) > 0).cloned().collect();
+        favourites.sort_by_key(|f| std::cmp::Reverse(score(f)));
+        favourites.truncate(Self::LENGTH * 3);
+
+        let added = |file: &PathBuf| {
+            fs::metadata(file)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+        };
+        let mut recent: Vec<(Duration, PathBuf)> = files
+            .iter()
+            .filter_map(|f| {
+                added(f)
+                    .filter(|age| *age < Self::RECENT)
+                    .map(|age| (age, f.clone()))
+            })
+            .collect();
+        recent.sort();
+        let mut recent: Vec<PathBuf> = recent
+            .into_iter()
+            .take(Self::LENGTH * 3)
+            .map(|(_, f)| f)
+            .collect();
+
+        let forgotten_before = unix_now() - Self::FORGOTTEN;
+        let mut forgotten: Vec<PathBuf> = files
+            .iter()
+            .filter(|f| last_played.get(*f).is_none_or(|&at| at < forgotten_before))
+            .filter(|f| skipped(f) < Self::SKIPPED_OUT)
+            .cloned()
+            .collect();
+
+        let day = chrono::Local::now().date_naive().num_days_from_ce() as u64;
+        let mut rng = Rng::seeded(day.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        for pool in [&mut favourites, &mut recent, &mut forgotten] {
+            for i in (1..pool.len()).rev() {
+                pool.swap(i, rng.below(i + 1));
+            }
+        }
+
+        let mut mix: Vec<PathBuf> = Vec::new();
+        let mut pools = [
+            favourites.into_iter(),
+            recent.into_iter(),
+            forgotten.into_iter(),
+        ];
+        let mut exhausted = 0;
+        while mix.len() < Self::LENGTH && exhausted < pools.len() {
+            exhausted = 0;
+            for pool in pools.iter_mut() {
+                match pool.find(|f| !mix.contains(f)) {
+                    Some(file) if mix.len() < Self::LENGTH => mix.push(file),
+                    Some(_) => {}
+                    None => exhausted += 1,
+                }
+            }
+        }
+
+        let mut content = format!("#EXTM3U\n#DAILYMIX:{}\n", Self::today());
+        for file in &mix {
+            content += &format!("{}\n", file.display());
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, content)?;
+        Ok(mix.len())
+    }
+}
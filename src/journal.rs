@@ -0,0 +1,331 @@
+//! The session journal, replayed at startup, and the named snapshots of a
+//! session.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crate::app::{DeviceProfile, RepeatMode, data_dir};
+use crate::player::EQ_PRESETS;
+
+/// Playback session as rebuilt from the journal
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SessionState {
+    pub(crate) dir: Option<PathBuf>,
+    pub(crate) track: Option<PathBuf>,
+    pub(crate) position: Duration,
+    /// The track was still playing at the last record
+    pub(crate) playing: bool,
+    /// None until the mode is first changed, so the config's default holds
+    pub(crate) repeat: Option<RepeatMode>,
+}
+
+impl SessionState {
+    /// Shortest list of records rebuilding this state
+    fn records(&self) -> Vec<JournalRecord> {
+        let mut records = Vec::new();
+        records.extend(self.dir.clone().map(JournalRecord::Dir));
+        if let Some(track) = &self.track {
+            records.push(JournalRecord::Track(track.clone()));
+            records.push(JournalRecord::Position(self.position));
+            if !self.playing {
+                records.push(JournalRecord::Stop);
+            }
+        }
+        records.extend(self.repeat.map(JournalRecord::Repeat));
+        records
+    }
+}
+
+/// One change to the session, as written to the journal
+#[derive(Clone, Debug)]
+pub(crate) enum JournalRecord {
+    Dir(PathBuf),
+    Track(PathBuf),
+    Position(Duration),
+    Stop,
+    Repeat(RepeatMode),
+}
+
+impl JournalRecord {
+    fn encode(&self) -> String {
+        match self {
+            JournalRecord::Dir(path) => format!("dir\t{}", path.display()),
+            JournalRecord::Track(path) => format!("track\t{}", path.display()),
+            JournalRecord::Position(position) => format!("pos\t{}", position.as_millis()),
+            JournalRecord::Stop => "stop".to_string(),
+            JournalRecord::Repeat(mode) => format!("repeat\t{}", mode.key()),
+        }
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        let (kind, value) = line.split_once('\t').unwrap_or((line, ""));
+        match kind {
+            "dir" => Some(JournalRecord::Dir(PathBuf::from(value))),
+            "track" => Some(JournalRecord::Track(PathBuf::from(value))),
+            "pos" => value
+                .parse()
+                .ok()
+                .map(|ms| JournalRecord::Position(Duration::from_millis(ms))),
+            "stop" => Some(JournalRecord::Stop),
+            "repeat" => RepeatMode::parse(value).map(JournalRecord::Repeat),
+            // Journals written before the repeat modes
+            "continuous" => Some(JournalRecord::Repeat(if value == "1" {
+                RepeatMode::RepeatAll
+            } else {
+                RepeatMode::Off
+            })),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, state: &mut SessionState) {
+        match self {
+            JournalRecord::Dir(path) => state.dir = Some(path.clone()),
+            JournalRecord::Track(path) => {
+                state.track = Some(path.clone());
+                state.position = Duration::ZERO;
+                state.playing = true;
+            }
+            JournalRecord::Position(position) => state.position = *position,
+            JournalRecord::Stop => state.playing = false,
+            JournalRecord::Repeat(mode) => state.repeat = Some(*mode),
+        }
+    }
+}
+
+/// Append-only journal of session changes in `<data>/session.journal`, so the
+/// session survives crashes and power loss, not just clean exits. Records are
+/// fsynced every few seconds; on startup the journal is replayed (a torn last
+/// line is ignored) and atomically compacted to a snapshot of the state.
+pub(crate) struct SessionJournal {
+    path: Option<PathBuf>,
+    file: Option<File>,
+    state: SessionState,
+    records: usize,
+    last_sync: Instant,
+    dirty: bool,
+}
+
+impl SessionJournal {
+    const SYNC_INTERVAL: Duration = Duration::from_secs(5);
+    /// Records after which the journal is rewritten as a snapshot
+    const COMPACT_AFTER: usize = 5000;
+
+    /// Opens the journal, returning it with the recovered session.
+    /// Journal errors never stop the player: it just runs without one.
+    pub(crate) fn open() -> (Self, SessionState) {
+        let path = data_dir().map(|dir| dir.join("session.journal"));
+        let mut state = SessionState::default();
+        if let Some(content) = path.as_ref().and_then(|p| fs::read_to_string(p).ok()) {
+            let complete = match content.rfind('\n') {
+                Some(end) => &content[..end],
+                None => "",
+            };
+            for record in complete.lines().filter_map(JournalRecord::decode) {
+                record.apply(&mut state);
+            }
+        }
+
+        let mut journal = Self {
+            path,
+            file: None,
+            state: state.clone(),
+            records: 0,
+            last_sync: Instant::now(),
+            dirty: false,
+        };
+        let _ = journal.compact();
+        (journal, state)
+    }
+
+    /// Rewrites the journal as a snapshot: written to a temporary file,
+    /// synced, then renamed over the old one
+    fn compact(&mut self) -> io::Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let records = self.state.records();
+        let tmp = path.with_extension("journal.tmp");
+        let mut file = File::create(&tmp)?;
+        for record in &records {
+            writeln!(file, "{}", record.encode())?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+
+        self.file = Some(OpenOptions::new().append(true).open(&path)?);
+        self.records = records.len();
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub(crate) fn record(&mut self, record: JournalRecord) {
+        record.apply(&mut self.state);
+        if let Some(file) = &mut self.file
+            && writeln!(file, "{}", record.encode()).is_err()
+        {
+            self.file = None;
+        }
+        self.records += 1;
+        self.dirty = true;
+        if self.records > Self::COMPACT_AFTER {
+            let _ = self.compact();
+        }
+    }
+
+    /// Called regularly: flushes to disk at most every `SYNC_INTERVAL`
+    pub(crate) fn sync(&mut self) {
+        if self.dirty && self.last_sync.elapsed() >= Self::SYNC_INTERVAL {
+            if let Some(file) = &self.file {
+                let _ = file.sync_data();
+            }
+            self.dirty = false;
+            self.last_sync = Instant::now();
+        }
+    }
+}
+
+impl Drop for SessionJournal {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            let _ = file.sync_data();
+        }
+    }
+}
+
+/// Player state saved under a name in `<data>/snapshots/<name>.session`:
+/// the journal's session records, plus the highlighted row, the A–B loop,
+/// shuffle, the queue, volume and EQ preset. Restoring one opens it as a
+/// session tab.
+pub(crate) struct Snapshot {
+    pub(crate) session: SessionState,
+    pub(crate) highlighted: Option<usize>,
+    pub(crate) ab_loop: Option<(Duration, Option<Duration>)>,
+    pub(crate) shuffle: bool,
+    /// Tracks still waiting in the queue, in order
+    pub(crate) queue: Vec<PathBuf>,
+    pub(crate) profile: DeviceProfile,
+}
+
+impl Snapshot {
+    fn dir() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join("snapshots"))
+    }
+
+    /// Names of the saved snapshots, sorted
+    fn names() -> Vec<String> {
+        let mut names: Vec<String> = Self::dir()
+            .and_then(|dir| fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                (path.extension()? == "session")
+                    .then(|| path.file_stem()?.to_str().map(str::to_string))
+                    .flatten()
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// File of the snapshot; the name can't leave the snapshots folder
+    fn path(name: &str) -> Result<PathBuf, String> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(format!("nome non valido: {}", name));
+        }
+        let dir = Self::dir().ok_or("cartella dati non disponibile")?;
+        Ok(dir.join(format!("{}.session", name)))
+    }
+
+    pub(crate) fn save(&self, name: &str) -> Result<(), String> {
+        let path = Self::path(name)?;
+        let mut content: String = self
+            .session
+            .records()
+            .iter()
+            .map(|record| format!("{}\n", record.encode()))
+            .collect();
+        if let Some(row) = self.highlighted {
+            content.push_str(&format!("highlight\t{}\n", row));
+        }
+        if let Some((a, b)) = self.ab_loop {
+            content.push_str(&format!("loop\t{}", a.as_millis()));
+            if let Some(b) = b {
+                content.push_str(&format!("\t{}", b.as_millis()));
+            }
+            content.push('\n');
+        }
+        content.push_str(&format!("shuffle\t{}\n", self.shuffle as u8));
+        for track in &self.queue {
+            content.push_str(&format!("queue\t{}\n", track.display()));
+        }
+        content.push_str(&format!(
+            "volume\t{:.2}\neq\t{}\n",
+            self.profile.volume, self.profile.eq
+        ));
+
+        let write = || -> io::Result<()> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&path, content)
+        };
+        write().map_err(|e| e.to_string())
+    }
+
+    pub(crate) fn load(name: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(Self::path(name)?).map_err(|_| {
+            let names = Self::names();
+            if names.is_empty() {
+                format!("{} non trovata, nessuna sessione salvata", name)
+            } else {
+                format!("{} non trovata (salvate: {})", name, names.join(", "))
+            }
+        })?;
+        let mut snapshot = Self {
+            session: SessionState::default(),
+            highlighted: None,
+            ab_loop: None,
+            shuffle: false,
+            queue: Vec::new(),
+            profile: DeviceProfile { volume: 1.0, eq: 0 },
+        };
+        let millis = |value: &str| value.parse().ok().map(Duration::from_millis);
+        for line in content.lines() {
+            if let Some(record) = JournalRecord::decode(line) {
+                record.apply(&mut snapshot.session);
+                continue;
+            }
+            let (kind, value) = line.split_once('\t').unwrap_or((line, ""));
+            match kind {
+                "highlight" => snapshot.highlighted = value.parse().ok(),
+                "loop" => {
+                    let (a, b) = value.split_once('\t').unwrap_or((value, ""));
+                    snapshot.ab_loop = millis(a).map(|a| (a, millis(b)));
+                }
+                "shuffle" => snapshot.shuffle = value == "1",
+                "queue" if !value.is_empty() => snapshot.queue.push(PathBuf::from(value)),
+                "volume" => {
+                    if let Ok(volume) = value.parse::<f32>() {
+                        snapshot.profile.volume = volume.clamp(0.0, 1.0);
+                    }
+                }
+                "eq" => {
+                    if let Ok(eq) = value.parse::<usize>() {
+                        snapshot.profile.eq = eq.min(EQ_PRESETS.len() - 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(snapshot)
+    }
+}
@@ -0,0 +1,742 @@
+//! User commands and the keys that trigger them: default bindings, the
+//! leader key and keyboard layouts.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{collections::HashMap, fs};
+
+use crate::app::config_dir;
+
+/// Rows of a US QWERTY board, unshifted then shifted, in the key order of
+/// `LAYOUTS`
+const QWERTY: (&str, &str) = (
+    "`1234567890-=qwertyuiop[]\\asdfghjkl;'zxcvbnm,./",
+    "~!@#$%^&*()_+QWERTYUIOP{}|ASDFGHJKL:\"ZXCVBNM<>?",
+);
+
+/// Layouts whose keys can be bound by position: each character stands on
+/// the key of the `QWERTY` character at the same index
+const LAYOUTS: [(&str, &str, &str); 4] = [
+    (
+        "azerty",
+        "²&é\"'(-è_çà)=azertyuiop^$*qsdfghjklmùwxcvbn,;:!",
+        "²1234567890°+AZERTYUIOP¨£µQSDFGHJKLM%WXCVBN?./§",
+    ),
+    (
+        "qwertz",
+        "^1234567890ß´qwertzuiopü+#asdfghjklöäyxcvbnm,.-",
+        "°!\"§$%&/()=?`QWERTZUIOPÜ*'ASDFGHJKLÖÄYXCVBNM;:_",
+    ),
+    (
+        "dvorak",
+        "`1234567890[]',.pyfgcrl/=\\aoeuidhtns-;qjkxbmwvz",
+        "~!@#$%^&*(){}\"<>PYFGCRL?+|AOEUIDHTNS_:QJKXBMWVZ",
+    ),
+    (
+        "colemak",
+        "`1234567890-=qwfpgjluy;[]\\arstdhneio'zxcvbkm,./",
+        "~!@#$%^&*()_+QWFPGJLUY:{}|ARSTDHNEIO\"ZXCVBKM<>?",
+    ),
+];
+
+/// Translation of typed keys before they are looked up as actions, from
+/// `<config>/keymap`. `layout NAME` makes every key act as the one in the
+/// same place on a US QWERTY board, so the bindings follow key positions;
+/// `X = Y` lines make key X do what Y does, and win over the layout;
+/// `leader X` picks the leader key. Text typed in prompts is never translated.
+/// The `[keys]` and `leader` of `config.toml` are applied over the file,
+/// and its `[bindings]` tie actions to the translated keys directly.
+pub(crate) struct Keymap {
+    pub(crate) keys: HashMap<char, char>,
+    /// Starts a two-key sequence, see `LEADER_BINDINGS`
+    pub(crate) leader: char,
+    /// Checked before the built-in keys, but after those of an open view
+    pub(crate) bindings: HashMap<KeyCode, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::new(),
+            leader: '\\',
+            bindings: HashMap::new(),
+        }
+    }
+}
+
+impl Keymap {
+    /// No file means no translation; a bad line is an error, so a typo
+    /// doesn't silently leave keys unbound
+    pub(crate) fn load() -> Result<Self, String> {
+        let Some(content) =
+            config_dir().and_then(|dir| fs::read_to_string(dir.join("keymap")).ok())
+        else {
+            return Ok(Self::default());
+        };
+        Self::parse(&content)
+    }
+
+    fn parse(content: &str) -> Result<Self, String> {
+        let mut layout = HashMap::new();
+        let mut remaps = HashMap::new();
+        let mut leader = Self::default().leader;
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("layout ") {
+                let name = name.trim();
+                let (_, unshifted, shifted) = LAYOUTS
+                    .iter()
+                    .find(|(layout, _, _)| *layout == name)
+                    .ok_or_else(|| format!("layout sconosciuto: {}", name))?;
+                layout.clear();
+                // Unshifted last, so it wins where both rows share a character
+                for (keys, qwerty) in [(shifted, QWERTY.1), (unshifted, QWERTY.0)] {
+                    layout.extend(keys.chars().zip(qwerty.chars()));
+                }
+                continue;
+            }
+            let single = |text: &str| {
+                let mut chars = text.trim().chars();
+                chars.next().filter(|_| chars.next().is_none())
+            };
+            if let Some(key) = line.strip_prefix("leader ") {
+                leader = single(key).ok_or_else(|| format!("leader non valido: {}", key))?;
+                continue;
+            }
+            let (key, target) = line
+                .split_once('=')
+                .and_then(|(key, target)| Some((single(key)?, single(target)?)))
+                .ok_or_else(|| format!("riga non valida: {}", line))?;
+            remaps.insert(key, target);
+        }
+        layout.retain(|key, target| key != target);
+        layout.extend(remaps);
+        Ok(Self {
+            keys: layout,
+            leader,
+            ..Self::default()
+        })
+    }
+
+    pub(crate) fn translate(&self, code: KeyCode) -> KeyCode {
+        match code {
+            KeyCode::Char(c) => KeyCode::Char(self.keys.get(&c).copied().unwrap_or(c)),
+            code => code,
+        }
+    }
+}
+
+/// Seconds moved by Left/Right, and by Shift+Left/Right
+const SEEK_STEP: i32 = 5;
+const LONG_SEEK_STEP: i32 = 30;
+
+/// Commands reachable as the leader key followed by a letter, with the hint
+/// shown for them after the leader. The single-key bindings stay; these
+/// group the less frequent commands where the keyboard has run out.
+pub(crate) const LEADER_BINDINGS: [(char, Action, &str); 35] = [
+    ('p', Action::AddToPlaylist, "Aggiungi alla playlist"),
+    ('a', Action::ToggleAlbumShuffle, "Album shuffle"),
+    ('l', Action::TogglePartyMode, "Party mode"),
+    ('g', Action::ScanLoudness, "ReplayGain"),
+    ('m', Action::FetchMetadata, "MusicBrainz"),
+    ('h', Action::ExportHistory, "Esporta storico"),
+    ('i', Action::ImportStats, "Importa statistiche"),
+    ('c', Action::CheckGaps, "Continuità album"),
+    ('v', Action::VerifyFiles, "Verifica integrità"),
+    ('f', Action::CompareWaveforms, "Confronta forme d'onda"),
+    ('w', Action::ToggleGenerator, "Generatore"),
+    ('b', Action::ToggleMetronome, "Metronomo"),
+    ('t', Action::StartPractice, "Pratica A–B"),
+    ('s', Action::SetAlarm, "Sveglia"),
+    ('d', Action::NextDevice, "Uscita audio"),
+    ('e', Action::CycleEqPreset, "Equalizzatore"),
+    ('q', Action::ToggleMacroRecording, "Registra macro"),
+    ('@', Action::PlayMacro, "Esegui macro per nome"),
+    ('n', Action::NewSession, "Nuova sessione"),
+    ('k', Action::CloseSession, "Chiudi sessione"),
+    ('o', Action::SaveSnapshot, "Salva sessione con nome"),
+    ('r', Action::RestoreSnapshot, "Ripristina sessione salvata"),
+    ('j', Action::SetIntroSkip, "Salta intro"),
+    ('y', Action::ToggleStatsView, "Statistiche di ascolto"),
+    ('x', Action::ShareAsQr, "Condividi lista (QR)"),
+    ('u', Action::ToggleSkipView, "Tracce saltate"),
+    ('U', Action::ResetSkips, "Azzera tracce saltate"),
+    ('z', Action::ToggleZonesView, "Zone"),
+    ('B', Action::ToggleBluetoothView, "Bluetooth"),
+    ('C', Action::ToggleCdView, "CD audio"),
+    ('Q', Action::ToggleQueueView, "Coda"),
+    ('I', Action::ToggleBroadcast, "Trasmissione Icecast"),
+    ('F', Action::CycleFadeCurve, "Curva dissolvenza"),
+    ('S', Action::ToggleBandSolo, "Solo banda"),
+    ('P', Action::CycleTiltOverlay, "Riferimento rumore rosa"),
+];
+
+/// User commands, decoupled from the keys that trigger them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Action {
+    Quit,
+    MoveDown,
+    MoveUp,
+    Select,
+    TogglePlayback,
+    VolumeUp,
+    VolumeDown,
+    NextTrack,
+    PreviousTrack,
+    /// Cycles off → repeat all → repeat one
+    CycleRepeat,
+    /// Plays the folder in a random order, see `playback_order`
+    ToggleShuffle,
+    ToggleAlbumShuffle,
+    TogglePartyMode,
+    CycleFrequencyScale,
+    ToggleVisualizer,
+    ToggleStereoSpectrum,
+    ScanLoudness,
+    FetchMetadata,
+    WriteTags,
+    ResumeSession,
+    ToggleLyrics,
+    SearchLyrics,
+    EditNote,
+    SearchNotes,
+    /// Asks how much of the highlighted track (or its album) to skip on play
+    SetIntroSkip,
+    ExportHistory,
+    ImportStats,
+    TogglePreview,
+    ToggleSfxBoard,
+    /// Sound effect slot 0-9 of the SFX board
+    PlaySfx(usize),
+    ToggleMixerView,
+    /// Seconds to move in the track, back when negative
+    Seek(i32),
+    /// Jumps to the start of the next (true) or previous section, see
+    /// `Waveform::sections`
+    JumpSection(bool),
+    /// Shows when the listening happens, from the history, or hides it
+    ToggleStatsView,
+    /// Shows the open playlist, or the folder, as a QR code for a phone
+    ShareAsQr,
+    ToggleSkipView,
+    /// Forgets how often tracks were skipped
+    ResetSkips,
+    /// Lists the output devices to play along with the main one
+    ToggleZonesView,
+    SelectZone(bool),
+    ZoneVolume(bool),
+    /// Adds the selected device as a zone, or removes it
+    ToggleZone,
+    /// Lists the paired Bluetooth audio devices, or hides them
+    ToggleBluetoothView,
+    SelectBluetoothDevice(bool),
+    /// Connects the selected device and plays through it, or disconnects it
+    ToggleBluetoothDevice,
+    /// Reads the disc in the CD drive and lists its tracks, or hides them
+    ToggleCdView,
+    SelectCdTrack(bool),
+    PlayCdTrack,
+    /// Rips the whole disc to FLAC in the music folder
+    RipCd,
+    /// Adds the highlighted track, or the tracks of the highlighted folder,
+    /// to the queue
+    Enqueue,
+    ToggleQueueView,
+    SelectQueued(bool),
+    MoveQueued(bool),
+    RemoveQueued,
+    ClearQueue,
+    /// Plays the selected queued track now, taking it out of the queue
+    PlayQueued,
+    /// Queues the oldest track a guest asked for over the control socket
+    ApproveRequest,
+    RejectRequest,
+    /// Starts or stops the stream to the `--icecast` mount
+    ToggleBroadcast,
+    /// Picks the next fade curve and plays a fade with it on the track
+    CycleFadeCurve,
+    /// Plays only the band of one spectrum bar, or everything again
+    ToggleBandSolo,
+    /// Solos the next bar up, or down
+    MoveBandSolo(bool),
+    /// Cycles off → pink reference → deviation from it on the spectrum
+    CycleTiltOverlay,
+    ToggleProfiler,
+    /// Deletes the highlighted file (to the trash unless `--permanent-delete`)
+    DeleteFile,
+    UndoDelete,
+    /// Copies the path of the highlighted (or playing) file
+    CopyPath,
+    /// Copies "artist – title" of the track playing
+    CopyNowPlaying,
+    /// Checks the current folder's track transitions for gaps and overlaps
+    CheckGaps,
+    /// Moves the output to the next audio device, applying its profile
+    NextDevice,
+    CycleEqPreset,
+    SetAlarm,
+    /// Sets A, then B, then clears the loop region
+    MarkLoop,
+    /// Asks repetitions and speeds of a practice run over the A–B loop
+    StartPractice,
+    ToggleMetronome,
+    /// Asks the metronome tempo, or `auto` to follow the track's
+    SetMetronomeBpm,
+    /// Asks for a test signal to play, or stops the one playing
+    ToggleGenerator,
+    /// Loads the highlighted file for the waveform comparison, or closes it
+    CompareWaveforms,
+    /// Decodes every track of the folder in the background to find damaged files
+    VerifyFiles,
+    /// Shows the highlighted file in the system file manager
+    RevealInFileManager,
+    /// Opens the highlighted file in the `--editor` program
+    OpenInEditor,
+    /// Moves the mixer view selection forward (true) or back
+    SelectStream(bool),
+    /// Raises (true) or lowers the gain of the stream selected in the mixer view
+    StreamGain(bool),
+    SwitchTab,
+    AddToPlaylist,
+    CreatePlaylist,
+    RenamePlaylist,
+    DuplicatePlaylist,
+    DeletePlaylist,
+    MoveTrackDown,
+    MoveTrackUp,
+    RemoveFromPlaylist,
+    ClosePlaylist,
+    ToggleMacroRecording,
+    /// Asks for the name of a saved macro and replays it
+    PlayMacro,
+    PlayLastMacro,
+    NewSession,
+    NextSession,
+    CloseSession,
+    SaveSnapshot,
+    RestoreSnapshot,
+}
+
+/// Actions that `[bindings]` in `config.toml` can name, as written here;
+/// `Seek(N)` and `PlaySfx(N)` take any number and are parsed apart
+const BINDABLE_ACTIONS: [Action; 108] = [
+    Action::Quit,
+    Action::MoveDown,
+    Action::MoveUp,
+    Action::Select,
+    Action::TogglePlayback,
+    Action::VolumeUp,
+    Action::VolumeDown,
+    Action::NextTrack,
+    Action::PreviousTrack,
+    Action::CycleRepeat,
+    Action::ToggleShuffle,
+    Action::ToggleAlbumShuffle,
+    Action::TogglePartyMode,
+    Action::CycleFrequencyScale,
+    Action::ToggleVisualizer,
+    Action::ToggleStereoSpectrum,
+    Action::ScanLoudness,
+    Action::FetchMetadata,
+    Action::WriteTags,
+    Action::ResumeSession,
+    Action::ToggleLyrics,
+    Action::SearchLyrics,
+    Action::EditNote,
+    Action::SearchNotes,
+    Action::SetIntroSkip,
+    Action::ExportHistory,
+    Action::ImportStats,
+    Action::TogglePreview,
+    Action::ToggleSfxBoard,
+    Action::ToggleMixerView,
+    Action::JumpSection(true),
+    Action::JumpSection(false),
+    Action::ToggleStatsView,
+    Action::ShareAsQr,
+    Action::ToggleSkipView,
+    Action::ResetSkips,
+    Action::ToggleZonesView,
+    Action::SelectZone(true),
+    Action::SelectZone(false),
+    Action::ZoneVolume(true),
+    Action::ZoneVolume(false),
+    Action::ToggleZone,
+    Action::ToggleBluetoothView,
+    Action::SelectBluetoothDevice(true),
+    Action::SelectBluetoothDevice(false),
+    Action::ToggleBluetoothDevice,
+    Action::ToggleCdView,
+    Action::SelectCdTrack(true),
+    Action::SelectCdTrack(false),
+    Action::PlayCdTrack,
+    Action::RipCd,
+    Action::Enqueue,
+    Action::ToggleQueueView,
+    Action::SelectQueued(true),
+    Action::SelectQueued(false),
+    Action::MoveQueued(true),
+    Action::MoveQueued(false),
+    Action::RemoveQueued,
+    Action::ClearQueue,
+    Action::PlayQueued,
+    Action::ApproveRequest,
+    Action::RejectRequest,
+    Action::ToggleBroadcast,
+    Action::CycleFadeCurve,
+    Action::ToggleBandSolo,
+    Action::MoveBandSolo(true),
+    Action::MoveBandSolo(false),
+    Action::CycleTiltOverlay,
+    Action::ToggleProfiler,
+    Action::DeleteFile,
+    Action::UndoDelete,
+    Action::CopyPath,
+    Action::CopyNowPlaying,
+    Action::CheckGaps,
+    Action::NextDevice,
+    Action::CycleEqPreset,
+    Action::SetAlarm,
+    Action::MarkLoop,
+    Action::StartPractice,
+    Action::ToggleMetronome,
+    Action::SetMetronomeBpm,
+    Action::ToggleGenerator,
+    Action::CompareWaveforms,
+    Action::VerifyFiles,
+    Action::RevealInFileManager,
+    Action::OpenInEditor,
+    Action::SelectStream(true),
+    Action::SelectStream(false),
+    Action::StreamGain(true),
+    Action::StreamGain(false),
+    Action::SwitchTab,
+    Action::AddToPlaylist,
+    Action::CreatePlaylist,
+    Action::RenamePlaylist,
+    Action::DuplicatePlaylist,
+    Action::DeletePlaylist,
+    Action::MoveTrackDown,
+    Action::MoveTrackUp,
+    Action::RemoveFromPlaylist,
+    Action::ClosePlaylist,
+    Action::ToggleMacroRecording,
+    Action::PlayMacro,
+    Action::PlayLastMacro,
+    Action::NewSession,
+    Action::NextSession,
+    Action::CloseSession,
+    Action::SaveSnapshot,
+    Action::RestoreSnapshot,
+];
+
+impl Action {
+    /// The action named as in `BINDABLE_ACTIONS`, e.g. `NextTrack`,
+    /// `SelectZone(true)` or `Seek(-30)`
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim();
+        let argument = |prefix: &str| name.strip_prefix(prefix)?.strip_suffix(')');
+        if let Some(seconds) = argument("Seek(") {
+            return seconds.trim().parse().ok().map(Action::Seek);
+        }
+        if let Some(slot) = argument("PlaySfx(") {
+            return slot
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&slot| slot < 10)
+                .map(Action::PlaySfx);
+        }
+        BINDABLE_ACTIONS
+            .into_iter()
+            .find(|action| format!("{:?}", action) == name)
+    }
+
+    pub(crate) fn from_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('q') => Some(Action::Quit),
+            KeyCode::Down | KeyCode::Char('j') => Some(Action::MoveDown),
+            KeyCode::Up | KeyCode::Char('k') => Some(Action::MoveUp),
+            KeyCode::Enter => Some(Action::Select),
+            KeyCode::Char(' ') => Some(Action::TogglePlayback),
+            KeyCode::Char('+') | KeyCode::Char('=') => Some(Action::VolumeUp),
+            KeyCode::Char('-') | KeyCode::Char('_') => Some(Action::VolumeDown),
+            KeyCode::Char('n') => Some(Action::NextTrack),
+            KeyCode::Char('p') => Some(Action::PreviousTrack),
+            KeyCode::Char('r') | KeyCode::Char('c') => Some(Action::CycleRepeat),
+            KeyCode::Char('s') => Some(Action::ToggleShuffle),
+            KeyCode::Char('A') => Some(Action::ToggleAlbumShuffle),
+            KeyCode::Char('L') => Some(Action::TogglePartyMode),
+            KeyCode::Char('f') => Some(Action::CycleFrequencyScale),
+            KeyCode::Char('v') => Some(Action::ToggleVisualizer),
+            KeyCode::Char('m') => Some(Action::ToggleStereoSpectrum),
+            KeyCode::Char('G') => Some(Action::ScanLoudness),
+            KeyCode::Char('M') => Some(Action::FetchMetadata),
+            KeyCode::Char('l') => Some(Action::ToggleLyrics),
+            KeyCode::Char('Y') => Some(Action::SearchLyrics),
+            KeyCode::Char('e') => Some(Action::EditNote),
+            KeyCode::Char('E') => Some(Action::SearchNotes),
+            KeyCode::Char('H') => Some(Action::ExportHistory),
+            KeyCode::Char('I') => Some(Action::ImportStats),
+            KeyCode::Char('h') => Some(Action::TogglePreview),
+            KeyCode::Char('b') => Some(Action::ToggleSfxBoard),
+            KeyCode::Char('z') => Some(Action::ToggleMixerView),
+            KeyCode::F(12) => Some(Action::ToggleProfiler),
+            KeyCode::Delete => Some(Action::DeleteFile),
+            KeyCode::Char('u') => Some(Action::UndoDelete),
+            KeyCode::Char('y') => Some(Action::CopyPath),
+            KeyCode::Char('w') => Some(Action::CopyNowPlaying),
+            KeyCode::Char('o') => Some(Action::RevealInFileManager),
+            KeyCode::Char('g') => Some(Action::CheckGaps),
+            KeyCode::Char('d') => Some(Action::NextDevice),
+            KeyCode::Char('x') => Some(Action::CycleEqPreset),
+            KeyCode::Char('t') => Some(Action::SetAlarm),
+            KeyCode::Char('i') => Some(Action::MarkLoop),
+            KeyCode::Char('T') => Some(Action::StartPractice),
+            KeyCode::Char('B') => Some(Action::ToggleMetronome),
+            KeyCode::Char('N') => Some(Action::SetMetronomeBpm),
+            KeyCode::Char('W') => Some(Action::ToggleGenerator),
+            KeyCode::Char('V') => Some(Action::CompareWaveforms),
+            KeyCode::Char('Z') => Some(Action::VerifyFiles),
+            KeyCode::Char('O') => Some(Action::OpenInEditor),
+            KeyCode::Tab => Some(Action::SwitchTab),
+            KeyCode::Char('P') => Some(Action::AddToPlaylist),
+            KeyCode::Char('@') => Some(Action::PlayLastMacro),
+            KeyCode::Char('a') => Some(Action::Enqueue),
+            KeyCode::Char('S') => Some(Action::NextSession),
+            KeyCode::Right => Some(Action::Seek(SEEK_STEP)),
+            KeyCode::Left => Some(Action::Seek(-SEEK_STEP)),
+            KeyCode::Char('}') => Some(Action::JumpSection(true)),
+            KeyCode::Char('{') => Some(Action::JumpSection(false)),
+            _ => None,
+        }
+    }
+
+    /// Second key of a leader sequence
+    pub(crate) fn from_leader_key(code: KeyCode) -> Option<Self> {
+        LEADER_BINDINGS
+            .iter()
+            .find(|(key, _, _)| code == KeyCode::Char(*key))
+            .map(|(_, action, _)| *action)
+    }
+
+    /// Shift+Left/Right make the long seek; everything else is `from_key`
+    pub(crate) fn from_seek_key(key: KeyEvent) -> Option<Self> {
+        if !key.modifiers.contains(KeyModifiers::SHIFT) {
+            return None;
+        }
+        match key.code {
+            KeyCode::Right => Some(Action::Seek(LONG_SEEK_STEP)),
+            KeyCode::Left => Some(Action::Seek(-LONG_SEEK_STEP)),
+            _ => None,
+        }
+    }
+
+    /// Keys of the mixer view
+    pub(crate) fn from_mixer_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('.') => Some(Action::SelectStream(true)),
+            KeyCode::Char(',') => Some(Action::SelectStream(false)),
+            KeyCode::Char(']') => Some(Action::StreamGain(true)),
+            KeyCode::Char('[') => Some(Action::StreamGain(false)),
+            _ => None,
+        }
+    }
+
+    /// Keys of the zones view
+    pub(crate) fn from_zones_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('.') => Some(Action::SelectZone(true)),
+            KeyCode::Char(',') => Some(Action::SelectZone(false)),
+            KeyCode::Char(']') => Some(Action::ZoneVolume(true)),
+            KeyCode::Char('[') => Some(Action::ZoneVolume(false)),
+            KeyCode::Enter => Some(Action::ToggleZone),
+            _ => None,
+        }
+    }
+
+    /// Keys of the Bluetooth view
+    pub(crate) fn from_bluetooth_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('.') => Some(Action::SelectBluetoothDevice(true)),
+            KeyCode::Char(',') => Some(Action::SelectBluetoothDevice(false)),
+            KeyCode::Enter => Some(Action::ToggleBluetoothDevice),
+            _ => None,
+        }
+    }
+
+    /// Keys of the queue view
+    pub(crate) fn from_queue_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('.') => Some(Action::SelectQueued(true)),
+            KeyCode::Char(',') => Some(Action::SelectQueued(false)),
+            KeyCode::Char('J') => Some(Action::MoveQueued(true)),
+            KeyCode::Char('K') => Some(Action::MoveQueued(false)),
+            KeyCode::Char('X') | KeyCode::Delete => Some(Action::RemoveQueued),
+            KeyCode::Char('C') => Some(Action::ClearQueue),
+            KeyCode::Char('A') => Some(Action::ApproveRequest),
+            KeyCode::Char('N') => Some(Action::RejectRequest),
+            KeyCode::Enter => Some(Action::PlayQueued),
+            _ => None,
+        }
+    }
+
+    /// Keys while a spectrum band is soloed
+    pub(crate) fn from_band_solo_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('.') => Some(Action::MoveBandSolo(true)),
+            KeyCode::Char(',') => Some(Action::MoveBandSolo(false)),
+            _ => None,
+        }
+    }
+
+    /// Keys of the CD view
+    pub(crate) fn from_cd_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('.') => Some(Action::SelectCdTrack(true)),
+            KeyCode::Char(',') => Some(Action::SelectCdTrack(false)),
+            KeyCode::Enter => Some(Action::PlayCdTrack),
+            KeyCode::Char('R') => Some(Action::RipCd),
+            _ => None,
+        }
+    }
+
+    /// Number keys of the SFX board: 1-9, then 0 for the tenth slot
+    pub(crate) fn from_sfx_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char(c @ '1'..='9') => Some(Action::PlaySfx(c as usize - '1' as usize)),
+            KeyCode::Char('0') => Some(Action::PlaySfx(9)),
+            _ => None,
+        }
+    }
+
+    /// Keys that only mean something in the playlists tab; checked before `from_key`
+    pub(crate) fn from_playlist_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('C') => Some(Action::CreatePlaylist),
+            KeyCode::Char('R') => Some(Action::RenamePlaylist),
+            KeyCode::Char('U') => Some(Action::DuplicatePlaylist),
+            KeyCode::Char('D') | KeyCode::Delete => Some(Action::DeletePlaylist),
+            KeyCode::Char('J') => Some(Action::MoveTrackDown),
+            KeyCode::Char('K') => Some(Action::MoveTrackUp),
+            KeyCode::Char('X') => Some(Action::RemoveFromPlaylist),
+            KeyCode::Backspace | KeyCode::Esc => Some(Action::ClosePlaylist),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            Action::Quit => "Esci",
+            Action::MoveDown => "Giù",
+            Action::MoveUp => "Su",
+            Action::Select => "Cambia traccia",
+            Action::TogglePlayback => "Play/Stop",
+            Action::VolumeUp => "Volume oltre il limite",
+            Action::VolumeDown => "Volume giù",
+            Action::NextTrack => "Traccia successiva",
+            Action::PreviousTrack => "Traccia precedente",
+            Action::CycleRepeat => "Modalità ripetizione",
+            Action::ToggleShuffle => "Shuffle",
+            Action::ToggleAlbumShuffle => "Album shuffle",
+            Action::TogglePartyMode => "Disattiva party mode",
+            Action::CycleFrequencyScale => "Scala frequenze",
+            Action::ToggleVisualizer => "Visualizzatore",
+            Action::ToggleStereoSpectrum => "Spettro stereo",
+            Action::ScanLoudness => "Analisi loudness",
+            Action::FetchMetadata => "Completa tag da MusicBrainz",
+            Action::WriteTags => "Scrivi tag",
+            Action::ResumeSession => "Riprendi sessione",
+            Action::ToggleLyrics => "Testi",
+            Action::SearchLyrics => "Cerca testo",
+            Action::EditNote => "Nota traccia",
+            Action::SearchNotes => "Cerca nelle note",
+            Action::SetIntroSkip => "Salta intro",
+            Action::ExportHistory => "Esporta storico",
+            Action::ImportStats => "Importa statistiche",
+            Action::TogglePreview => "Anteprima",
+            Action::ToggleSfxBoard => "SFX board",
+            Action::PlaySfx(_) => "Effetto sonoro",
+            Action::ToggleMixerView => "Mixer",
+            Action::Seek(secs) if *secs > 0 => "Avanti",
+            Action::Seek(_) => "Indietro",
+            Action::JumpSection(true) => "Sezione successiva",
+            Action::JumpSection(false) => "Sezione precedente",
+            Action::ToggleStatsView => "Statistiche di ascolto",
+            Action::ShareAsQr => "Condividi lista (QR)",
+            Action::ToggleSkipView => "Tracce saltate",
+            Action::ToggleZonesView => "Zone",
+            Action::SelectZone(true) => "Zona successiva",
+            Action::SelectZone(false) => "Zona precedente",
+            Action::ZoneVolume(true) => "Volume zona +",
+            Action::ZoneVolume(false) => "Volume zona -",
+            Action::ToggleZone => "Attiva/disattiva zona",
+            Action::ToggleBluetoothView => "Bluetooth",
+            Action::SelectBluetoothDevice(true) => "Dispositivo Bluetooth successivo",
+            Action::SelectBluetoothDevice(false) => "Dispositivo Bluetooth precedente",
+            Action::ToggleBluetoothDevice => "Connetti/disconnetti Bluetooth",
+            Action::ToggleCdView => "CD audio",
+            Action::SelectCdTrack(true) => "Traccia CD successiva",
+            Action::SelectCdTrack(false) => "Traccia CD precedente",
+            Action::PlayCdTrack => "Riproduci traccia CD",
+            Action::RipCd => "Rippa CD in FLAC",
+            Action::Enqueue => "Aggiungi alla coda",
+            Action::ToggleQueueView => "Coda",
+            Action::SelectQueued(true) => "Traccia in coda successiva",
+            Action::SelectQueued(false) => "Traccia in coda precedente",
+            Action::MoveQueued(true) => "Sposta giù in coda",
+            Action::MoveQueued(false) => "Sposta su in coda",
+            Action::RemoveQueued => "Togli dalla coda",
+            Action::ClearQueue => "Svuota coda",
+            Action::PlayQueued => "Riproduci dalla coda",
+            Action::ApproveRequest => "Approva richiesta",
+            Action::RejectRequest => "Rifiuta richiesta",
+            Action::ToggleBroadcast => "Trasmissione Icecast",
+            Action::CycleFadeCurve => "Curva dissolvenza",
+            Action::ToggleBandSolo => "Solo banda",
+            Action::MoveBandSolo(true) => "Banda successiva",
+            Action::MoveBandSolo(false) => "Banda precedente",
+            Action::CycleTiltOverlay => "Riferimento rumore rosa",
+            Action::ResetSkips => "Azzera tracce saltate",
+            Action::ToggleProfiler => "Profiler",
+            Action::DeleteFile => "Elimina file",
+            Action::UndoDelete => "Ripristina dal cestino",
+            Action::CopyPath => "Copia percorso",
+            Action::CopyNowPlaying => "Copia titolo",
+            Action::RevealInFileManager => "Mostra nella cartella",
+            Action::CheckGaps => "Controllo continuità",
+            Action::NextDevice => "Cambia uscita audio",
+            Action::CycleEqPreset => "Preset equalizzatore",
+            Action::SetAlarm => "Sveglia",
+            Action::MarkLoop => "Loop A–B",
+            Action::StartPractice => "Pratica",
+            Action::ToggleMetronome => "Metronomo",
+            Action::SetMetronomeBpm => "Tempo metronomo",
+            Action::ToggleGenerator => "Generatore",
+            Action::CompareWaveforms => "Confronto forme d'onda",
+            Action::VerifyFiles => "Verifica integrità",
+            Action::OpenInEditor => "Apri nell'editor",
+            Action::ToggleMacroRecording => "Registra macro",
+            Action::PlayMacro => "Esegui macro",
+            Action::PlayLastMacro => "Ripeti macro",
+            Action::NewSession => "Nuova sessione",
+            Action::NextSession => "Cambia sessione",
+            Action::CloseSession => "Chiudi sessione",
+            Action::SaveSnapshot => "Salva sessione",
+            Action::RestoreSnapshot => "Ripristina sessione salvata",
+            Action::SelectStream(_) => "Seleziona stream",
+            Action::StreamGain(_) => "Volume stream",
+            Action::SwitchTab => "Cambia scheda",
+            Action::AddToPlaylist => "Aggiungi alla playlist",
+            Action::CreatePlaylist => "Nuova playlist",
+            Action::RenamePlaylist => "Rinomina playlist",
+            Action::DuplicatePlaylist => "Duplica playlist",
+            Action::DeletePlaylist => "Elimina playlist",
+            Action::MoveTrackDown => "Sposta giù",
+            Action::MoveTrackUp => "Sposta su",
+            Action::RemoveFromPlaylist => "Togli dalla playlist",
+            Action::ClosePlaylist => "Chiudi playlist",
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Terminal music player with a spectrum visualizer. `App` holds the
+//! state and handles keys, `AudioPlayer` plays, and `run` drives both in
+//! the terminal.
+
+mod alarm;
+mod app;
+mod broadcast;
+mod browser;
+mod cd;
+mod chiptune;
+mod config;
+#[cfg(unix)]
+mod control;
+mod fft;
+mod history;
+mod journal;
+mod keymap;
+mod macros;
+mod metadata;
+mod player;
+mod playlist;
+mod queue;
+mod session;
+mod ui;
+mod zones;
+
+pub use app::{App, Settings};
+pub use player::AudioPlayer;
+
+use crossterm::{
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute,
+    terminal::{
+        EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+        supports_keyboard_enhancement,
+    },
+};
+use ratatui::{Terminal, backend::CrosstermBackend};
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use crate::player::set_stream_properties;
+use crate::ui::ui;
+
+/// Runs the player in the terminal until the user quits
+pub fn run(settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    set_stream_properties(&settings.media_role);
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    // Terminals speaking the kitty protocol then report media keys too
+    let enhanced = supports_keyboard_enhancement().unwrap_or(false);
+    if enhanced {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        )?;
+    }
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let mut app = App::new(settings)?;
+    let res = run_app(&mut terminal, &mut app);
+    // The track playing at exit was heard up to here
+    app.audio_player.stop();
+    app.log_true_peaks();
+
+    if enhanced {
+        execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags)?;
+    }
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    if let Err(err) = res {
+        println!("{:?}", err)
+    }
+
+    Ok(())
+}
+
+fn run_app<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+) -> io::Result<()> {
+    loop {
+        app.update_playback();
+        if app.quit {
+            return Ok(());
+        }
+        let frame_start = Instant::now();
+        terminal.draw(|f| ui(f, app))?;
+        app.profiler.record_frame(frame_start.elapsed());
+
+        // Without the visualizer only the clock changes, so redraw less often
+        let poll_interval = if app.visualizer_enabled { 50 } else { 250 };
+        if event::poll(Duration::from_millis(poll_interval))?
+            && let Event::Key(key) = event::read()?
+        {
+            app.profiler.pending_event = Some(Instant::now());
+            // Recorded after dispatch, so the keys starting and stopping
+            // the recording are left out
+            let recording = app.macros.is_recording();
+            let quit = app.handle_key(key)?;
+            if recording {
+                app.macros.record(key.code);
+            }
+            if quit {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Macros: key sequences recorded or written in `<config>/macros`, and
+//! replayed as if typed.
+
+use crossterm::event::{KeyCode, MediaKeyCode};
+use std::{fs, io, path::PathBuf};
+
+use crate::app::config_dir;
+
+/// Names of the keys that aren't written as themselves in `<config>/macros`
+/// and `[bindings]`. Media keys only arrive from terminals that report them
+/// (e.g. kitty, foot, WezTerm); the others keep them for the desktop.
+const KEY_NAMES: [(&str, KeyCode); 24] = [
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Tab", KeyCode::Tab),
+    ("BS", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+    ("Space", KeyCode::Char(' ')),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("lt", KeyCode::Char('<')),
+    ("PlayPause", KeyCode::Media(MediaKeyCode::PlayPause)),
+    ("Play", KeyCode::Media(MediaKeyCode::Play)),
+    ("Pause", KeyCode::Media(MediaKeyCode::Pause)),
+    ("Stop", KeyCode::Media(MediaKeyCode::Stop)),
+    ("TrackNext", KeyCode::Media(MediaKeyCode::TrackNext)),
+    ("TrackPrevious", KeyCode::Media(MediaKeyCode::TrackPrevious)),
+    ("RaiseVolume", KeyCode::Media(MediaKeyCode::RaiseVolume)),
+    ("LowerVolume", KeyCode::Media(MediaKeyCode::LowerVolume)),
+    ("MuteVolume", KeyCode::Media(MediaKeyCode::MuteVolume)),
+];
+
+/// Named key sequences from `<config>/macros`, one per line: `name = keys`.
+/// Keys are written as typed, special ones by name in angle brackets
+/// (`<Enter>`, `<Space>`, `<lt>` for `<`, `<F5>`), so `lento = N90<Enter>`
+/// sets the metronome to 90 BPM. Replayed keys go through the same
+/// translation and confirmations as typed ones.
+#[derive(Default)]
+pub(crate) struct Macros {
+    file: Option<PathBuf>,
+    entries: Vec<(String, Vec<KeyCode>)>,
+    /// Keys typed since recording started
+    recording: Option<Vec<KeyCode>>,
+    /// Macro replayed by `@`: the last one recorded or run by name
+    last: Vec<KeyCode>,
+    /// Set while a macro is being replayed, so it can't start another one
+    replaying: bool,
+}
+
+impl Macros {
+    pub(crate) fn load() -> Result<Self, String> {
+        let file = config_dir().map(|dir| dir.join("macros"));
+        let content = file
+            .as_ref()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .unwrap_or_default();
+        let mut entries = Vec::new();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, keys) = line
+                .split_once('=')
+                .ok_or_else(|| format!("riga non valida: {}", line))?;
+            entries.push((name.trim().to_string(), Self::decode(keys.trim())?));
+        }
+        Ok(Self {
+            file,
+            entries,
+            ..Self::default()
+        })
+    }
+
+    pub(crate) fn save(&self) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content: String = self
+            .entries
+            .iter()
+            .map(|(name, keys)| format!("{} = {}\n", name, Self::encode(keys)))
+            .collect();
+        fs::write(file, content)
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&[KeyCode]> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry == name)
+            .map(|(_, keys)| keys.as_slice())
+    }
+
+    /// Adds the macro, replacing one with the same name
+    pub(crate) fn set(&mut self, name: &str, keys: Vec<KeyCode>) {
+        match self.entries.iter_mut().find(|(entry, _)| entry == name) {
+            Some(entry) => entry.1 = keys,
+            None => self.entries.push((name.to_string(), keys)),
+        }
+    }
+
+    /// Names the last macro, keeping it with the others
+    pub(crate) fn keep_last(&mut self, name: &str) {
+        self.set(name, self.last.clone());
+    }
+
+    pub(crate) fn last(&self) -> &[KeyCode] {
+        &self.last
+    }
+
+    pub(crate) fn set_last(&mut self, keys: Vec<KeyCode>) {
+        self.last = keys;
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub(crate) fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Adds a key typed while recording
+    pub(crate) fn record(&mut self, code: KeyCode) {
+        if let Some(keys) = &mut self.recording {
+            keys.push(code);
+        }
+    }
+
+    /// The keys recorded, if recording was on
+    pub(crate) fn stop_recording(&mut self) -> Option<Vec<KeyCode>> {
+        self.recording.take()
+    }
+
+    pub(crate) fn is_replaying(&self) -> bool {
+        self.replaying
+    }
+
+    pub(crate) fn set_replaying(&mut self, replaying: bool) {
+        self.replaying = replaying;
+    }
+
+    fn encode(keys: &[KeyCode]) -> String {
+        keys.iter()
+            .filter_map(
+                |&code| match KEY_NAMES.iter().find(|(_, key)| *key == code) {
+                    Some((name, _)) => Some(format!("<{}>", name)),
+                    None => match code {
+                        KeyCode::Char(c) => Some(c.to_string()),
+                        KeyCode::F(n) => Some(format!("<F{}>", n)),
+                        _ => None,
+                    },
+                },
+            )
+            .collect()
+    }
+
+    pub(crate) fn decode(text: &str) -> Result<Vec<KeyCode>, String> {
+        let mut keys = Vec::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c != '<' {
+                keys.push(KeyCode::Char(c));
+                continue;
+            }
+            let name: String = chars.by_ref().take_while(|&c| c != '>').collect();
+            let key = KEY_NAMES
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(&name))
+                .map(|(_, code)| *code)
+                .or_else(|| {
+                    let n = name.strip_prefix(['F', 'f'])?.parse().ok()?;
+                    (1..=12).contains(&n).then_some(KeyCode::F(n))
+                })
+                .ok_or_else(|| format!("tasto sconosciuto: <{}>", name))?;
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_round_trip_through_their_names() {
+        let keys = Macros::decode("N90<enter><lt><F5><Space>x").unwrap();
+        assert_eq!(
+            keys,
+            [
+                KeyCode::Char('N'),
+                KeyCode::Char('9'),
+                KeyCode::Char('0'),
+                KeyCode::Enter,
+                KeyCode::Char('<'),
+                KeyCode::F(5),
+                KeyCode::Char(' '),
+                KeyCode::Char('x'),
+            ]
+        );
+        assert_eq!(Macros::encode(&keys), "N90<Enter><lt><F5><Space>x");
+        assert!(Macros::decode("<F13>").is_err());
+        assert!(Macros::decode("<Nessuno>").is_err());
+    }
+
+    #[test]
+    fn recording_keeps_only_keys_typed_while_on() {
+        let mut macros = Macros::default();
+        macros.record(KeyCode::Char('a'));
+        macros.start_recording();
+        macros.record(KeyCode::Char('b'));
+        macros.record(KeyCode::Enter);
+        let keys = macros.stop_recording().unwrap();
+        macros.record(KeyCode::Char('c'));
+        assert_eq!(keys, [KeyCode::Char('b'), KeyCode::Enter]);
+        assert!(macros.stop_recording().is_none());
+
+        macros.set_last(keys);
+        macros.keep_last("invio");
+        assert_eq!(macros.get("invio"), Some(macros.last()));
+    }
+}
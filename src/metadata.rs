@@ -0,0 +1,622 @@
+//! What is known about a track beyond its audio: tag edits, MusicBrainz
+//! lookups, cover art and lyrics.
+
+use lofty::{
+    config::WriteOptions,
+    file::{AudioFile, TaggedFileExt},
+    picture::{Picture, PictureType},
+    tag::{ItemKey, Tag},
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::app::{App, cache_dir};
+use crate::browser::TrackTags;
+
+/// New tag values for one file; fields not listed are left as they are
+pub(crate) struct TagChange {
+    pub(crate) path: PathBuf,
+    pub(crate) items: Vec<(ItemKey, String)>,
+    /// Front cover to embed
+    pub(crate) picture: Option<Arc<Vec<u8>>>,
+}
+
+/// Tag changes prepared by a background job (loudness scan, MusicBrainz
+/// lookup). Nothing is written until the user confirms.
+pub(crate) struct TagUpdate {
+    pub(crate) summary: String,
+    pub(crate) files: Vec<TagChange>,
+}
+
+impl TagUpdate {
+    /// Writes every file, each one after copying the original to `<file>.bak`.
+    /// Returns how many files were written.
+    pub(crate) fn apply(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        for change in &self.files {
+            let mut backup = change.path.clone().into_os_string();
+            backup.push(".bak");
+            fs::copy(&change.path, PathBuf::from(backup))?;
+
+            let mut tagged_file = lofty::read_from_path(&change.path)?;
+            if tagged_file.primary_tag().is_none() {
+                tagged_file.insert_tag(Tag::new(tagged_file.primary_tag_type()));
+            }
+            let tag = tagged_file
+                .primary_tag_mut()
+                .ok_or("impossibile creare il tag")?;
+
+            for (key, value) in &change.items {
+                tag.insert_text(key.clone(), value.clone());
+            }
+            if let Some(data) = &change.picture {
+                let mut picture = Picture::from_reader(&mut data.as_slice())?;
+                picture.set_pic_type(PictureType::CoverFront);
+                tag.push_picture(picture);
+            }
+
+            tagged_file.save_to_path(&change.path, WriteOptions::default())?;
+        }
+        Ok(self.files.len())
+    }
+}
+
+/// Album lookups on the MusicBrainz web service, with front covers from the
+/// Cover Art Archive
+pub(crate) struct MusicBrainz;
+
+impl MusicBrainz {
+    pub(crate) const API: &str = "https://musicbrainz.org/ws/2";
+    const COVER_ART_API: &str = "https://coverartarchive.org";
+    /// MusicBrainz rejects anonymous clients
+    const USER_AGENT: &str = concat!(
+        "rust-player/",
+        env!("CARGO_PKG_VERSION"),
+        " ( https://github.com/Mastyx/audio_player )"
+    );
+    /// Search results below this score are not trusted
+    const MIN_SCORE: u64 = 90;
+
+    pub(crate) fn agent() -> ureq::Agent {
+        ureq::AgentBuilder::new()
+            .user_agent(Self::USER_AGENT)
+            .timeout(Duration::from_secs(15))
+            .build()
+    }
+
+    pub(crate) fn get_json(
+        agent: &ureq::Agent,
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        let mut request = agent.get(url).query("fmt", "json");
+        for (key, value) in query {
+            request = request.query(key, value);
+        }
+        Ok(serde_json::from_str(&request.call()?.into_string()?)?)
+    }
+
+    /// Front cover of a release (500px), from the Cover Art Archive
+    fn front_cover(
+        agent: &ureq::Agent,
+        release_id: &str,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let response = agent
+            .get(&format!(
+                "{}/release/{}/front-500",
+                Self::COVER_ART_API,
+                release_id
+            ))
+            .call()?;
+        let mut data = Vec::new();
+        response.into_reader().read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Looks the album in `dir` up by its artist/album tags (or the folder
+    /// names when untagged) and prepares the missing tags for each file.
+    /// Files are matched to the release tracks by disc/track number, or by
+    /// position when they aren't numbered. The release id is always written,
+    /// so the cover can be found again later.
+    pub(crate) fn complete_album(dir: &Path) -> Result<TagUpdate, Box<dyn std::error::Error>> {
+        let mut files: Vec<(PathBuf, TrackTags)> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| App::is_audio_file(path))
+            .map(|path| {
+                let tags = TrackTags::read(&path);
+                (path, tags)
+            })
+            .collect();
+        if files.is_empty() {
+            return Err("nessun file audio nella cartella".into());
+        }
+        files.sort_by(|(a, ta), (b, tb)| {
+            (ta.disc.unwrap_or(1), ta.track.unwrap_or(u32::MAX), a).cmp(&(
+                tb.disc.unwrap_or(1),
+                tb.track.unwrap_or(u32::MAX),
+                b,
+            ))
+        });
+
+        let folder_name = |path: Option<&Path>| {
+            path.and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().into_owned())
+        };
+        let album = files
+            .iter()
+            .find_map(|(_, tags)| tags.album.clone())
+            .or_else(|| folder_name(Some(dir)))
+            .ok_or("titolo album sconosciuto")?;
+        let artist = files
+            .iter()
+            .find_map(|(_, tags)| tags.artist.clone())
+            .or_else(|| folder_name(dir.parent()));
+
+        let agent = Self::agent();
+        let mut query = format!("release:\"{}\"", album.replace('"', ""));
+        if let Some(artist) = &artist {
+            query += &format!(" AND artist:\"{}\"", artist.replace('"', ""));
+        }
+        let search = Self::get_json(
+            &agent,
+            &format!("{}/release/", Self::API),
+            &[("query", &query), ("limit", "1")],
+        )?;
+        let release = search["releases"]
+            .get(0)
+            .filter(|r| r["score"].as_u64().unwrap_or(0) >= Self::MIN_SCORE)
+            .ok_or("nessuna corrispondenza su MusicBrainz")?;
+        let release_id = release["id"].as_str().ok_or("risposta non valida")?;
+
+        let release = Self::get_json(
+            &agent,
+            &format!("{}/release/{}", Self::API, release_id),
+            &[("inc", "recordings+artist-credits")],
+        )?;
+        let credit = |value: &serde_json::Value| {
+            value["artist-credit"].as_array().map(|credits| {
+                credits
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{}{}",
+                            c["name"].as_str().unwrap_or(""),
+                            c["joinphrase"].as_str().unwrap_or("")
+                        )
+                    })
+                    .collect::<String>()
+            })
+        };
+        let release_title = release["title"].as_str().unwrap_or(&album).to_string();
+        let release_artist = credit(&release);
+        let release_year = release["date"]
+            .as_str()
+            .and_then(|d| d.get(..4))
+            .and_then(|y| y.parse::<u32>().ok());
+
+        // (disc, track number, tracks on disc, title, artist, recording id)
+        let mut tracks = Vec::new();
+        for medium in release["media"].as_array().into_iter().flatten() {
+            let disc = medium["position"].as_u64().unwrap_or(1) as u32;
+            let medium_tracks = medium["tracks"].as_array().cloned().unwrap_or_default();
+            for track in &medium_tracks {
+                tracks.push((
+                    disc,
+                    track["position"].as_u64().unwrap_or(0) as u32,
+                    medium_tracks.len() as u32,
+                    track["title"].as_str().unwrap_or("").to_string(),
+                    credit(track).or_else(|| release_artist.clone()),
+                    track["recording"]["id"].as_str().unwrap_or("").to_string(),
+                ));
+            }
+        }
+
+        let cover = if files.iter().any(|(_, tags)| !tags.has_picture) {
+            Self::front_cover(&agent, release_id).ok().map(Arc::new)
+        } else {
+            None
+        };
+
+        let mut changes = Vec::new();
+        for (index, (path, tags)) in files.iter().enumerate() {
+            let track = match (tags.disc, tags.track) {
+                (disc, Some(number)) => tracks
+                    .iter()
+                    .find(|t| t.0 == disc.unwrap_or(1) && t.1 == number),
+                _ => tracks.get(index),
+            };
+            let Some((disc, number, total, title, track_artist, recording_id)) = track else {
+                continue;
+            };
+
+            let mut items = vec![(ItemKey::MusicBrainzReleaseId, release_id.to_string())];
+            if !recording_id.is_empty() {
+                items.push((ItemKey::MusicBrainzRecordingId, recording_id.clone()));
+            }
+            if tags.title.is_none() {
+                items.push((ItemKey::TrackTitle, title.clone()));
+            }
+            if tags.artist.is_none()
+                && let Some(track_artist) = track_artist
+            {
+                items.push((ItemKey::TrackArtist, track_artist.clone()));
+            }
+            if tags.album.is_none() {
+                items.push((ItemKey::AlbumTitle, release_title.clone()));
+                if let Some(release_artist) = &release_artist {
+                    items.push((ItemKey::AlbumArtist, release_artist.clone()));
+                }
+            }
+            if tags.track.is_none() {
+                items.push((ItemKey::TrackNumber, number.to_string()));
+                items.push((ItemKey::TrackTotal, total.to_string()));
+            }
+            if tags.disc.is_none() {
+                items.push((ItemKey::DiscNumber, disc.to_string()));
+            }
+            if tags.year.is_none()
+                && let Some(year) = release_year
+            {
+                items.push((ItemKey::Year, year.to_string()));
+            }
+
+            changes.push(TagChange {
+                path: path.clone(),
+                items,
+                picture: cover.clone().filter(|_| !tags.has_picture),
+            });
+        }
+        if changes.is_empty() {
+            return Err("nessuna traccia corrisponde alla release trovata".into());
+        }
+
+        Ok(TagUpdate {
+            summary: format!(
+                "MusicBrainz: \"{}\"{} ({} tracce{}): completare i tag di {} file?",
+                release_title,
+                release_artist
+                    .as_ref()
+                    .map(|a| format!(" di {}", a))
+                    .unwrap_or_default(),
+                tracks.len(),
+                if cover.is_some() { ", copertina" } else { "" },
+                changes.len()
+            ),
+            files: changes,
+        })
+    }
+}
+
+/// Where the cover of a track comes from
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum CoverArt {
+    Embedded,
+    /// Image next to the file, or a downloaded cover in the cache
+    File(PathBuf),
+}
+
+impl CoverArt {
+    const FOLDER_IMAGES: [&str; 4] = ["cover.jpg", "cover.png", "folder.jpg", "front.jpg"];
+
+    /// Embedded picture first, then an image in the track's folder, then the cache
+    pub(crate) fn find(path: &Path, tags: &TrackTags) -> Option<Self> {
+        if tags.has_picture {
+            return Some(CoverArt::Embedded);
+        }
+        let folder = path.parent()?;
+        Self::FOLDER_IMAGES
+            .iter()
+            .map(|name| folder.join(name))
+            .chain(tags.release_id.as_deref().and_then(Self::cache_path))
+            .find(|image| image.is_file())
+            .map(CoverArt::File)
+    }
+
+    /// `<cache>/covers/<release id>.jpg`
+    fn cache_path(release_id: &str) -> Option<PathBuf> {
+        Some(
+            cache_dir()?
+                .join("covers")
+                .join(format!("{}.jpg", release_id)),
+        )
+    }
+
+    /// Downloads the release's front cover into the cache
+    pub(crate) fn fetch(release_id: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let path = Self::cache_path(release_id).ok_or("cartella cache non trovata")?;
+        let data = MusicBrainz::front_cover(&MusicBrainz::agent(), release_id)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, data)?;
+        Ok(path)
+    }
+
+    pub(crate) fn label(&self) -> String {
+        match self {
+            CoverArt::Embedded => "incorporata".to_string(),
+            CoverArt::File(path) => path.display().to_string(),
+        }
+    }
+}
+
+/// Lyrics of a track. LRC files carry a timestamp per line, plain text doesn't.
+#[derive(Clone, Debug)]
+pub(crate) struct Lyrics {
+    /// Original text, kept for the cache
+    text: String,
+    pub(crate) lines: Vec<(Option<Duration>, String)>,
+    /// Provider that found them
+    pub(crate) source: &'static str,
+}
+
+impl Lyrics {
+    /// Parses `[mm:ss.xx]` line tags (several per line allowed); metadata tags
+    /// like `[ar:...]` are dropped. Text without any timestamp stays plain.
+    fn parse(text: String, source: &'static str) -> Self {
+        let mut lines = Vec::new();
+        for raw in text.lines() {
+            let mut rest = raw.trim();
+            let mut times = Vec::new();
+            let mut is_metadata = false;
+            while let Some(tag) = rest.strip_prefix('[')
+                && let Some(end) = tag.find(']')
+            {
+                let (content, after) = (&tag[..end], &tag[end + 1..]);
+                match Self::parse_timestamp(content) {
+                    Some(time) => times.push(time),
+                    None => is_metadata = true,
+                }
+                rest = after;
+            }
+            if is_metadata && times.is_empty() {
+                continue;
+            }
+            if times.is_empty() {
+                lines.push((None, rest.to_string()));
+            }
+            for time in times {
+                lines.push((Some(time), rest.trim().to_string()));
+            }
+        }
+        if lines.iter().any(|(time, _)| time.is_some()) {
+            lines.retain(|(time, _)| time.is_some());
+            lines.sort_by_key(|(time, _)| *time);
+        }
+        Self {
+            text,
+            lines,
+            source,
+        }
+    }
+
+    /// `mm:ss`, `mm:ss.xx` or `mm:ss:xx`
+    fn parse_timestamp(tag: &str) -> Option<Duration> {
+        let (minutes, seconds) = tag.split_once(':')?;
+        let minutes: u64 = minutes.parse().ok()?;
+        let seconds: f64 = seconds.replacen(':', ".", 1).parse().ok()?;
+        Some(Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds))
+    }
+
+    fn is_synced(&self) -> bool {
+        self.lines.first().is_some_and(|(time, _)| time.is_some())
+    }
+
+    /// Index of the line being sung at `position` (synced lyrics only)
+    pub(crate) fn current_line(&self, position: Duration) -> Option<usize> {
+        if !self.is_synced() {
+            return None;
+        }
+        self.lines
+            .iter()
+            .rposition(|(time, _)| time.is_some_and(|t| t <= position))
+    }
+}
+
+/// What the lyrics providers know about the track being looked up
+pub(crate) struct LyricsQuery {
+    path: PathBuf,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    duration: Option<Duration>,
+}
+
+impl LyricsQuery {
+    /// Title and artist come from the tags, or from an "Artist - Title" file name
+    pub(crate) fn new(path: &Path, duration: Option<Duration>) -> Self {
+        let tags = TrackTags::read(&path.to_path_buf());
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (stem_artist, stem_title) = match stem.split_once(" - ") {
+            Some((artist, title)) => (Some(artist.trim().to_string()), title.trim().to_string()),
+            None => (None, stem.clone()),
+        };
+        Self {
+            path: path.to_path_buf(),
+            title: tags.title.or(Some(stem_title)),
+            artist: tags.artist.or(stem_artist),
+            album: tags.album,
+            duration,
+        }
+    }
+
+    /// File name used for the cache: "artist - title.lrc"
+    fn cache_key(&self) -> Option<String> {
+        let name = format!("{} - {}", self.artist.as_ref()?, self.title.as_ref()?);
+        Some(
+            name.chars()
+                .map(|c| {
+                    if matches!(c, '/' | '\\' | ':') {
+                        '_'
+                    } else {
+                        c
+                    }
+                })
+                .collect::<String>()
+                + ".lrc",
+        )
+    }
+}
+
+/// Source of song lyrics. `Ok(None)` means the provider has nothing for the track.
+trait LyricsProvider: Send {
+    fn name(&self) -> &'static str;
+    fn fetch(&self, query: &LyricsQuery) -> Result<Option<Lyrics>, Box<dyn std::error::Error>>;
+    /// Online providers only run on an explicit search
+    fn is_online(&self) -> bool {
+        false
+    }
+}
+
+/// `.lrc` or `.txt` file next to the track, with the same name
+struct LocalLyrics;
+
+impl LyricsProvider for LocalLyrics {
+    fn name(&self) -> &'static str {
+        "file locale"
+    }
+
+    fn fetch(&self, query: &LyricsQuery) -> Result<Option<Lyrics>, Box<dyn std::error::Error>> {
+        for ext in ["lrc", "txt"] {
+            let path = query.path.with_extension(ext);
+            if path.is_file() {
+                return Ok(Some(Lyrics::parse(fs::read_to_string(path)?, self.name())));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Lyrics downloaded earlier, in `<cache>/lyrics`
+struct CachedLyrics;
+
+impl CachedLyrics {
+    fn path(query: &LyricsQuery) -> Option<PathBuf> {
+        Some(cache_dir()?.join("lyrics").join(query.cache_key()?))
+    }
+
+    fn store(query: &LyricsQuery, lyrics: &Lyrics) -> io::Result<()> {
+        let Some(path) = Self::path(query) else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, &lyrics.text)
+    }
+}
+
+impl LyricsProvider for CachedLyrics {
+    fn name(&self) -> &'static str {
+        "cache"
+    }
+
+    fn fetch(&self, query: &LyricsQuery) -> Result<Option<Lyrics>, Box<dyn std::error::Error>> {
+        match Self::path(query) {
+            Some(path) if path.is_file() => {
+                Ok(Some(Lyrics::parse(fs::read_to_string(path)?, self.name())))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// lrclib.net, free and without API keys; prefers synced lyrics
+struct LrcLib;
+
+impl LrcLib {
+    const API: &str = "https://lrclib.net/api";
+}
+
+impl LyricsProvider for LrcLib {
+    fn name(&self) -> &'static str {
+        "lrclib.net"
+    }
+
+    fn is_online(&self) -> bool {
+        true
+    }
+
+    fn fetch(&self, query: &LyricsQuery) -> Result<Option<Lyrics>, Box<dyn std::error::Error>> {
+        let (Some(title), Some(artist)) = (&query.title, &query.artist) else {
+            return Ok(None);
+        };
+        let agent = MusicBrainz::agent();
+
+        // Exact match first (needs the duration), then a looser search
+        let mut request = agent
+            .get(&format!("{}/get", Self::API))
+            .query("track_name", title)
+            .query("artist_name", artist);
+        if let Some(album) = &query.album {
+            request = request.query("album_name", album);
+        }
+        if let Some(duration) = query.duration {
+            request = request.query("duration", &duration.as_secs().to_string());
+        }
+        let record = match request.call() {
+            Ok(response) => serde_json::from_str(&response.into_string()?)?,
+            Err(ureq::Error::Status(404, _)) => {
+                let response = agent
+                    .get(&format!("{}/search", Self::API))
+                    .query("track_name", title)
+                    .query("artist_name", artist)
+                    .call()?;
+                let results: serde_json::Value = serde_json::from_str(&response.into_string()?)?;
+                match results.get(0) {
+                    Some(record) => record.clone(),
+                    None => return Ok(None),
+                }
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let text = record["syncedLyrics"]
+            .as_str()
+            .or_else(|| record["plainLyrics"].as_str())
+            .filter(|text| !text.trim().is_empty());
+        Ok(text.map(|text| Lyrics::parse(text.to_string(), self.name())))
+    }
+}
+
+/// Providers in lookup order: local files, then the cache, then the network
+fn lyrics_providers() -> Vec<Box<dyn LyricsProvider>> {
+    vec![
+        Box::new(LocalLyrics),
+        Box::new(CachedLyrics),
+        Box::new(LrcLib),
+    ]
+}
+
+/// First lyrics found for `query`. Lyrics from online providers are cached.
+pub(crate) fn find_lyrics(
+    query: &LyricsQuery,
+    online: bool,
+) -> Result<Option<Lyrics>, Box<dyn std::error::Error>> {
+    let mut last_error = None;
+    for provider in lyrics_providers() {
+        if provider.is_online() && !online {
+            continue;
+        }
+        match provider.fetch(query) {
+            Ok(Some(lyrics)) => {
+                if provider.is_online() {
+                    let _ = CachedLyrics::store(query, &lyrics);
+                }
+                return Ok(Some(lyrics));
+            }
+            Ok(None) => {}
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match last_error {
+        Some(e) => Err(e),
+        None => Ok(None),
+    }
+}
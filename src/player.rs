@@ -0,0 +1,5047 @@
+//! Audio playback: decoding, the DSP chain, the output device and the
+//! player driving it.
+
+use lofty::{config::ParseOptions, file::AudioFile, probe::Probe};
+use rodio::{
+    Decoder, Sink, Source,
+    cpal::{
+        self, BufferSize, FromSample, SampleFormat, SizedSample, StreamConfig, SupportedBufferSize,
+        traits::{DeviceTrait, HostTrait, StreamTrait},
+    },
+    dynamic_mixer::{self, DynamicMixer, DynamicMixerController},
+    source::SeekError,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, SyncSender},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::app::{Settings, config_dir};
+use crate::broadcast::Broadcast;
+use crate::browser::Archive;
+use crate::chiptune::Chiptune;
+use crate::fft::FFT_SIZE;
+
+/// Samples captured for the analyzer, limited to a time window of the
+/// current track, with counters for diagnostics
+#[derive(Default)]
+struct CaptureBuffer {
+    samples: VecDeque<f32>,
+    /// Limit in samples, derived from the window and the track's format
+    max_size: usize,
+    /// Frames written since the analyzer last read the buffer
+    unread: usize,
+    /// Frames captured since the track started
+    written: u64,
+    /// Frames dropped before the analyzer got to read them
+    overruns: u64,
+}
+
+/// Snapshot of the capture buffer shown by the profiler
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CaptureStats {
+    pub(crate) used_bytes: usize,
+    pub(crate) allocated_bytes: usize,
+    /// 0.0-1.0 of the configured window
+    pub(crate) fill: f32,
+    pub(crate) written: u64,
+    pub(crate) overruns: u64,
+}
+
+impl CaptureBuffer {
+    /// Empties the buffer and sizes it to hold `window` of audio in the given
+    /// format, never less than one FFT block
+    fn reset(&mut self, window: Duration, sample_rate: u32, channels: u16) {
+        let frames = (window.as_secs_f64() * sample_rate as f64).ceil() as usize;
+        self.max_size = frames.max(FFT_SIZE) * channels.max(1) as usize;
+        self.samples.clear();
+        self.samples.shrink_to(self.max_size);
+        self.unread = 0;
+        self.written = 0;
+        self.overruns = 0;
+    }
+
+    fn push_frame(&mut self, frame: &[f32]) {
+        while !self.samples.is_empty() && self.samples.len() + frame.len() > self.max_size {
+            self.samples.drain(..frame.len().min(self.samples.len()));
+            let frames = self.samples.len() / frame.len();
+            if self.unread > frames {
+                self.overruns += 1;
+                self.unread = frames;
+            }
+        }
+        self.samples.extend(frame);
+        self.unread += 1;
+        self.written += 1;
+    }
+
+    fn mark_read(&mut self) {
+        self.unread = 0;
+    }
+
+    fn clear(&mut self) {
+        self.samples.clear();
+        self.unread = 0;
+    }
+
+    fn stats(&self) -> CaptureStats {
+        let sample = std::mem::size_of::<f32>();
+        CaptureStats {
+            used_bytes: self.samples.len() * sample,
+            allocated_bytes: self.samples.capacity() * sample,
+            fill: if self.max_size == 0 {
+                0.0
+            } else {
+                self.samples.len() as f32 / self.max_size as f32
+            },
+            written: self.written,
+            overruns: self.overruns,
+        }
+    }
+}
+
+/// Wrapper that captures audio samples from an underlying rodio Source.
+/// It stores the samples in a shared ring buffer (Arc<Mutex<CaptureBuffer>>)
+/// for real-time FFT visualization while passing the samples unchanged
+/// to the audio output. The buffer holds a configurable time window of audio.
+/// Samples are stored in whole interleaved frames, so readers can split the
+/// channels. While `enabled` is false samples pass through without touching the buffer.
+/// Every frame passing through is counted in `frames`, which makes the
+/// playback position: seeks set it, and pauses, speed changes and underruns
+/// can't make it drift from the track.
+struct SampleCapturer<I> {
+    input: I,
+    buffer: Arc<Mutex<CaptureBuffer>>,
+    enabled: Arc<AtomicBool>,
+    frames: Arc<AtomicU64>,
+    channels: usize,
+    position: usize,
+    frame: Vec<f32>,
+}
+
+impl<I> SampleCapturer<I>
+where
+    I: Source<Item = f32>,
+{
+    fn new(
+        input: I,
+        buffer: Arc<Mutex<CaptureBuffer>>,
+        enabled: Arc<AtomicBool>,
+        frames: Arc<AtomicU64>,
+    ) -> Self {
+        let channels = input.channels().max(1) as usize;
+        frames.store(0, Ordering::Relaxed);
+        Self {
+            input,
+            buffer,
+            enabled,
+            frames,
+            channels,
+            position: 0,
+            frame: Vec::with_capacity(channels),
+        }
+    }
+}
+
+impl<I> Iterator for SampleCapturer<I>
+where
+    I: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+
+        // Keep counting channels while disabled so frames stay aligned
+        let channel = self.position;
+        self.position = (self.position + 1) % self.channels;
+        if self.position == 0 {
+            self.frames.fetch_add(1, Ordering::Relaxed);
+        }
+        if !self.enabled.load(Ordering::Relaxed) {
+            self.frame.clear();
+            return Some(sample);
+        }
+
+        if channel == 0 {
+            self.frame.clear();
+        }
+        self.frame.push(sample);
+
+        if self.frame.len() == self.channels {
+            self.buffer.lock().unwrap().push_frame(&self.frame);
+            self.frame.clear();
+        }
+        Some(sample)
+    }
+}
+
+impl<I> Source for SampleCapturer<I>
+where
+    I: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        // Decoders land on frame boundaries, so restart the channel count
+        self.position = 0;
+        self.frame.clear();
+        self.buffer.lock().unwrap().clear();
+        let frames = pos.as_secs_f64() * self.input.sample_rate() as f64;
+        self.frames.store(frames.round() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Speaker positions in the channel order used by WAV, FLAC and Vorbis
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Speaker {
+    FrontLeft,
+    FrontRight,
+    Center,
+    Lfe,
+    SurroundLeft,
+    SurroundRight,
+    BackCenter,
+}
+
+impl Speaker {
+    /// Layout of a source with `channels` channels, for the multichannel
+    /// counts that have a standard order
+    fn layout(channels: u16) -> Option<&'static [Speaker]> {
+        use Speaker::*;
+        match channels {
+            3 => Some(&[FrontLeft, FrontRight, Center]),
+            4 => Some(&[FrontLeft, FrontRight, SurroundLeft, SurroundRight]),
+            5 => Some(&[FrontLeft, FrontRight, Center, SurroundLeft, SurroundRight]),
+            6 => Some(&[
+                FrontLeft,
+                FrontRight,
+                Center,
+                Lfe,
+                SurroundLeft,
+                SurroundRight,
+            ]),
+            7 => Some(&[
+                FrontLeft,
+                FrontRight,
+                Center,
+                Lfe,
+                BackCenter,
+                SurroundLeft,
+                SurroundRight,
+            ]),
+            // 7.1 puts the back pair before the sides; both fold into the surrounds
+            8 => Some(&[
+                FrontLeft,
+                FrontRight,
+                Center,
+                Lfe,
+                SurroundLeft,
+                SurroundRight,
+                SurroundLeft,
+                SurroundRight,
+            ]),
+            _ => None,
+        }
+    }
+
+    /// "5.1", "7.1"... for the info panel
+    fn layout_name(channels: u16) -> String {
+        match channels {
+            1 => "mono".to_string(),
+            2 => "stereo".to_string(),
+            3 => "3.0".to_string(),
+            4 => "4.0".to_string(),
+            5 => "5.0".to_string(),
+            6 => "5.1".to_string(),
+            7 => "6.1".to_string(),
+            8 => "7.1".to_string(),
+            n => format!("{} ch", n),
+        }
+    }
+}
+
+/// How multichannel sources are folded to stereo
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DownmixSettings {
+    pub(crate) enabled: bool,
+    /// Linear gains for the center, surround and LFE channels
+    pub(crate) center: f32,
+    pub(crate) surround: f32,
+    pub(crate) lfe: f32,
+}
+
+impl Default for DownmixSettings {
+    /// ITU-R BS.775 coefficients, LFE dropped
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            center: std::f32::consts::FRAC_1_SQRT_2,
+            surround: std::f32::consts::FRAC_1_SQRT_2,
+            lfe: 0.0,
+        }
+    }
+}
+
+/// Folds a 3-8 channel source down to stereo. The matrix is scaled so
+/// that full-scale audio in every channel can't clip.
+struct Downmix<I> {
+    input: I,
+    /// (left, right) gain of each input channel
+    matrix: Vec<[f32; 2]>,
+    frame: Vec<f32>,
+    /// Right sample of the frame whose left sample was returned last
+    right: Option<f32>,
+}
+
+impl<I> Downmix<I>
+where
+    I: Source<Item = f32>,
+{
+    /// `layout` gives the speaker of each of the input's channels
+    fn new(input: I, layout: &[Speaker], settings: &DownmixSettings) -> Self {
+        let side = std::f32::consts::FRAC_1_SQRT_2;
+        let mut matrix: Vec<[f32; 2]> = layout
+            .iter()
+            .map(|speaker| match speaker {
+                Speaker::FrontLeft => [1.0, 0.0],
+                Speaker::FrontRight => [0.0, 1.0],
+                Speaker::Center => [settings.center; 2],
+                Speaker::Lfe => [settings.lfe; 2],
+                Speaker::SurroundLeft => [settings.surround, 0.0],
+                Speaker::SurroundRight => [0.0, settings.surround],
+                Speaker::BackCenter => [settings.surround * side; 2],
+            })
+            .collect();
+        let loudest = matrix.iter().fold([0.0f32; 2], |sum, gains| {
+            [sum[0] + gains[0], sum[1] + gains[1]]
+        });
+        let scale = 1.0 / loudest[0].max(loudest[1]).max(1.0);
+        for gains in &mut matrix {
+            gains[0] *= scale;
+            gains[1] *= scale;
+        }
+        Self {
+            frame: Vec::with_capacity(matrix.len()),
+            input,
+            matrix,
+            right: None,
+        }
+    }
+}
+
+impl<I> Iterator for Downmix<I>
+where
+    I: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        self.frame.clear();
+        self.frame.push(self.input.next()?);
+        while self.frame.len() < self.matrix.len() {
+            // A truncated last frame is completed with silence
+            self.frame.push(self.input.next().unwrap_or(0.0));
+        }
+        let (left, right) = self
+            .frame
+            .iter()
+            .zip(&self.matrix)
+            .fold((0.0, 0.0), |(l, r), (sample, gains)| {
+                (l + sample * gains[0], r + sample * gains[1])
+            });
+        self.right = Some(right);
+        Some(left)
+    }
+}
+
+impl<I> Source for Downmix<I>
+where
+    I: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input
+            .current_frame_len()
+            .map(|len| len / self.matrix.len() * 2 + self.right.is_some() as usize)
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.right = None;
+        self.input.try_seek(pos)
+    }
+}
+
+/// Centre frequencies of the equalizer bands
+const EQ_BANDS: [f64; 5] = [60.0, 250.0, 1000.0, 4000.0, 12000.0];
+
+/// Equalizer presets: name and gain of each band in dB. The first one is
+/// flat and leaves the signal untouched. Profiles store the index, so new
+/// presets go at the end.
+pub(crate) const EQ_PRESETS: [(&str, [f64; 5]); 10] = [
+    ("Flat", [0.0; 5]),
+    ("Bassi", [6.0, 3.0, 0.0, 0.0, 0.0]),
+    ("Voce", [-2.0, 0.0, 3.0, 2.0, 0.0]),
+    ("Alti", [0.0, 0.0, 0.0, 3.0, 6.0]),
+    ("Loudness", [5.0, 1.0, 0.0, 1.0, 4.0]),
+    ("Rock", [4.0, 1.0, -1.0, 2.0, 4.0]),
+    ("Pop", [-1.0, 2.0, 3.0, 2.0, -1.0]),
+    ("Jazz", [3.0, 1.0, 0.0, 1.0, 3.0]),
+    ("Classica", [2.0, 0.0, 0.0, 0.0, 3.0]),
+    ("Elettronica", [5.0, 2.0, 0.0, 1.0, 3.0]),
+];
+
+/// Five-band peaking equalizer. The preset is read from `control` at every
+/// frame, so changes apply to the track already playing.
+struct Equalizer<I> {
+    input: I,
+    control: Arc<AtomicUsize>,
+    preset: usize,
+    /// Band filters of each channel
+    filters: Vec<Vec<Biquad>>,
+    /// Lowers the signal by the largest boost, so boosted bands can't clip
+    preamp: f64,
+    channel: usize,
+}
+
+impl<I> Equalizer<I>
+where
+    I: Source<Item = f32>,
+{
+    fn new(input: I, control: Arc<AtomicUsize>) -> Self {
+        let mut eq = Self {
+            input,
+            control,
+            preset: 0,
+            filters: Vec::new(),
+            preamp: 1.0,
+            channel: 0,
+        };
+        eq.load_preset();
+        eq
+    }
+
+    fn load_preset(&mut self) {
+        self.preset = self
+            .control
+            .load(Ordering::Relaxed)
+            .min(EQ_PRESETS.len() - 1);
+        let gains = EQ_PRESETS[self.preset].1;
+        let rate = self.input.sample_rate();
+        // Bands too close to Nyquist can't be realised at low sample rates
+        let bands: Vec<Biquad> = EQ_BANDS
+            .iter()
+            .zip(gains)
+            .filter(|&(&freq, gain)| gain != 0.0 && freq < rate as f64 * 0.45)
+            .map(|(&freq, gain)| Biquad::peaking(rate, freq, 1.0, gain))
+            .collect();
+        self.filters = vec![bands; self.input.channels().max(1) as usize];
+        let boost = gains.iter().fold(0.0f64, |max, &gain| max.max(gain));
+        self.preamp = 10f64.powf(-boost / 20.0);
+    }
+}
+
+impl<I> Iterator for Equalizer<I>
+where
+    I: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        if self.channel == 0 && self.control.load(Ordering::Relaxed) != self.preset {
+            self.load_preset();
+        }
+        let channel = self.channel;
+        self.channel = (channel + 1) % self.filters.len();
+        let filters = &mut self.filters[channel];
+        if filters.is_empty() {
+            return Some(sample);
+        }
+        let output = filters
+            .iter_mut()
+            .fold(sample as f64 * self.preamp, |x, filter| filter.process(x));
+        Some(output as f32)
+    }
+}
+
+impl<I> Source for Equalizer<I>
+where
+    I: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.channel = 0;
+        self.load_preset();
+        Ok(())
+    }
+}
+
+/// Lets only one frequency band through, so what a spectrum bar shows can
+/// be heard on its own. The band is read from `control` at every frame: the
+/// low and high edge in Hz as the two halves of the word (f32 bits), 0 for
+/// no solo, which leaves the samples untouched.
+struct BandSolo<I> {
+    input: I,
+    control: Arc<AtomicU64>,
+    band: u64,
+    /// Two band-pass sections per channel, for steeper edges than one
+    filters: Vec<[Biquad; 2]>,
+    channel: usize,
+}
+
+impl<I> BandSolo<I>
+where
+    I: Source<Item = f32>,
+{
+    fn new(input: I, control: Arc<AtomicU64>) -> Self {
+        let mut solo = Self {
+            input,
+            control,
+            band: 0,
+            filters: Vec::new(),
+            channel: 0,
+        };
+        solo.load_band();
+        solo
+    }
+
+    fn load_band(&mut self) {
+        self.band = self.control.load(Ordering::Relaxed);
+        self.filters.clear();
+        let Some((low, high)) = unpack_band(self.band) else {
+            return;
+        };
+        let rate = self.input.sample_rate() as f64;
+        let (low, high) = (low as f64, (high as f64).min(rate * 0.45));
+        let center = (low * high).sqrt();
+        let q = center / (high - low).max(1.0);
+        let section = Biquad::band_pass(self.input.sample_rate(), center, q);
+        self.filters = vec![[section; 2]; self.input.channels().max(1) as usize];
+    }
+}
+
+fn pack_band(band: Option<(f32, f32)>) -> u64 {
+    band.map_or(0, |(low, high)| {
+        (low.to_bits() as u64) << 32 | high.to_bits() as u64
+    })
+}
+
+fn unpack_band(band: u64) -> Option<(f32, f32)> {
+    (band != 0).then(|| {
+        (
+            f32::from_bits((band >> 32) as u32),
+            f32::from_bits(band as u32),
+        )
+    })
+}
+
+impl<I> Iterator for BandSolo<I>
+where
+    I: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        if self.channel == 0 && self.control.load(Ordering::Relaxed) != self.band {
+            self.load_band();
+        }
+        if self.filters.is_empty() {
+            return Some(sample);
+        }
+        let channel = self.channel;
+        self.channel = (channel + 1) % self.filters.len();
+        let output = self.filters[channel]
+            .iter_mut()
+            .fold(sample as f64, |x, filter| filter.process(x));
+        Some(output as f32)
+    }
+}
+
+impl<I> Source for BandSolo<I>
+where
+    I: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.channel = 0;
+        self.load_band();
+        Ok(())
+    }
+}
+
+/// Oversampling of the true-peak meter (ITU-R BS.1770 asks for at least 4x
+/// at 48 kHz) and taps of its interpolation filter per phase
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+const TRUE_PEAK_TAPS: usize = 12;
+
+/// Highest level reached between the samples of a track and how many times
+/// it went over full scale, shared between its `TruePeakMeter` and the player
+#[derive(Default)]
+pub(crate) struct TruePeak {
+    /// Linear peak as f32 bits; non-negative floats order like their bits
+    peak: AtomicU32,
+    clips: AtomicU64,
+}
+
+impl TruePeak {
+    pub(crate) fn reading(&self) -> TruePeakReading {
+        let peak = f32::from_bits(self.peak.load(Ordering::Relaxed));
+        TruePeakReading {
+            peak_db: 20.0 * peak.max(1e-6).log10(),
+            clips: self.clips.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct TruePeakReading {
+    /// dBTP: 0 is full scale, the headroom is its opposite
+    pub(crate) peak_db: f32,
+    /// Stretches of the waveform at or over full scale, counted once each
+    pub(crate) clips: u64,
+}
+
+/// Measures the true peak of a track as it plays: every sample is
+/// interpolated `TRUE_PEAK_OVERSAMPLING` times with a windowed-sinc filter,
+/// which finds the overs that fall between samples and that a DAC or a
+/// lossy encoder would clip. Samples pass through untouched.
+struct TruePeakMeter<I> {
+    input: I,
+    meter: Arc<TruePeak>,
+    /// One row of filter taps per phase
+    phases: [[f32; TRUE_PEAK_TAPS]; TRUE_PEAK_OVERSAMPLING],
+    /// Last `TRUE_PEAK_TAPS` samples of each channel, newest first
+    history: Vec<[f32; TRUE_PEAK_TAPS]>,
+    /// Whether each channel is in an over, so a stretch counts once
+    over: Vec<bool>,
+    peak: f32,
+    channel: usize,
+}
+
+impl<I> TruePeakMeter<I>
+where
+    I: Source<Item = f32>,
+{
+    fn new(input: I, meter: Arc<TruePeak>) -> Self {
+        use std::f32::consts::PI;
+        let channels = input.channels().max(1) as usize;
+        let length = TRUE_PEAK_OVERSAMPLING * TRUE_PEAK_TAPS;
+        let center = (length - 1) as f32 / 2.0;
+        let mut phases = [[0.0; TRUE_PEAK_TAPS]; TRUE_PEAK_OVERSAMPLING];
+        for (phase, taps) in phases.iter_mut().enumerate() {
+            for (tap, coefficient) in taps.iter_mut().enumerate() {
+                let n = (tap * TRUE_PEAK_OVERSAMPLING + phase) as f32;
+                let x = (n - center) / TRUE_PEAK_OVERSAMPLING as f32;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (PI * x).sin() / (PI * x)
+                };
+                let window = 0.5 - 0.5 * (2.0 * PI * (n + 0.5) / length as f32).cos();
+                *coefficient = sinc * window;
+            }
+            // Unity gain at DC for every phase
+            let sum: f32 = taps.iter().sum();
+            taps.iter_mut().for_each(|coefficient| *coefficient /= sum);
+        }
+        Self {
+            input,
+            meter,
+            phases,
+            history: vec![[0.0; TRUE_PEAK_TAPS]; channels],
+            over: vec![false; channels],
+            peak: 0.0,
+            channel: 0,
+        }
+    }
+}
+
+impl<I> Iterator for TruePeakMeter<I>
+where
+    I: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        let channel = self.channel;
+        self.channel = (channel + 1) % self.history.len();
+
+        let history = &mut self.history[channel];
+        history.copy_within(..TRUE_PEAK_TAPS - 1, 1);
+        history[0] = sample;
+        let level = self
+            .phases
+            .iter()
+            .map(|taps| {
+                taps.iter()
+                    .zip(history.iter())
+                    .map(|(tap, x)| tap * x)
+                    .sum::<f32>()
+                    .abs()
+            })
+            .fold(sample.abs(), f32::max);
+
+        let over = level >= 1.0;
+        if over && !self.over[channel] {
+            self.meter.clips.fetch_add(1, Ordering::Relaxed);
+        }
+        self.over[channel] = over;
+        if level > self.peak && level.is_finite() {
+            self.peak = level;
+            self.meter
+                .peak
+                .fetch_max(level.to_bits(), Ordering::Relaxed);
+        }
+        Some(sample)
+    }
+}
+
+impl<I> Source for TruePeakMeter<I>
+where
+    I: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.channel = 0;
+        // Interpolating across the jump would make up a peak
+        self.history
+            .iter_mut()
+            .for_each(|history| history.fill(0.0));
+        self.over.fill(false);
+        Ok(())
+    }
+}
+
+/// How long a gain change takes to glide from silence to full level;
+/// smaller changes take proportionally less
+const GAIN_RAMP: Duration = Duration::from_millis(20);
+
+/// A factor read from `target` (f32 bits) at every frame and approached
+/// linearly, at the pace of a full-scale change in `attack` going down and
+/// in `release` coming back up
+struct Ramp {
+    target: Arc<AtomicU32>,
+    current: f32,
+    /// Largest change per frame, going down and up
+    down: f32,
+    up: f32,
+}
+
+impl Ramp {
+    fn new(target: Arc<AtomicU32>, attack: Duration, release: Duration, rate: u32) -> Self {
+        let step = |ramp: Duration| 1.0 / (ramp.as_secs_f32() * rate as f32).max(1.0);
+        Self {
+            current: f32::from_bits(target.load(Ordering::Relaxed)),
+            target,
+            down: step(attack),
+            up: step(release),
+        }
+    }
+
+    fn advance(&mut self) {
+        let target = f32::from_bits(self.target.load(Ordering::Relaxed));
+        self.current = if target < self.current {
+            (self.current - self.down).max(target)
+        } else {
+            (self.current + self.up).min(target)
+        };
+    }
+}
+
+/// Volume of a stream, the only place it is applied, ducking included.
+/// The level follows volume and fade changes within `GAIN_RAMP`; the duck,
+/// on the music only, moves at the ducking pace. Both glide, so changes
+/// take effect on the track playing without clicks.
+struct Gain<I> {
+    input: I,
+    level: Ramp,
+    duck: Option<Ramp>,
+    channel: usize,
+}
+
+impl<I> Gain<I>
+where
+    I: Source<Item = f32>,
+{
+    fn new(input: I, level: Arc<AtomicU32>) -> Self {
+        Self {
+            level: Ramp::new(level, GAIN_RAMP, GAIN_RAMP, input.sample_rate()),
+            duck: None,
+            channel: 0,
+            input,
+        }
+    }
+
+    /// Also lowered by the factor in `duck`, at the pace in `settings`
+    fn ducked(mut self, duck: Arc<AtomicU32>, settings: &DuckSettings) -> Self {
+        let rate = self.input.sample_rate();
+        self.duck = Some(Ramp::new(duck, settings.attack, settings.release, rate));
+        self
+    }
+}
+
+impl<I> Iterator for Gain<I>
+where
+    I: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        if self.channel == 0 {
+            self.level.advance();
+            if let Some(duck) = &mut self.duck {
+                duck.advance();
+            }
+        }
+        self.channel = (self.channel + 1) % self.input.channels().max(1) as usize;
+        let gain = self.level.current * self.duck.as_ref().map_or(1.0, |duck| duck.current);
+        // Unity leaves the samples untouched, for bit-perfect output
+        if gain == 1.0 {
+            Some(sample)
+        } else {
+            Some(sample * gain)
+        }
+    }
+}
+
+impl<I> Source for Gain<I>
+where
+    I: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.channel = 0;
+        Ok(())
+    }
+}
+
+/// How the music makes way for an announcement
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DuckSettings {
+    /// Linear gain of the music while ducked
+    pub(crate) depth: f32,
+    /// Time to go down to `depth`, and to come back up to full level
+    pub(crate) attack: Duration,
+    pub(crate) release: Duration,
+}
+
+impl Default for DuckSettings {
+    /// -15 dB, fast enough not to cover the first words
+    fn default() -> Self {
+        Self {
+            depth: 10f32.powf(-15.0 / 20.0),
+            attack: Duration::from_millis(300),
+            release: Duration::from_millis(1500),
+        }
+    }
+}
+
+/// Shape of the fades between tracks. Linear suits speech; equal-power
+/// keeps the loudness of two overlapping tracks even, which suits music.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum FadeCurve {
+    #[default]
+    Linear,
+    EqualPower,
+    SCurve,
+}
+
+impl FadeCurve {
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        match text {
+            "linear" => Some(FadeCurve::Linear),
+            "equal-power" => Some(FadeCurve::EqualPower),
+            "s-curve" => Some(FadeCurve::SCurve),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn next(self) -> Self {
+        match self {
+            FadeCurve::Linear => FadeCurve::EqualPower,
+            FadeCurve::EqualPower => FadeCurve::SCurve,
+            FadeCurve::SCurve => FadeCurve::Linear,
+        }
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            FadeCurve::Linear => "Lineare",
+            FadeCurve::EqualPower => "Equal-power",
+            FadeCurve::SCurve => "Curva a S",
+        }
+    }
+
+    /// Gain `progress` (0-1) into a fade-in; a fade-out is the same curve
+    /// read from the end, so the two halves of a crossfade mirror each other
+    pub(crate) fn gain(&self, progress: f32) -> f32 {
+        let progress = progress.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => progress,
+            FadeCurve::EqualPower => (progress * std::f32::consts::FRAC_PI_2).sin(),
+            FadeCurve::SCurve => progress * progress * (3.0 - 2.0 * progress),
+        }
+    }
+}
+
+/// Track queued to follow the playing one on the same sink, see `Gapless`
+struct Follower {
+    source: Box<dyn Source<Item = f32> + Send>,
+    /// Where the source was sought to, in frames
+    start: u64,
+    /// Frames the two tracks overlap for once a crossfade starts
+    overlap: u64,
+    curve: FadeCurve,
+}
+
+type NextSource = Arc<Mutex<Option<Follower>>>;
+
+/// The track fading out under the next one during a crossfade
+struct Fade {
+    source: Box<dyn Source<Item = f32> + Send>,
+    frame: u64,
+    frames: u64,
+    curve: FadeCurve,
+}
+
+/// Plays the track in `next` as soon as the current one runs out, in the
+/// same stream, so there is no silence between them. When `crossfade` is
+/// set it starts the next track at the next frame instead, mixing it over
+/// the fading end of the current one. The switch sets `switched` and
+/// restarts the `frames` count of the capturer further on; the slot is only
+/// tried, never waited for, on the audio thread. While it is busy the
+/// stream plays silence and tries again, rather than ending.
+struct Gapless {
+    current: Box<dyn Source<Item = f32> + Send>,
+    next: NextSource,
+    crossfade: Arc<AtomicBool>,
+    switched: Arc<AtomicBool>,
+    frames: Arc<AtomicU64>,
+    fading: Option<Fade>,
+    channel: usize,
+}
+
+impl Gapless {
+    fn new(
+        current: Box<dyn Source<Item = f32> + Send>,
+        next: NextSource,
+        crossfade: Arc<AtomicBool>,
+        switched: Arc<AtomicBool>,
+        frames: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            current,
+            next,
+            crossfade,
+            switched,
+            frames,
+            fading: None,
+            channel: 0,
+        }
+    }
+
+    /// Makes the queued track the current one
+    fn switch(&mut self) -> Switch {
+        let Ok(mut slot) = self.next.try_lock() else {
+            return Switch::Busy;
+        };
+        let Some(follower) = slot.take() else {
+            return Switch::Empty;
+        };
+        // Set while the slot is held, so a cancel can tell it came too late
+        self.switched.store(true, Ordering::Relaxed);
+        self.frames.store(follower.start, Ordering::Relaxed);
+        drop(slot);
+        let previous = std::mem::replace(&mut self.current, follower.source);
+        Switch::Done(previous, follower.overlap, follower.curve)
+    }
+}
+
+/// What `Gapless::switch` found in the slot
+enum Switch {
+    /// The queued track plays now; the one it replaced, with the overlap and
+    /// curve of the crossfade
+    Done(Box<dyn Source<Item = f32> + Send>, u64, FadeCurve),
+    Empty,
+    /// The UI thread holds the slot at this moment
+    Busy,
+}
+
+impl Iterator for Gapless {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.channel == 0 && self.fading.is_none() && self.crossfade.load(Ordering::Relaxed) {
+            match self.switch() {
+                Switch::Done(previous, overlap, curve) => {
+                    self.crossfade.store(false, Ordering::Relaxed);
+                    self.fading = Some(Fade {
+                        source: previous,
+                        frame: 0,
+                        frames: overlap.max(1),
+                        curve,
+                    });
+                }
+                Switch::Empty => self.crossfade.store(false, Ordering::Relaxed),
+                Switch::Busy => {}
+            }
+        }
+        let channels = self.current.channels().max(1) as usize;
+        let sample = match self.current.next() {
+            Some(sample) => sample,
+            None => {
+                self.fading = None;
+                // Whole frames of silence until the slot is free, so the
+                // next track starts on its first channel
+                let switched = self.channel == 0
+                    && match self.switch() {
+                        Switch::Done(..) => true,
+                        Switch::Empty => return None,
+                        Switch::Busy => false,
+                    };
+                if !switched {
+                    self.channel = (self.channel + 1) % channels;
+                    return Some(0.0);
+                }
+                self.current.next()?
+            }
+        };
+        let sample = match &mut self.fading {
+            Some(fade) => {
+                let progress = fade.frame as f32 / fade.frames as f32;
+                let out = fade.source.next().unwrap_or(0.0) * fade.curve.gain(1.0 - progress);
+                if self.channel + 1 == channels {
+                    fade.frame += 1;
+                }
+                sample * fade.curve.gain(progress) + out
+            }
+            None => sample,
+        };
+        if self
+            .fading
+            .as_ref()
+            .is_some_and(|fade| fade.frame >= fade.frames)
+        {
+            self.fading = None;
+        }
+        self.channel = (self.channel + 1) % channels;
+        Some(sample)
+    }
+}
+
+impl Source for Gapless {
+    /// Unbounded: the format never changes across the switch, and a frame
+    /// ending with the track would have the mixer take the stream for over
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.current.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.current.sample_rate()
+    }
+
+    /// Unknown, the stream may go on with another track
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.current.try_seek(pos)?;
+        // The jump leaves the fading track behind
+        self.fading = None;
+        self.channel = 0;
+        Ok(())
+    }
+}
+
+/// Click track: a short decaying sine on every beat, higher on the first beat
+/// of each bar of four. The tempo is read from `bpm` (f32 bits) once per beat,
+/// so changes apply from the next click.
+struct Metronome {
+    bpm: Arc<AtomicU32>,
+    sample_rate: u32,
+    /// Samples into the current beat and its length
+    position: usize,
+    beat_length: usize,
+    beat: u32,
+}
+
+impl Metronome {
+    const SAMPLE_RATE: u32 = 44100;
+    const CLICK: Duration = Duration::from_millis(30);
+
+    fn new(bpm: Arc<AtomicU32>) -> Self {
+        let mut metronome = Self {
+            bpm,
+            sample_rate: Self::SAMPLE_RATE,
+            position: 0,
+            beat_length: 0,
+            beat: 0,
+        };
+        metronome.beat_length = metronome.beat_length();
+        metronome
+    }
+
+    fn beat_length(&self) -> usize {
+        let bpm = f32::from_bits(self.bpm.load(Ordering::Relaxed)).clamp(20.0, 400.0);
+        (self.sample_rate as f32 * 60.0 / bpm) as usize
+    }
+}
+
+impl Iterator for Metronome {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position >= self.beat_length {
+            self.position = 0;
+            self.beat = (self.beat + 1) % 4;
+            self.beat_length = self.beat_length();
+        }
+        let t = self.position as f32 / self.sample_rate as f32;
+        self.position += 1;
+        if t >= Self::CLICK.as_secs_f32() {
+            return Some(0.0);
+        }
+        let frequency = if self.beat == 0 { 1500.0 } else { 1000.0 };
+        let envelope = (-t / 0.006).exp();
+        Some(0.5 * envelope * (2.0 * std::f32::consts::PI * frequency * t).sin())
+    }
+}
+
+impl Source for Metronome {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Level of the generator's signals, -12 dBFS, to spare speakers and ears
+const GENERATOR_LEVEL: f32 = 0.25;
+
+/// Test signal of the generator mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Signal {
+    /// Fixed sine tone in Hz
+    Tone(f32),
+    /// Logarithmic sine sweep between two frequencies, played once
+    Sweep {
+        from: f32,
+        to: f32,
+        length: Duration,
+    },
+    WhiteNoise,
+    PinkNoise,
+}
+
+impl Signal {
+    /// `tono HZ`, `sweep [DA A [SECONDI]]`, `bianco` or `rosa`
+    /// (English names work too)
+    pub(crate) fn parse(text: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let number = |index: usize, default: f32| -> Result<f32, String> {
+            match fields.get(index) {
+                Some(field) => field
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|&n| n > 0.0)
+                    .ok_or_else(|| format!("valore non valido: {}", field)),
+                None => Ok(default),
+            }
+        };
+        let kind = fields.first().ok_or("segnale mancante")?.to_lowercase();
+        match kind.as_str() {
+            "tono" | "sine" | "tone" => Ok(Signal::Tone(number(1, 1000.0)?)),
+            "sweep" => {
+                let (from, to) = (number(1, 20.0)?, number(2, 20000.0)?);
+                if from == to {
+                    return Err("lo sweep richiede due frequenze diverse".to_string());
+                }
+                Ok(Signal::Sweep {
+                    from,
+                    to,
+                    length: Duration::from_secs_f32(number(3, 10.0)?),
+                })
+            }
+            "bianco" | "white" => Ok(Signal::WhiteNoise),
+            "rosa" | "pink" => Ok(Signal::PinkNoise),
+            _ => Err(format!("segnale sconosciuto: {}", kind)),
+        }
+    }
+
+    pub(crate) fn label(&self) -> String {
+        match self {
+            Signal::Tone(frequency) => format!("Tono {} Hz", frequency),
+            Signal::Sweep { from, to, length } => {
+                format!("Sweep {}–{} Hz, {:.0} s", from, to, length.as_secs_f32())
+            }
+            Signal::WhiteNoise => "Rumore bianco".to_string(),
+            Signal::PinkNoise => "Rumore rosa".to_string(),
+        }
+    }
+}
+
+/// Mono source of a test signal; sweeps end, the rest play until stopped
+pub(crate) struct Generator {
+    signal: Signal,
+    sample_rate: u32,
+    /// Samples played, for the sweep
+    position: u64,
+    phase: f64,
+    /// xorshift32 state for the noise
+    rng: u32,
+    /// Paul Kellet's pink noise filter state
+    pink: [f32; 7],
+}
+
+impl Generator {
+    fn new(signal: Signal, sample_rate: u32) -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        Self {
+            signal,
+            sample_rate,
+            position: 0,
+            phase: 0.0,
+            rng: seed | 1,
+            pink: [0.0; 7],
+        }
+    }
+
+    /// Uniform in -1..1
+    fn white(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    fn sine(&mut self, frequency: f64) -> f32 {
+        let sample = self.phase.sin() as f32;
+        self.phase = (self.phase + std::f64::consts::TAU * frequency / self.sample_rate as f64)
+            % std::f64::consts::TAU;
+        sample
+    }
+}
+
+impl Iterator for Generator {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let t = self.position as f64 / self.sample_rate as f64;
+        self.position += 1;
+        let sample = match self.signal {
+            Signal::Tone(frequency) => self.sine(frequency as f64),
+            Signal::Sweep { from, to, length } => {
+                let progress = t / length.as_secs_f64();
+                if progress >= 1.0 {
+                    return None;
+                }
+                self.sine(from as f64 * (to as f64 / from as f64).powf(progress))
+            }
+            Signal::WhiteNoise => self.white(),
+            Signal::PinkNoise => {
+                let white = self.white();
+                let b = &mut self.pink;
+                b[0] = 0.99886 * b[0] + white * 0.0555179;
+                b[1] = 0.99332 * b[1] + white * 0.0750759;
+                b[2] = 0.96900 * b[2] + white * 0.153852;
+                b[3] = 0.86650 * b[3] + white * 0.3104856;
+                b[4] = 0.55000 * b[4] + white * 0.5329522;
+                b[5] = -0.7616 * b[5] - white * 0.0168980;
+                let pink = b.iter().sum::<f32>() + white * 0.5362;
+                b[6] = white * 0.115926;
+                // The filter's gain is about 5 (+14 dB)
+                pink * 0.2
+            }
+        };
+        Some(sample * GENERATOR_LEVEL)
+    }
+}
+
+impl Source for Generator {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        match self.signal {
+            Signal::Sweep { length, .. } => Some(length),
+            _ => None,
+        }
+    }
+}
+
+/// DSD container layout: DSF stores per-channel blocks with LSB-first bytes,
+/// DFF (DSDIFF) interleaves channels byte by byte, MSB first
+#[derive(Clone, Copy, PartialEq)]
+enum DsdContainer {
+    Dsf { block_size: usize, lsb_first: bool },
+    Dff,
+}
+
+/// Stream parameters read from a DSF/DFF header
+struct DsdHeader {
+    container: DsdContainer,
+    channels: u16,
+    dsd_rate: u32,
+    data_len: u64,
+    /// Samples per channel
+    sample_count: u64,
+}
+
+/// Decodes DSF/DFF files to PCM. The 1-bit stream is low-pass filtered and
+/// decimated to 88.2 kHz (or 96 kHz for 48k-family rates) with a FIR filter
+/// evaluated one byte (8 taps) at a time through lookup tables.
+struct DsdSource {
+    reader: BufReader<File>,
+    container: DsdContainer,
+    channels: u16,
+    sample_rate: u32,
+    data_left: u64,
+    total_duration: Duration,
+    pending: Vec<VecDeque<u8>>,
+    history: Vec<VecDeque<u8>>,
+    bytes_per_sample: usize,
+    tables: Vec<[f32; 256]>,
+    frame: Vec<f32>,
+    frame_pos: usize,
+}
+
+impl DsdSource {
+    fn open(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        let header = match &magic {
+            b"DSD " => Self::read_dsf_header(&mut reader)?,
+            b"FRM8" => Self::read_dff_header(&mut reader)?,
+            _ => return Err("File DSD non riconosciuto".into()),
+        };
+        let DsdHeader {
+            container,
+            channels,
+            dsd_rate,
+            data_len,
+            sample_count,
+        } = header;
+        if channels == 0 || dsd_rate == 0 {
+            return Err("Intestazione DSD non valida".into());
+        }
+
+        let sample_rate = if dsd_rate % 44100 == 0 { 88200 } else { 96000 };
+        let bytes_per_sample = (dsd_rate / 8 / sample_rate).max(1) as usize;
+        let filter_bytes = bytes_per_sample * 4;
+        let cutoff = sample_rate as f32 * 0.25 / dsd_rate as f32;
+
+        Ok(Self {
+            reader,
+            container,
+            channels,
+            sample_rate,
+            data_left: data_len,
+            total_duration: Duration::from_secs_f64(sample_count as f64 / dsd_rate as f64),
+            pending: vec![VecDeque::new(); channels as usize],
+            history: vec![VecDeque::from(vec![0x69; filter_bytes]); channels as usize],
+            bytes_per_sample,
+            tables: Self::build_tables(filter_bytes, cutoff),
+            frame: Vec::with_capacity(channels as usize),
+            frame_pos: 0,
+        })
+    }
+
+    fn read_u32_le(reader: &mut impl Read) -> io::Result<u32> {
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_u64_le(reader: &mut impl Read) -> io::Result<u64> {
+        let mut buf = [0u8; 8];
+        reader.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn read_u64_be(reader: &mut impl Read) -> io::Result<u64> {
+        let mut buf = [0u8; 8];
+        reader.read_exact(&mut buf)?;
+        Ok(u64::from_be_bytes(buf))
+    }
+
+    fn read_dsf_header(
+        reader: &mut BufReader<File>,
+    ) -> Result<DsdHeader, Box<dyn std::error::Error>> {
+        // Rest of the "DSD " chunk: size, file size, metadata pointer
+        reader.seek(SeekFrom::Current(24))?;
+
+        let mut id = [0u8; 4];
+        reader.read_exact(&mut id)?;
+        if &id != b"fmt " {
+            return Err("Chunk fmt DSF mancante".into());
+        }
+        let fmt_size = Self::read_u64_le(reader)?;
+        let _version = Self::read_u32_le(reader)?;
+        let format_id = Self::read_u32_le(reader)?;
+        let _channel_type = Self::read_u32_le(reader)?;
+        let channels = Self::read_u32_le(reader)? as u16;
+        let dsd_rate = Self::read_u32_le(reader)?;
+        let bits_per_sample = Self::read_u32_le(reader)?;
+        let sample_count = Self::read_u64_le(reader)?;
+        let block_size = Self::read_u32_le(reader)? as usize;
+        reader.seek(SeekFrom::Current(fmt_size as i64 - 48))?;
+
+        if format_id != 0 || block_size == 0 {
+            return Err("Formato DSF non supportato".into());
+        }
+
+        reader.read_exact(&mut id)?;
+        if &id != b"data" {
+            return Err("Chunk data DSF mancante".into());
+        }
+        let data_len = Self::read_u64_le(reader)?.saturating_sub(12);
+
+        Ok(DsdHeader {
+            container: DsdContainer::Dsf {
+                block_size,
+                lsb_first: bits_per_sample == 1,
+            },
+            channels,
+            dsd_rate,
+            data_len,
+            sample_count,
+        })
+    }
+
+    /// Walks the IFF chunks up to the "DSD " sound data chunk
+    fn read_dff_header(
+        reader: &mut BufReader<File>,
+    ) -> Result<DsdHeader, Box<dyn std::error::Error>> {
+        let _form_size = Self::read_u64_be(reader)?;
+        let mut id = [0u8; 4];
+        reader.read_exact(&mut id)?;
+        if &id != b"DSD " {
+            return Err("File DFF non valido".into());
+        }
+
+        let mut channels = 0u16;
+        let mut dsd_rate = 0u32;
+
+        loop {
+            reader.read_exact(&mut id)?;
+            let size = Self::read_u64_be(reader)?;
+            match &id {
+                b"PROP" => {
+                    let mut prop_type = [0u8; 4];
+                    reader.read_exact(&mut prop_type)?;
+                    let end = reader.stream_position()? + size - 4;
+                    while reader.stream_position()? < end {
+                        reader.read_exact(&mut id)?;
+                        let sub_size = Self::read_u64_be(reader)?;
+                        let mut data = vec![0u8; sub_size as usize];
+                        reader.read_exact(&mut data)?;
+                        if sub_size % 2 == 1 {
+                            reader.seek(SeekFrom::Current(1))?;
+                        }
+                        match &id {
+                            b"FS  " if data.len() >= 4 => {
+                                dsd_rate = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                            }
+                            b"CHNL" if data.len() >= 2 => {
+                                channels = u16::from_be_bytes([data[0], data[1]]);
+                            }
+                            b"CMPR" if data.len() >= 4 && &data[..4] != b"DSD " => {
+                                return Err("DFF compresso (DST) non supportato".into());
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                b"DSD " => {
+                    return Ok(DsdHeader {
+                        container: DsdContainer::Dff,
+                        channels,
+                        dsd_rate,
+                        data_len: size,
+                        sample_count: size * 8 / channels.max(1) as u64,
+                    });
+                }
+                b"DST " => return Err("DFF compresso (DST) non supportato".into()),
+                _ => {
+                    reader.seek(SeekFrom::Current((size + size % 2) as i64))?;
+                }
+            }
+        }
+    }
+
+    /// Blackman-windowed sinc low-pass, folded into one table per filter byte:
+    /// tables[i][byte] is the filter output contribution of `byte` at position i
+    fn build_tables(filter_bytes: usize, cutoff: f32) -> Vec<[f32; 256]> {
+        let taps = filter_bytes * 8;
+        let center = (taps - 1) as f32 / 2.0;
+        let mut coeffs: Vec<f32> = (0..taps)
+            .map(|n| {
+                let x = n as f32 - center;
+                let sinc = if x == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * std::f32::consts::PI * cutoff * x).sin() / (std::f32::consts::PI * x)
+                };
+                let phase = 2.0 * std::f32::consts::PI * n as f32 / (taps - 1) as f32;
+                sinc * (0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos())
+            })
+            .collect();
+        let sum: f32 = coeffs.iter().sum();
+        coeffs.iter_mut().for_each(|c| *c /= sum);
+
+        (0..filter_bytes)
+            .map(|i| {
+                let mut table = [0.0f32; 256];
+                for (byte, entry) in table.iter_mut().enumerate() {
+                    *entry = (0..8)
+                        .map(|bit| {
+                            let level = if byte & (0x80 >> bit) != 0 { 1.0 } else { -1.0 };
+                            coeffs[i * 8 + bit] * level
+                        })
+                        .sum();
+                }
+                table
+            })
+            .collect()
+    }
+
+    /// Reads the next chunk of the data area into the per-channel queues
+    fn fill_pending(&mut self) -> bool {
+        let channels = self.channels as usize;
+        let chunk = match self.container {
+            DsdContainer::Dsf { block_size, .. } => block_size * channels,
+            DsdContainer::Dff => 4096 * channels,
+        };
+        let len = (chunk as u64).min(self.data_left) as usize;
+        if len < channels {
+            return false;
+        }
+
+        let mut data = vec![0u8; len];
+        if self.reader.read_exact(&mut data).is_err() {
+            return false;
+        }
+        self.data_left -= len as u64;
+
+        match self.container {
+            DsdContainer::Dsf {
+                block_size,
+                lsb_first,
+            } => {
+                for (ch, block) in data.chunks(block_size).enumerate() {
+                    let queue = &mut self.pending[ch % channels];
+                    if lsb_first {
+                        queue.extend(block.iter().map(|b| b.reverse_bits()));
+                    } else {
+                        queue.extend(block.iter().copied());
+                    }
+                }
+            }
+            DsdContainer::Dff => {
+                for (i, &byte) in data.iter().enumerate() {
+                    self.pending[i % channels].push_back(byte);
+                }
+            }
+        }
+        true
+    }
+
+    /// Produces one PCM frame (one sample per channel) into `self.frame`
+    fn decode_frame(&mut self) -> bool {
+        while self.pending[0].len() < self.bytes_per_sample {
+            if !self.fill_pending() {
+                return false;
+            }
+        }
+
+        self.frame.clear();
+        for ch in 0..self.channels as usize {
+            let history = &mut self.history[ch];
+            for _ in 0..self.bytes_per_sample {
+                let byte = self.pending[ch].pop_front().unwrap_or(0x69);
+                history.pop_front();
+                history.push_back(byte);
+            }
+            let value: f32 = history
+                .iter()
+                .zip(&self.tables)
+                .map(|(&byte, table)| table[byte as usize])
+                .sum();
+            self.frame.push(value.clamp(-1.0, 1.0));
+        }
+        self.frame_pos = 0;
+        true
+    }
+}
+
+impl Iterator for DsdSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.frame_pos >= self.frame.len() && !self.decode_frame() {
+            return None;
+        }
+        let sample = self.frame[self.frame_pos];
+        self.frame_pos += 1;
+        Some(sample)
+    }
+}
+
+impl Source for DsdSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.total_duration)
+    }
+}
+
+/// MP3 decoder that rides over damaged frames. rodio's gives up after a few
+/// bad frames in a row and ends the track; here a frame that fails to decode
+/// becomes silence of the same length, so the track keeps its timing, and
+/// the stream only ends with the file or after a long run of errors.
+struct Mp3Source {
+    format: Box<dyn symphonia::core::formats::FormatReader>,
+    decoder: Box<dyn symphonia::core::codecs::Decoder>,
+    track_id: u32,
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+    /// Interleaved samples of the current frame; empty at the end
+    buffer: Vec<f32>,
+    position: usize,
+    consecutive_errors: usize,
+}
+
+impl Mp3Source {
+    /// Bad packets in a row after which the rest of the file is taken as lost
+    const MAX_CONSECUTIVE_ERRORS: usize = 100;
+    /// Samples per channel of a Layer III frame, for lost frames of unknown length
+    const FRAME_LENGTH: u64 = 1152;
+
+    fn new(
+        media: Box<dyn symphonia::core::io::MediaSource>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        use symphonia::core::{
+            codecs::DecoderOptions, formats::FormatOptions, io::MediaSourceStream,
+            meta::MetadataOptions, probe::Hint,
+        };
+
+        let stream = MediaSourceStream::new(media, Default::default());
+        let mut hint = Hint::new();
+        hint.with_extension("mp3");
+        let format = symphonia::default::get_probe()
+            .format(
+                &hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )?
+            .format;
+        let track = format.default_track().ok_or("nessuna traccia audio")?;
+        let params = &track.codec_params;
+        let decoder = symphonia::default::get_codecs().make(params, &DecoderOptions::default())?;
+        let total_duration = params.time_base.zip(params.n_frames).map(|(base, frames)| {
+            let time = base.calc_time(frames);
+            Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac)
+        });
+        let mut source = Self {
+            track_id: track.id,
+            channels: params.channels.map_or(2, |c| c.count() as u16),
+            sample_rate: params.sample_rate.unwrap_or(44100),
+            total_duration,
+            format,
+            decoder,
+            buffer: Vec::new(),
+            position: 0,
+            consecutive_errors: 0,
+        };
+        source.refill();
+        Ok(source)
+    }
+
+    /// Decodes the next frame into `buffer`, which stays empty at the end
+    fn refill(&mut self) {
+        use symphonia::core::{audio::SampleBuffer, errors::Error};
+
+        self.buffer.clear();
+        self.position = 0;
+        while self.consecutive_errors <= Self::MAX_CONSECUTIVE_ERRORS {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(_)) | Err(Error::ResetRequired) => return,
+                // Garbage between frames; the reader resyncs on the next call
+                Err(_) => {
+                    self.consecutive_errors += 1;
+                    continue;
+                }
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    let spec = *decoded.spec();
+                    let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                    samples.copy_interleaved_ref(decoded);
+                    self.consecutive_errors = 0;
+                    self.channels = spec.channels.count() as u16;
+                    self.sample_rate = spec.rate;
+                    self.buffer.extend_from_slice(samples.samples());
+                    if !self.buffer.is_empty() {
+                        return;
+                    }
+                }
+                Err(Error::DecodeError(_)) => {
+                    self.consecutive_errors += 1;
+                    let frames = match packet.dur {
+                        0 => Self::FRAME_LENGTH,
+                        dur => dur,
+                    };
+                    self.buffer
+                        .resize(frames as usize * self.channels as usize, 0.0);
+                    return;
+                }
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+impl Iterator for Mp3Source {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = *self.buffer.get(self.position)?;
+        self.position += 1;
+        // Refilled right away, so the frame length is only zero at the end
+        if self.position == self.buffer.len() {
+            self.refill();
+        }
+        Some(sample)
+    }
+}
+
+impl Source for Mp3Source {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.buffer.len() - self.position)
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        use symphonia::core::formats::{SeekMode, SeekTo};
+
+        let seeked = self
+            .format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: pos.into(),
+                    track_id: Some(self.track_id),
+                },
+            )
+            .map_err(|e| SeekError::Other(Box::new(e)))?;
+        self.decoder.reset();
+        self.consecutive_errors = 0;
+        self.refill();
+        // The reader lands on a frame boundary before the target
+        let mut skip =
+            seeked.required_ts.saturating_sub(seeked.actual_ts) as usize * self.channels as usize;
+        while skip > 0 && !self.buffer.is_empty() {
+            let available = self.buffer.len() - self.position;
+            if skip < available {
+                self.position += skip;
+                break;
+            }
+            skip -= available;
+            self.refill();
+        }
+        Ok(())
+    }
+}
+
+/// Sample encoding of headerless PCM input
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RawFormat {
+    U8,
+    S16Le,
+    S24Le,
+    S32Le,
+    F32Le,
+}
+
+impl RawFormat {
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        match text {
+            "u8" => Some(RawFormat::U8),
+            "s16le" => Some(RawFormat::S16Le),
+            "s24le" => Some(RawFormat::S24Le),
+            "s32le" => Some(RawFormat::S32Le),
+            "f32le" => Some(RawFormat::F32Le),
+            _ => None,
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        match self {
+            RawFormat::U8 => 1,
+            RawFormat::S16Le => 2,
+            RawFormat::S24Le => 3,
+            RawFormat::S32Le | RawFormat::F32Le => 4,
+        }
+    }
+
+    /// One sample from exactly `bytes()` bytes
+    fn decode(&self, b: &[u8]) -> f32 {
+        match self {
+            RawFormat::U8 => (b[0] as f32 - 128.0) / 128.0,
+            RawFormat::S16Le => i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+            RawFormat::S24Le => i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2_147_483_648.0,
+            RawFormat::S32Le => {
+                i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0
+            }
+            RawFormat::F32Le => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        }
+    }
+}
+
+/// What a stream of headerless PCM holds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PcmLayout {
+    pub(crate) format: RawFormat,
+    pub(crate) sample_rate: u32,
+    pub(crate) channels: u16,
+}
+
+impl PcmLayout {
+    /// Reads a WAV header up to the start of the samples. Written to a pipe,
+    /// the sizes can't be filled in afterwards and are ignored.
+    fn read_wav_header(input: &mut impl Read) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "intestazione WAV non valida");
+        let mut riff = [0u8; 12];
+        input.read_exact(&mut riff)?;
+        if &riff[..4] != b"RIFF" || &riff[8..] != b"WAVE" {
+            return Err(invalid());
+        }
+        let mut layout = None;
+        loop {
+            let mut header = [0u8; 8];
+            input.read_exact(&mut header)?;
+            let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+            if &header[..4] == b"data" {
+                return layout.ok_or_else(invalid);
+            }
+            let mut chunk = vec![0; size as usize + size as usize % 2];
+            input.read_exact(&mut chunk)?;
+            if &header[..4] == b"fmt " && chunk.len() >= 16 {
+                let tag = u16::from_le_bytes([chunk[0], chunk[1]]);
+                let bits = u16::from_le_bytes([chunk[14], chunk[15]]);
+                let format = match (tag, bits) {
+                    (3, 32) => RawFormat::F32Le,
+                    (_, 8) => RawFormat::U8,
+                    (_, 16) => RawFormat::S16Le,
+                    (_, 24) => RawFormat::S24Le,
+                    (_, 32) => RawFormat::S32Le,
+                    _ => return Err(invalid()),
+                };
+                layout = Some(Self {
+                    format,
+                    sample_rate: u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+                    channels: u16::from_le_bytes([chunk[2], chunk[3]]).max(1),
+                });
+            }
+        }
+    }
+}
+
+/// External decoder commands from `<config>/decoders`, one per line:
+/// `ext [rate channels [format]] = command`. `{}` in the command stands for
+/// the file; the command writes raw PCM to stdout, 32-bit float stereo at
+/// 44.1 kHz unless given, e.g.
+/// `ape = ffmpeg -v quiet -i {} -f f32le -ac 2 -ar 44100 -`.
+/// A command set for a format the player decodes itself replaces its decoder.
+#[derive(Default)]
+pub(crate) struct ExternalDecoders {
+    decoders: HashMap<String, ExternalDecoder>,
+    /// First bad line of the file, reported at startup
+    pub(crate) error: Option<String>,
+}
+
+pub(crate) struct ExternalDecoder {
+    layout: PcmLayout,
+    command: Vec<String>,
+    /// The command writes WAV, whose header gives the layout
+    wav: bool,
+}
+
+impl ExternalDecoder {
+    pub(crate) fn new(layout: PcmLayout, command: Vec<String>) -> Self {
+        Self {
+            layout,
+            command,
+            wav: false,
+        }
+    }
+
+    /// A command writing a WAV stream to stdout
+    pub(crate) fn wav(command: Vec<String>) -> Self {
+        let layout = PcmLayout {
+            format: RawFormat::S16Le,
+            sample_rate: 44100,
+            channels: 2,
+        };
+        Self {
+            layout,
+            command,
+            wav: true,
+        }
+    }
+}
+
+impl ExternalDecoders {
+    /// Read once, on first use
+    pub(crate) fn get() -> &'static Self {
+        static DECODERS: std::sync::OnceLock<ExternalDecoders> = std::sync::OnceLock::new();
+        DECODERS.get_or_init(|| {
+            config_dir()
+                .and_then(|dir| fs::read_to_string(dir.join("decoders")).ok())
+                .map(|content| Self::parse(&content))
+                .unwrap_or_default()
+        })
+    }
+
+    fn parse(content: &str) -> Self {
+        let mut decoders = Self::default();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match Self::parse_line(line) {
+                Ok((ext, decoder)) => {
+                    decoders.decoders.insert(ext, decoder);
+                }
+                Err(e) => {
+                    decoders.error.get_or_insert(e);
+                }
+            }
+        }
+        decoders
+    }
+
+    fn parse_line(line: &str) -> Result<(String, ExternalDecoder), String> {
+        let invalid = || format!("riga non valida: {}", line);
+        let (format, command) = line.split_once('=').ok_or_else(invalid)?;
+        let mut format = format.split_whitespace();
+        let ext = format.next().ok_or_else(invalid)?;
+        let mut layout = PcmLayout {
+            format: RawFormat::F32Le,
+            sample_rate: 44100,
+            channels: 2,
+        };
+        if let Some(rate) = format.next() {
+            layout.sample_rate = rate.parse().ok().filter(|&r| r > 0).ok_or_else(invalid)?;
+        }
+        if let Some(channels) = format.next() {
+            layout.channels = channels
+                .parse()
+                .ok()
+                .filter(|&c| c > 0)
+                .ok_or_else(invalid)?;
+        }
+        if let Some(sample_format) = format.next() {
+            layout.format = RawFormat::parse(sample_format).ok_or_else(invalid)?;
+        }
+        let command: Vec<String> = command.split_whitespace().map(str::to_string).collect();
+        if command.is_empty() || format.next().is_some() {
+            return Err(invalid());
+        }
+        let ext = ext.trim_start_matches('.').to_lowercase();
+        Ok((ext, ExternalDecoder::new(layout, command)))
+    }
+
+    pub(crate) fn for_path(&self, path: &Path) -> Option<&ExternalDecoder> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        self.decoders.get(&ext)
+    }
+}
+
+/// Headerless PCM from a file, a FIFO, stdin or an external decoder's
+/// output. A worker thread reads and converts it, so a slow producer (a
+/// radio receiver, a synthesizer) never blocks the audio callback: while
+/// no data is there the source plays silence, and it ends with the input.
+/// It can't seek, and its length is unknown unless the caller knows it.
+struct RawPcmSource {
+    chunks: mpsc::Receiver<Vec<f32>>,
+    /// Whole frames, converted to float
+    chunk: Vec<f32>,
+    position: usize,
+    layout: PcmLayout,
+    /// External decoder writing the stream, killed with the source
+    child: Option<process::Child>,
+    duration: Option<Duration>,
+}
+
+impl RawPcmSource {
+    /// Frames read at a time, and chunks buffered ahead of playback
+    const CHUNK_FRAMES: usize = 1024;
+    const BUFFERED_CHUNKS: usize = 32;
+
+    /// `-` reads stdin. A FIFO is opened on the worker thread, where waiting
+    /// for its writer doesn't hold up the player.
+    fn open(path: &Path, layout: PcmLayout) -> io::Result<Self> {
+        if path == Path::new("-") {
+            return Ok(Self::start(|| Ok(io::stdin()), layout, None));
+        }
+        if !path.exists() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        let path = path.to_path_buf();
+        Ok(Self::start(move || File::open(path), layout, None))
+    }
+
+    fn spawn(decoder: &ExternalDecoder, path: &Path) -> io::Result<Self> {
+        let mut args = decoder.command.iter().map(|arg| {
+            if arg == "{}" {
+                path.as_os_str().to_owned()
+            } else {
+                arg.into()
+            }
+        });
+        let program = args.next().unwrap_or_default();
+        let mut child = process::Command::new(program)
+            .args(args)
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::null())
+            .spawn()?;
+        let mut output = child.stdout.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        let layout = if decoder.wav {
+            // The header comes at once, before the decoder gets to work
+            match PcmLayout::read_wav_header(&mut output) {
+                Ok(layout) => layout,
+                Err(e) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(e);
+                }
+            }
+        } else {
+            decoder.layout
+        };
+        Ok(Self::start(move || Ok(output), layout, Some(child)))
+    }
+
+    fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    fn start<R: Read>(
+        open: impl FnOnce() -> io::Result<R> + Send + 'static,
+        layout: PcmLayout,
+        child: Option<process::Child>,
+    ) -> Self {
+        let (sender, chunks) = mpsc::sync_channel(Self::BUFFERED_CHUNKS);
+        thread::spawn(move || {
+            let Ok(mut input) = open() else {
+                return;
+            };
+            let frame = layout.format.bytes() * layout.channels as usize;
+            let mut buffer = vec![0; frame * Self::CHUNK_FRAMES];
+            let mut filled = 0;
+            loop {
+                let read = match input.read(&mut buffer[filled..]) {
+                    Ok(0) => return,
+                    Ok(read) => read,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => return,
+                };
+                filled += read;
+                // Partial frames wait for the rest, so channels never swap
+                let whole = filled - filled % frame;
+                let samples: Vec<f32> = buffer[..whole]
+                    .chunks_exact(layout.format.bytes())
+                    .map(|bytes| layout.format.decode(bytes))
+                    .collect();
+                buffer.copy_within(whole..filled, 0);
+                filled -= whole;
+                if !samples.is_empty() && sender.send(samples).is_err() {
+                    return;
+                }
+            }
+        });
+        Self {
+            chunks,
+            chunk: Vec::new(),
+            position: 0,
+            layout,
+            child,
+            duration: None,
+        }
+    }
+}
+
+impl Drop for RawPcmSource {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Iterator for RawPcmSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position == self.chunk.len() {
+            self.chunk = match self.chunks.try_recv() {
+                Ok(chunk) => chunk,
+                // A frame of silence, keeping the channels in step
+                Err(mpsc::TryRecvError::Empty) => vec![0.0; self.layout.channels as usize],
+                Err(mpsc::TryRecvError::Disconnected) => return None,
+            };
+            self.position = 0;
+        }
+        self.position += 1;
+        Some(self.chunk[self.position - 1])
+    }
+}
+
+impl Source for RawPcmSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.layout.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.layout.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.duration
+    }
+}
+
+/// Fields of an MPEG audio Layer III frame header needed to walk the stream
+struct Mp3FrameHeader {
+    mpeg1: bool,
+    mono: bool,
+    sample_rate: u32,
+    frame_len: usize,
+}
+
+impl Mp3FrameHeader {
+    fn parse(bytes: [u8; 4]) -> Option<Self> {
+        if bytes[0] != 0xFF || bytes[1] & 0xE0 != 0xE0 {
+            return None;
+        }
+        let version = (bytes[1] >> 3) & 0x03; // 0 = 2.5, 2 = 2, 3 = 1
+        let layer = (bytes[1] >> 1) & 0x03; // 1 = Layer III
+        let bitrate_index = (bytes[2] >> 4) as usize;
+        let rate_index = ((bytes[2] >> 2) & 0x03) as usize;
+        if version == 1
+            || layer != 1
+            || bitrate_index == 0
+            || bitrate_index == 15
+            || rate_index == 3
+        {
+            return None;
+        }
+
+        const BITRATES_V1: [u32; 15] = [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ];
+        const BITRATES_V2: [u32; 15] =
+            [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+        const RATES: [u32; 3] = [44100, 48000, 32000];
+
+        let mpeg1 = version == 3;
+        let bitrate = if mpeg1 {
+            BITRATES_V1[bitrate_index]
+        } else {
+            BITRATES_V2[bitrate_index]
+        } * 1000;
+        let sample_rate = match version {
+            3 => RATES[rate_index],
+            2 => RATES[rate_index] / 2,
+            _ => RATES[rate_index] / 4,
+        };
+        let padding = ((bytes[2] >> 1) & 0x01) as u32;
+        let coefficient = if mpeg1 { 144 } else { 72 };
+
+        Some(Self {
+            mpeg1,
+            mono: bytes[3] >> 6 == 3,
+            sample_rate,
+            frame_len: (coefficient * bitrate / sample_rate + padding) as usize,
+        })
+    }
+
+    fn samples_per_frame(&self) -> u64 {
+        if self.mpeg1 { 1152 } else { 576 }
+    }
+
+    /// Offset of the Xing/Info tag from the frame start (after the side info)
+    fn xing_offset(&self) -> usize {
+        4 + match (self.mpeg1, self.mono) {
+            (true, false) => 32,
+            (true, true) | (false, false) => 17,
+            (false, true) => 9,
+        }
+    }
+}
+
+/// Duration of an MP3 file. VBR encoders write the frame count in a Xing/Info
+/// or VBRI header in the first frame; without one, every frame header is walked
+/// (no decoding), which reads the whole file.
+fn mp3_duration(path: &PathBuf) -> io::Result<Option<Duration>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    // Skip an ID3v2 tag, whose size is stored as a 28-bit syncsafe integer
+    let mut id3 = [0u8; 10];
+    reader.read_exact(&mut id3)?;
+    let mut offset = if &id3[..3] == b"ID3" {
+        let size = id3[6..10]
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 7) | (b & 0x7F) as u64);
+        let footer = if id3[5] & 0x10 != 0 { 10 } else { 0 };
+        10 + size + footer
+    } else {
+        0
+    };
+
+    // Resync on the first valid frame header. Moves are relative from here
+    // on, so BufReader keeps its buffer instead of refilling it every frame.
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut header_bytes = [0u8; 4];
+    let first = loop {
+        if reader.read_exact(&mut header_bytes).is_err() {
+            return Ok(None);
+        }
+        if let Some(header) = Mp3FrameHeader::parse(header_bytes) {
+            break header;
+        }
+        offset += 1;
+        if offset > 64 * 1024 {
+            return Ok(None);
+        }
+        reader.seek_relative(-3)?;
+    };
+
+    let mut frame = vec![0u8; first.frame_len.max(4)];
+    frame[..4].copy_from_slice(&header_bytes);
+    reader.read_exact(&mut frame[4..])?;
+
+    let xing = first.xing_offset();
+    let frames = if frame.len() >= xing + 12
+        && (&frame[xing..xing + 4] == b"Xing" || &frame[xing..xing + 4] == b"Info")
+        && frame[xing + 7] & 0x01 != 0
+    {
+        Some(u32::from_be_bytes(frame[xing + 8..xing + 12].try_into().unwrap()) as u64)
+    } else if frame.len() >= 36 + 18 && &frame[36..40] == b"VBRI" {
+        Some(u32::from_be_bytes(frame[50..54].try_into().unwrap()) as u64)
+    } else {
+        None
+    };
+
+    let total_samples = match frames {
+        Some(frames) => frames * first.samples_per_frame(),
+        None => {
+            // The first frame was read whole; walk the headers of the others
+            let mut total = first.samples_per_frame();
+            loop {
+                if reader.read_exact(&mut header_bytes).is_err() {
+                    break;
+                }
+                match Mp3FrameHeader::parse(header_bytes) {
+                    Some(header) if header.frame_len >= 4 => {
+                        total += header.samples_per_frame();
+                        reader.seek_relative(header.frame_len as i64 - 4)?;
+                    }
+                    _ => break,
+                }
+            }
+            total
+        }
+    };
+
+    if total_samples == 0 {
+        return Ok(None);
+    }
+    Ok(Some(Duration::from_secs_f64(
+        total_samples as f64 / first.sample_rate as f64,
+    )))
+}
+
+/// Bits per sample stored in a PCM or lossless file, from its headers only.
+/// None for lossy formats, which have no fixed depth to preserve.
+fn source_bit_depth(path: &Path) -> Option<u8> {
+    let mut options = ParseOptions::new();
+    Probe::open(path)
+        .ok()?
+        .options(options.read_tags(false))
+        .read()
+        .ok()?
+        .properties()
+        .bit_depth()
+}
+
+/// Second-order IIR section (direct form I), used for the K-weighting filter
+#[derive(Clone, Copy)]
+pub(crate) struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    pub(crate) fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+
+    /// BS.1770 K-weighting (high shelf + high pass) for any sample rate,
+    /// with the analog prototypes used by libebur128
+    /// Peaking filter boosting or cutting `gain_db` around `freq` (RBJ cookbook)
+    fn peaking(sample_rate: u32, freq: f64, q: f64, gain_db: f64) -> Self {
+        let a = 10f64.powf(gain_db / 40.0);
+        let w0 = 2.0 * std::f64::consts::PI * freq / sample_rate as f64;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha / a;
+        Self::new(
+            [
+                (1.0 + alpha * a) / a0,
+                -2.0 * cos / a0,
+                (1.0 - alpha * a) / a0,
+            ],
+            [-2.0 * cos / a0, (1.0 - alpha / a) / a0],
+        )
+    }
+
+    /// Band-pass with 0 dB at `freq` and a width of `freq / q` (RBJ cookbook)
+    fn band_pass(sample_rate: u32, freq: f64, q: f64) -> Self {
+        let w0 = 2.0 * std::f64::consts::PI * freq / sample_rate as f64;
+        let alpha = w0.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        Self::new(
+            [alpha / a0, 0.0, -alpha / a0],
+            [-2.0 * w0.cos() / a0, (1.0 - alpha) / a0],
+        )
+    }
+
+    pub(crate) fn k_weighting(sample_rate: u32) -> [Self; 2] {
+        let rate = sample_rate as f64;
+
+        let f0 = 1_681.974_450_955_533;
+        let gain = 3.999_843_853_973_347;
+        let q = 0.707_175_236_955_419_6;
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Self::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        let f0 = 38.135_470_876_024_44;
+        let q = 0.500_327_037_323_877_3;
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Self::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        [shelf, high_pass]
+    }
+}
+
+/// Audio system the output is opened on
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum AudioBackend {
+    /// The platform's own: ALSA, CoreAudio, WASAPI
+    #[default]
+    Default,
+    /// A JACK client (also on PipeWire's JACK layer), its ports connected
+    /// to the system playback ports. Needs the `jack` build feature.
+    Jack,
+}
+
+impl AudioBackend {
+    fn host(&self) -> Result<cpal::Host, Box<dyn std::error::Error>> {
+        match self {
+            AudioBackend::Default => Ok(cpal::default_host()),
+            #[cfg(feature = "jack")]
+            AudioBackend::Jack => Ok(cpal::host_from_id(cpal::HostId::Jack)?),
+            #[cfg(not(feature = "jack"))]
+            AudioBackend::Jack => {
+                Err("supporto JACK non compilato (cargo build --features jack)".into())
+            }
+        }
+    }
+}
+
+/// Dithering applied when the output device has fewer bits than the mixer (16-bit)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum DitherMode {
+    Off,
+    /// Triangular (TPDF) noise of ±1 LSB
+    #[default]
+    Tpdf,
+    /// TPDF with first-order error feedback, pushing the noise towards high frequencies
+    Shaped,
+}
+
+impl DitherMode {
+    fn label(&self) -> &'static str {
+        match self {
+            DitherMode::Off => "off",
+            DitherMode::Tpdf => "TPDF",
+            DitherMode::Shaped => "TPDF shaped",
+        }
+    }
+}
+
+/// Quantizes f32 samples to a coarser integer grid with dither noise.
+/// Runs inside the output callback, so it uses a tiny xorshift generator
+/// instead of pulling in an RNG crate.
+struct Ditherer {
+    mode: DitherMode,
+    lsb: f32,
+    rng_state: u32,
+    errors: Vec<f32>,
+    channel: usize,
+}
+
+impl Ditherer {
+    fn new(mode: DitherMode, bits: u32, channels: u16) -> Self {
+        Self {
+            mode,
+            lsb: 1.0 / (1u32 << (bits - 1)) as f32,
+            rng_state: 0x9E37_79B9,
+            errors: vec![0.0; channels.max(1) as usize],
+            channel: 0,
+        }
+    }
+
+    fn random(&mut self) -> f32 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 17;
+        self.rng_state ^= self.rng_state << 5;
+        self.rng_state as f32 / u32::MAX as f32
+    }
+
+    fn process(&mut self, sample: f32) -> f32 {
+        let channel = self.channel;
+        self.channel = (self.channel + 1) % self.errors.len();
+
+        let target = match self.mode {
+            DitherMode::Off => return sample,
+            DitherMode::Tpdf => sample,
+            DitherMode::Shaped => sample - self.errors[channel],
+        };
+        let noise = (self.random() - self.random()) * self.lsb;
+        let quantized = ((target + noise) / self.lsb).round() * self.lsb;
+        self.errors[channel] = quantized - target;
+        quantized
+    }
+}
+
+/// Shared state moved into the output stream callback
+struct StreamCallbackState {
+    dither: DitherMode,
+    dither_bypass: Arc<AtomicBool>,
+    stream_error: Arc<Mutex<Option<String>>>,
+    tap: Option<SyncSender<Vec<f32>>>,
+    zones: ZoneFeeds,
+    broadcast: BroadcastFeed,
+    timing: Arc<CallbackTiming>,
+}
+
+/// Durations of the output callback, written by the audio thread
+#[derive(Default)]
+struct CallbackTiming {
+    last_nanos: AtomicU64,
+    /// Longest callback since the UI last read it
+    peak_nanos: AtomicU64,
+    /// Audio length the last callback had to produce, i.e. its deadline
+    budget_nanos: AtomicU64,
+    /// Callbacks so far; standing still while playing means the device hangs
+    calls: AtomicU64,
+}
+
+impl CallbackTiming {
+    fn record(&self, elapsed: Duration, budget: Duration) {
+        let nanos = elapsed.as_nanos() as u64;
+        self.last_nanos.store(nanos, Ordering::Relaxed);
+        self.peak_nanos.fetch_max(nanos, Ordering::Relaxed);
+        self.budget_nanos
+            .store(budget.as_nanos() as u64, Ordering::Relaxed);
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// (last, peak, budget), resetting the peak
+    fn take(&self) -> (Duration, Duration, Duration) {
+        (
+            Duration::from_nanos(self.last_nanos.load(Ordering::Relaxed)),
+            Duration::from_nanos(self.peak_nanos.swap(0, Ordering::Relaxed)),
+            Duration::from_nanos(self.budget_nanos.load(Ordering::Relaxed)),
+        )
+    }
+}
+
+/// Sample encoding written by the PCM tap
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum TapFormat {
+    /// Signed 16-bit little endian, what cava and `aplay -f S16_LE` expect
+    S16Le,
+    /// 32-bit float little endian
+    F32Le,
+}
+
+impl TapFormat {
+    fn label(&self) -> &'static str {
+        match self {
+            TapFormat::S16Le => "s16le",
+            TapFormat::F32Le => "f32le",
+        }
+    }
+}
+
+/// PCM tap for external consumers (visualizers such as cava or projectM,
+/// recorders). The final mix, after volume and before dither, is written to
+/// `path` as headerless interleaved samples in `format`, at the sample rate
+/// and channel count of the output device shown in the info panel.
+///
+/// `path` is normally a FIFO created with `mkfifo`; the writer waits for a
+/// reader and reconnects when it goes away. A regular file records the
+/// stream instead. Writing runs on its own thread: the audio callback only
+/// hands over buffers, which are dropped if the writer falls behind.
+struct PcmTap {
+    path: PathBuf,
+    format: TapFormat,
+    sender: SyncSender<Vec<f32>>,
+}
+
+impl PcmTap {
+    fn start(path: PathBuf, format: TapFormat) -> Self {
+        let sender = FifoWriter::start(path.clone(), move |samples: Vec<f32>| match format {
+            TapFormat::S16Le => samples
+                .iter()
+                .flat_map(|s| ((s.clamp(-1.0, 1.0) * 32767.0) as i16).to_le_bytes())
+                .collect(),
+            TapFormat::F32Le => samples.iter().flat_map(|s| s.to_le_bytes()).collect(),
+        });
+
+        Self {
+            path,
+            format,
+            sender,
+        }
+    }
+}
+
+/// Channels from the main output callback to the zones, one per zone
+type ZoneFeed = SyncSender<Vec<f32>>;
+type ZoneFeeds = Arc<Mutex<Vec<ZoneFeed>>>;
+
+/// Channel from the output callback to the broadcast, while there is one
+type BroadcastFeed = Arc<Mutex<Option<SyncSender<Vec<f32>>>>>;
+
+/// Another output device playing along with the main one: the main
+/// callback hands it a copy of every buffer it plays, after the player
+/// volume, and the zone applies its own volume on top. The two device
+/// clocks drift apart, so a zone keeps at most `MAX_BUFFERS` callbacks'
+/// worth queued, dropping the oldest samples, and plays silence when it
+/// runs dry; the zones stay within a few buffers of the main output.
+pub(crate) struct Zone {
+    pub(crate) device_name: String,
+    sample_rate: u32,
+    channels: u16,
+    volume: Arc<AtomicU32>,
+    _stream: cpal::Stream,
+}
+
+impl Zone {
+    const MAX_BUFFERS: usize = 4;
+
+    /// Opens `name` at the main output's rate and channels; the returned
+    /// sender goes into the main output's feeds
+    fn open(
+        backend: AudioBackend,
+        name: &str,
+        sample_rate: u32,
+        channels: u16,
+        volume: f32,
+    ) -> Result<(Self, ZoneFeed), Box<dyn std::error::Error>> {
+        let device = AudioOutput::find_device(backend, Some(name))?;
+        let supported = device
+            .supported_output_configs()?
+            .filter(|c| c.channels() == channels)
+            .filter(|c| {
+                c.min_sample_rate().0 <= sample_rate && sample_rate <= c.max_sample_rate().0
+            })
+            .max_by_key(|c| c.sample_format() == SampleFormat::F32)
+            .ok_or_else(|| {
+                format!(
+                    "{} non supporta {} Hz, {} canali",
+                    name, sample_rate, channels
+                )
+            })?
+            .with_sample_rate(cpal::SampleRate(sample_rate));
+        let config = supported.config();
+        let (sender, receiver) = mpsc::sync_channel(16);
+        let volume = Arc::new(AtomicU32::new(volume.to_bits()));
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => {
+                Self::build_stream::<f32>(&device, &config, receiver, volume.clone())
+            }
+            SampleFormat::I16 => {
+                Self::build_stream::<i16>(&device, &config, receiver, volume.clone())
+            }
+            SampleFormat::U16 => {
+                Self::build_stream::<u16>(&device, &config, receiver, volume.clone())
+            }
+            SampleFormat::I32 => {
+                Self::build_stream::<i32>(&device, &config, receiver, volume.clone())
+            }
+            format => return Err(format!("Formato di uscita non supportato: {}", format).into()),
+        }?;
+        stream.play()?;
+        let zone = Self {
+            device_name: device.name().unwrap_or_else(|_| name.to_string()),
+            sample_rate,
+            channels,
+            volume,
+            _stream: stream,
+        };
+        Ok((zone, sender))
+    }
+
+    fn build_stream<T>(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        receiver: mpsc::Receiver<Vec<f32>>,
+        volume: Arc<AtomicU32>,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError>
+    where
+        T: SizedSample + FromSample<f32>,
+    {
+        let channels = config.channels.max(1) as usize;
+        let mut queue: VecDeque<f32> = VecDeque::new();
+        device.build_output_stream::<T, _, _>(
+            config,
+            move |data: &mut [T], _| {
+                while let Ok(buffer) = receiver.try_recv() {
+                    queue.extend(buffer);
+                }
+                let limit = data.len() * Self::MAX_BUFFERS;
+                if queue.len() > limit {
+                    let excess = (queue.len() - limit) / channels * channels;
+                    queue.drain(..excess);
+                }
+                let gain = f32::from_bits(volume.load(Ordering::Relaxed));
+                for out in data.iter_mut() {
+                    *out = T::from_sample(queue.pop_front().unwrap_or(0.0) * gain);
+                }
+            },
+            // A zone failing is no reason to disturb the main output
+            |_| {},
+            None,
+        )
+    }
+
+    pub(crate) fn volume(&self) -> f32 {
+        f32::from_bits(self.volume.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set_volume(&self, volume: f32) {
+        self.volume
+            .store(volume.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Background writer feeding a FIFO (or file) at `path`. Producers send
+/// values through the returned channel and never block; `encode` turns
+/// each value into bytes on the writer thread. Opening a FIFO blocks until a
+/// reader connects, and the writer reopens it when the reader goes away.
+pub(crate) struct FifoWriter;
+
+impl FifoWriter {
+    pub(crate) fn start<T, F>(path: PathBuf, encode: F) -> SyncSender<T>
+    where
+        T: Send + 'static,
+        F: Fn(T) -> Vec<u8> + Send + 'static,
+    {
+        Self::start_with(
+            move || {
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(&path)
+            },
+            encode,
+        )
+    }
+
+    /// Same, writing to whatever `open` returns (a socket, say), called
+    /// again whenever a write fails
+    fn start_with<T, W, O, F>(open: O, encode: F) -> SyncSender<T>
+    where
+        T: Send + 'static,
+        W: Write,
+        O: Fn() -> io::Result<W> + Send + 'static,
+        F: Fn(T) -> Vec<u8> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel::<T>(16);
+
+        thread::spawn(move || {
+            loop {
+                let Ok(mut file) = open() else {
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                };
+
+                // Skip what piled up while nobody was listening
+                while receiver.try_recv().is_ok() {}
+
+                loop {
+                    let Ok(value) = receiver.recv() else {
+                        return;
+                    };
+                    if file.write_all(&encode(value)).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        sender
+    }
+}
+
+/// Where `--snapcast` sends the stream: a pipe source of snapserver
+/// (`source = pipe:///tmp/snapfifo?name=Player`), or a TCP source in server
+/// mode (`source = tcp://0.0.0.0:4953?name=Player`) given as
+/// `tcp://host:port`. Either way the source's `sampleformat` must be
+/// snapserver's default, 48000:16:2.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum SnapcastTarget {
+    Pipe(PathBuf),
+    Tcp(String),
+}
+
+impl SnapcastTarget {
+    const SAMPLE_RATE: u32 = 48000;
+    const CHANNELS: u16 = 2;
+
+    pub(crate) fn parse(text: &str) -> Self {
+        match text.strip_prefix("tcp://") {
+            Some(address) => SnapcastTarget::Tcp(address.to_string()),
+            None => SnapcastTarget::Pipe(PathBuf::from(text)),
+        }
+    }
+
+    /// Shown in place of the device name
+    fn label(&self) -> String {
+        match self {
+            SnapcastTarget::Pipe(path) => format!("Snapcast ({})", path.display()),
+            SnapcastTarget::Tcp(address) => format!("Snapcast (tcp://{})", address),
+        }
+    }
+
+    fn open(&self) -> io::Result<Box<dyn Write>> {
+        Ok(match self {
+            SnapcastTarget::Pipe(path) => Box::new(OpenOptions::new().write(true).open(path)?),
+            SnapcastTarget::Tcp(address) => Box::new(TcpStream::connect(address)?),
+        })
+    }
+}
+
+/// Drives the output mixer in place of a sound card for `--snapcast`: a
+/// thread pulls the mix in real time, `LEAD` ahead of the clock, and a
+/// writer thread sends it to snapserver as s16le, which then keeps the
+/// rooms in sync. Nothing waits for snapserver: while it isn't reading,
+/// chunks are dropped and playback goes on. Dropping the feed stops it.
+struct SnapcastFeed {
+    stop: Arc<AtomicBool>,
+}
+
+impl SnapcastFeed {
+    const CHUNK: Duration = Duration::from_millis(20);
+    const LEAD: Duration = Duration::from_millis(100);
+
+    fn start(
+        target: SnapcastTarget,
+        mut mixer: DynamicMixer<f32>,
+        state: StreamCallbackState,
+    ) -> Self {
+        let StreamCallbackState {
+            dither,
+            dither_bypass,
+            stream_error: _,
+            tap,
+            zones,
+            broadcast,
+            timing,
+        } = state;
+        let channels = SnapcastTarget::CHANNELS;
+        let mut ditherer = (dither != DitherMode::Off).then(|| Ditherer::new(dither, 16, channels));
+        let writer = FifoWriter::start_with(
+            move || target.open(),
+            |samples: Vec<f32>| {
+                samples
+                    .iter()
+                    .flat_map(|s| ((s.clamp(-1.0, 1.0) * 32767.0) as i16).to_le_bytes())
+                    .collect()
+            },
+        );
+        let stop = Arc::new(AtomicBool::new(false));
+        let running = stop.clone();
+        let chunk_samples = (SnapcastTarget::SAMPLE_RATE as usize / 50) * channels as usize;
+
+        thread::spawn(move || {
+            let mut started = Instant::now();
+            let mut sent = Duration::ZERO;
+            while !running.load(Ordering::Relaxed) {
+                let now = Instant::now();
+                let due = started + sent;
+                if due > now + Self::LEAD {
+                    thread::sleep(due - now - Self::LEAD);
+                } else if now > due + Duration::from_secs(1) {
+                    // Far behind (a suspend, say): start over from now
+                    // rather than catch up in a burst
+                    (started, sent) = (now, Duration::ZERO);
+                }
+
+                let pulled = Instant::now();
+                let mixed: Vec<f32> = (0..chunk_samples)
+                    .map(|_| mixer.next().unwrap_or(0.0))
+                    .collect();
+                if let Ok(zones) = zones.try_lock() {
+                    for zone in zones.iter() {
+                        let _ = zone.try_send(mixed.clone());
+                    }
+                }
+                if let Some(tap) = tap.as_ref() {
+                    let _ = tap.try_send(mixed.clone());
+                }
+                if let Some(broadcast) = broadcast.try_lock().ok().and_then(|feed| feed.clone()) {
+                    let _ = broadcast.try_send(mixed.clone());
+                }
+                let samples = match ditherer.as_mut() {
+                    Some(ditherer) if !dither_bypass.load(Ordering::Relaxed) => {
+                        mixed.into_iter().map(|s| ditherer.process(s)).collect()
+                    }
+                    _ => mixed,
+                };
+                let _ = writer.try_send(samples);
+                sent += Self::CHUNK;
+                timing.record(pulled.elapsed(), Self::CHUNK);
+            }
+        });
+
+        Self { stop }
+    }
+}
+
+impl Drop for SnapcastFeed {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Bar encoding of cava's raw output (`method = raw` in cava's config)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum CavaFormat {
+    /// `data_format = binary`, `bit_format = 16bit`: one native-endian u16 per bar
+    Binary16,
+    /// `data_format = binary`, `bit_format = 8bit`: one byte per bar
+    Binary8,
+    /// `data_format = ascii`: values 0-1000 separated by ';', one frame per line
+    Ascii,
+}
+
+impl CavaFormat {
+    pub(crate) fn encode(&self, bars: &[f32]) -> Vec<u8> {
+        let levels = bars.iter().map(|b| b.clamp(0.0, 1.0));
+        match self {
+            CavaFormat::Binary16 => levels
+                .flat_map(|b| ((b * 65535.0) as u16).to_ne_bytes())
+                .collect(),
+            CavaFormat::Binary8 => levels.map(|b| (b * 255.0) as u8).collect(),
+            CavaFormat::Ascii => {
+                let mut line: String = levels
+                    .map(|b| format!("{};", (b * 1000.0) as u32))
+                    .collect();
+                line.push('\n');
+                line.into_bytes()
+            }
+        }
+    }
+}
+
+/// Output stream on the configured device. The cpal stream is built here instead of
+/// through rodio's OutputStream so that buffer size and sample rate can be chosen;
+/// sinks are attached to a rodio mixer that the stream callback drains.
+struct AudioOutput {
+    stream: Option<cpal::Stream>,
+    /// Stands in for `stream` with `--snapcast`
+    snapcast: Option<SnapcastFeed>,
+    mixer: Arc<DynamicMixerController<f32>>,
+    device_name: String,
+    sample_rate: u32,
+    channels: u16,
+    sample_format: SampleFormat,
+    buffer_frames: Option<u32>,
+    dither: DitherMode,
+    dither_bypass: Arc<AtomicBool>,
+    stream_error: Arc<Mutex<Option<String>>>,
+    timing: Arc<CallbackTiming>,
+}
+
+impl AudioOutput {
+    /// Opens the output. With `sample_rate` set, a device configuration running at
+    /// that rate is preferred over the default one (used by exclusive mode).
+    fn open(
+        settings: &Settings,
+        sample_rate: Option<u32>,
+        tap: Option<SyncSender<Vec<f32>>>,
+        zones: ZoneFeeds,
+        broadcast: BroadcastFeed,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(target) = &settings.snapcast {
+            return Ok(Self::open_snapcast(settings, target, tap, zones, broadcast));
+        }
+        let device = Self::find_device(settings.backend, settings.device.as_deref())?;
+        let default_config = device.default_output_config()?;
+        let supported = sample_rate
+            .and_then(|rate| Self::config_for_rate(&device, &default_config, rate))
+            .unwrap_or(default_config);
+
+        // The driver rejects sizes outside its range, so clamp rather than fail
+        let buffer_frames = settings
+            .buffer_frames
+            .map(|frames| match supported.buffer_size() {
+                SupportedBufferSize::Range { min, max } => frames.clamp(*min, *max),
+                SupportedBufferSize::Unknown => frames,
+            });
+
+        let config = StreamConfig {
+            channels: supported.channels(),
+            sample_rate: supported.sample_rate(),
+            buffer_size: buffer_frames.map_or(BufferSize::Default, BufferSize::Fixed),
+        };
+        let (mixer, mixer_rx) = dynamic_mixer::mixer::<f32>(config.channels, config.sample_rate.0);
+        let stream_error = Arc::new(Mutex::new(None));
+        let dither_bypass = Arc::new(AtomicBool::new(false));
+        let timing = Arc::new(CallbackTiming::default());
+        let callback_state = StreamCallbackState {
+            dither: settings.dither,
+            dither_bypass: dither_bypass.clone(),
+            stream_error: stream_error.clone(),
+            tap,
+            zones,
+            broadcast,
+            timing: timing.clone(),
+        };
+
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => {
+                Self::build_stream::<f32>(&device, &config, mixer_rx, callback_state)
+            }
+            SampleFormat::I16 => {
+                Self::build_stream::<i16>(&device, &config, mixer_rx, callback_state)
+            }
+            SampleFormat::U16 => {
+                Self::build_stream::<u16>(&device, &config, mixer_rx, callback_state)
+            }
+            SampleFormat::I32 => {
+                Self::build_stream::<i32>(&device, &config, mixer_rx, callback_state)
+            }
+            format => return Err(format!("Formato di uscita non supportato: {}", format).into()),
+        }?;
+        stream.play()?;
+
+        Ok(Self {
+            stream: Some(stream),
+            snapcast: None,
+            mixer,
+            device_name: device.name().unwrap_or_else(|_| "?".to_string()),
+            sample_rate: config.sample_rate.0,
+            channels: config.channels,
+            sample_format: supported.sample_format(),
+            buffer_frames,
+            dither: settings.dither,
+            dither_bypass,
+            stream_error,
+            timing,
+        })
+    }
+
+    /// Output to snapserver at its fixed format; the device settings don't apply
+    fn open_snapcast(
+        settings: &Settings,
+        target: &SnapcastTarget,
+        tap: Option<SyncSender<Vec<f32>>>,
+        zones: ZoneFeeds,
+        broadcast: BroadcastFeed,
+    ) -> Self {
+        let (sample_rate, channels) = (SnapcastTarget::SAMPLE_RATE, SnapcastTarget::CHANNELS);
+        let (mixer, mixer_rx) = dynamic_mixer::mixer::<f32>(channels, sample_rate);
+        let stream_error = Arc::new(Mutex::new(None));
+        let dither_bypass = Arc::new(AtomicBool::new(false));
+        let timing = Arc::new(CallbackTiming::default());
+        let callback_state = StreamCallbackState {
+            dither: settings.dither,
+            dither_bypass: dither_bypass.clone(),
+            stream_error: stream_error.clone(),
+            tap,
+            zones,
+            broadcast,
+            timing: timing.clone(),
+        };
+        let feed = SnapcastFeed::start(target.clone(), mixer_rx, callback_state);
+
+        Self {
+            stream: None,
+            snapcast: Some(feed),
+            mixer,
+            device_name: target.label(),
+            sample_rate,
+            channels,
+            sample_format: SampleFormat::I16,
+            buffer_frames: None,
+            dither: settings.dither,
+            dither_bypass,
+            stream_error,
+            timing,
+        }
+    }
+
+    /// Default device, or the first one whose name contains `name`
+    /// (e.g. "hw:CARD=PCH" to bypass the ALSA mixer)
+    fn find_device(
+        backend: AudioBackend,
+        name: Option<&str>,
+    ) -> Result<cpal::Device, Box<dyn std::error::Error>> {
+        let host = backend.host()?;
+        match name {
+            Some(name) => {
+                let devices: Vec<cpal::Device> = host.output_devices()?.collect();
+                let exact = devices
+                    .iter()
+                    .position(|d| d.name().is_ok_and(|n| n == name));
+                let partial = || {
+                    devices
+                        .iter()
+                        .position(|d| d.name().is_ok_and(|n| n.contains(name)))
+                };
+                exact
+                    .or_else(partial)
+                    .map(|i| devices.into_iter().nth(i).unwrap())
+                    .ok_or_else(|| format!("Dispositivo non trovato: {}", name).into())
+            }
+            None => host
+                .default_output_device()
+                .ok_or_else(|| "Nessun dispositivo audio disponibile".into()),
+        }
+    }
+
+    /// Finds a configuration running at `rate`, keeping the default channel count
+    /// and preferring float or wide integer formats
+    fn config_for_rate(
+        device: &cpal::Device,
+        default_config: &cpal::SupportedStreamConfig,
+        rate: u32,
+    ) -> Option<cpal::SupportedStreamConfig> {
+        let format_rank = |format: SampleFormat| match format {
+            SampleFormat::F32 => 0,
+            SampleFormat::I32 => 1,
+            SampleFormat::I16 => 2,
+            SampleFormat::U16 => 3,
+            _ => 4,
+        };
+
+        device
+            .supported_output_configs()
+            .ok()?
+            .filter(|c| c.channels() == default_config.channels())
+            .filter(|c| c.min_sample_rate().0 <= rate && rate <= c.max_sample_rate().0)
+            .filter(|c| format_rank(c.sample_format()) < 4)
+            .min_by_key(|c| format_rank(c.sample_format()))
+            .map(|c| c.with_sample_rate(cpal::SampleRate(rate)))
+    }
+
+    /// Bits per sample the device takes; a float sample holds 24
+    fn bits(&self) -> u8 {
+        match self.sample_format {
+            SampleFormat::I16 | SampleFormat::U16 => 16,
+            SampleFormat::I32 => 32,
+            _ => 24,
+        }
+    }
+
+    /// Label of the dither actually applied on this output, if any
+    fn active_dither(&self) -> Option<&'static str> {
+        let narrow = matches!(self.sample_format, SampleFormat::I16 | SampleFormat::U16);
+        if narrow && self.dither != DitherMode::Off && !self.dither_bypass.load(Ordering::Relaxed) {
+            Some(self.dither.label())
+        } else {
+            None
+        }
+    }
+
+    /// Disables dither while the signal path is bit-perfect, so untouched
+    /// 16-bit material is not altered
+    fn set_dither_bypass(&self, bypass: bool) {
+        self.dither_bypass.store(bypass, Ordering::Relaxed);
+    }
+
+    /// Releases the device. Needed before reopening a hw device, which only
+    /// accepts one stream at a time.
+    fn close(&mut self) {
+        self.stream = None;
+        self.snapcast = None;
+    }
+
+    fn build_stream<T>(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        mut mixer: DynamicMixer<f32>,
+        state: StreamCallbackState,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError>
+    where
+        T: SizedSample + FromSample<f32>,
+    {
+        let StreamCallbackState {
+            dither,
+            dither_bypass,
+            stream_error,
+            tap,
+            zones,
+            broadcast,
+            timing,
+        } = state;
+        let samples_per_sec = config.sample_rate.0 as f64 * config.channels as f64;
+
+        // Only narrow formats lose precision when converting from the f32 mixer
+        let mut ditherer = match T::FORMAT {
+            SampleFormat::I16 | SampleFormat::U16 if dither != DitherMode::Off => {
+                Some(Ditherer::new(dither, 16, config.channels))
+            }
+            _ => None,
+        };
+
+        device.build_output_stream::<T, _, _>(
+            config,
+            move |data: &mut [T], _| {
+                let started = Instant::now();
+                let bypass = dither_bypass.load(Ordering::Relaxed);
+                // Adding or removing a zone holds the lock briefly; skip a
+                // buffer for the zones rather than wait in the audio thread
+                let zones = zones.try_lock().ok().filter(|zones| !zones.is_empty());
+                let broadcast = broadcast.try_lock().ok().and_then(|feed| feed.clone());
+                let mut tapped = (tap.is_some() || zones.is_some() || broadcast.is_some())
+                    .then(|| Vec::with_capacity(data.len()));
+                for out in data.iter_mut() {
+                    let mixed = mixer.next();
+                    if let Some(tapped) = tapped.as_mut() {
+                        tapped.push(mixed.unwrap_or(0.0));
+                    }
+                    let sample = match (mixed, ditherer.as_mut()) {
+                        (Some(sample), Some(ditherer)) if !bypass => ditherer.process(sample),
+                        (sample, _) => sample.unwrap_or(0.0),
+                    };
+                    *out = T::from_sample(sample);
+                }
+                // Never block the audio thread: if a reader is behind, drop the buffer
+                if let (Some(zones), Some(tapped)) = (zones.as_ref(), tapped.as_ref()) {
+                    for zone in zones.iter() {
+                        let _ = zone.try_send(tapped.clone());
+                    }
+                }
+                if let (Some(broadcast), Some(tapped)) = (broadcast, tapped.as_ref()) {
+                    let _ = broadcast.try_send(tapped.clone());
+                }
+                if let (Some(tap), Some(tapped)) = (tap.as_ref(), tapped) {
+                    let _ = tap.try_send(tapped);
+                }
+                let budget = Duration::from_secs_f64(data.len() as f64 / samples_per_sec);
+                timing.record(started.elapsed(), budget);
+            },
+            // Printing would corrupt the TUI, so keep the error for the UI to show
+            move |err| *stream_error.lock().unwrap() = Some(err.to_string()),
+            None,
+        )
+    }
+
+    fn new_sink(&self) -> Sink {
+        let (sink, queue_rx) = Sink::new_idle();
+        self.mixer.add(queue_rx);
+        sink
+    }
+
+    /// Latency added by the output buffer, if a fixed size was requested
+    fn buffer_latency(&self) -> Option<Duration> {
+        self.buffer_frames
+            .map(|frames| Duration::from_secs_f64(frames as f64 / self.sample_rate as f64))
+    }
+
+    fn take_error(&self) -> Option<String> {
+        self.stream_error.lock().unwrap().take()
+    }
+}
+
+/// What a stream on the output mixer is for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum StreamRole {
+    /// The track being played
+    Main,
+    /// Short preview from the browser, replaces the main track
+    Preview,
+    /// One-shot sound effect, mixed over the rest
+    Sfx,
+    /// Click track of the practice tools
+    Metronome,
+    /// Test signal of the generator mode, replaces the main track
+    Generator,
+}
+
+impl StreamRole {
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            StreamRole::Main => "Traccia",
+            StreamRole::Preview => "Anteprima",
+            StreamRole::Sfx => "SFX",
+            StreamRole::Metronome => "Metronomo",
+            StreamRole::Generator => "Generatore",
+        }
+    }
+
+    /// Main, preview and generator feed the spectrum and the position; only
+    /// one at a time
+    fn is_primary(&self) -> bool {
+        matches!(
+            self,
+            StreamRole::Main | StreamRole::Preview | StreamRole::Generator
+        )
+    }
+}
+
+/// A sink on the output mixer. Its volume is the master volume times `gain`.
+pub(crate) struct MixerStream {
+    pub(crate) id: u64,
+    pub(crate) role: StreamRole,
+    pub(crate) name: String,
+    pub(crate) sink: Sink,
+    pub(crate) gain: f32,
+    /// Level of the stream's `Gain` node: the player volume times `gain`
+    level: Arc<AtomicU32>,
+}
+
+impl MixerStream {
+    fn set_level(&self, level: f32) {
+        self.level.store(level.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Volume of the player's own stream in the PulseAudio/PipeWire mixer, read
+/// and set through `pactl`. With it the player's volume is that one, instead
+/// of a second gain multiplied with it: a worker applies changes made in the
+/// player and reports the ones made in pavucontrol or by media keys.
+struct SystemVolume {
+    requests: mpsc::Sender<f32>,
+    /// New volumes set from outside; `None` once `pactl` turns out unusable
+    changes: mpsc::Receiver<Option<f32>>,
+}
+
+impl SystemVolume {
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+    /// Pulse's volume for 100%
+    const NORM: f32 = 65536.0;
+
+    fn start() -> Self {
+        let (requests, pending) = mpsc::channel::<f32>();
+        let (reports, changes) = mpsc::channel();
+        thread::spawn(move || {
+            let pid = process::id().to_string();
+            let mut request = None;
+            let mut reported: Option<f32> = None;
+            loop {
+                match pending.recv_timeout(Self::POLL_INTERVAL) {
+                    Ok(volume) => request = Some(volume),
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+                // Only the latest of a burst of key presses matters
+                request = pending.try_iter().last().or(request);
+
+                let stream = match Self::query(&pid) {
+                    Ok(stream) => stream,
+                    Err(()) => {
+                        let _ = reports.send(None);
+                        return;
+                    }
+                };
+                // Until the output is open there's no stream to act on
+                let Some((index, volume)) = stream else {
+                    continue;
+                };
+                if let Some(volume) = request.take() {
+                    Self::set(index, volume);
+                    reported = Some(volume);
+                } else if reported.is_none_or(|r| (r - volume).abs() > 0.005) {
+                    reported = Some(volume);
+                    if reports.send(Some(volume)).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Self { requests, changes }
+    }
+
+    /// Index and volume of this process's stream; Err when `pactl` can't
+    /// be run or doesn't speak JSON
+    fn query(pid: &str) -> Result<Option<(u64, f32)>, ()> {
+        let output = process::Command::new("pactl")
+            .args(["--format=json", "list", "sink-inputs"])
+            .stderr(process::Stdio::null())
+            .output()
+            .map_err(|_| ())?;
+        let inputs: serde_json::Value = serde_json::from_slice(&output.stdout).map_err(|_| ())?;
+        let Some(input) = inputs
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|input| input["properties"]["application.process.id"].as_str() == Some(pid))
+        else {
+            return Ok(None);
+        };
+        let Some(index) = input["index"].as_u64() else {
+            return Ok(None);
+        };
+        let channels: Vec<f32> = input["volume"]
+            .as_object()
+            .into_iter()
+            .flat_map(|channels| channels.values())
+            .filter_map(|channel| channel["value"].as_f64())
+            .map(|value| value as f32 / Self::NORM)
+            .collect();
+        if channels.is_empty() {
+            return Ok(None);
+        }
+        let volume = channels.iter().sum::<f32>() / channels.len() as f32;
+        Ok(Some((index, volume)))
+    }
+
+    fn set(index: u64, volume: f32) {
+        let _ = process::Command::new("pactl")
+            .args([
+                "set-sink-input-volume".to_string(),
+                index.to_string(),
+                format!("{}", (volume * Self::NORM).round() as u32),
+            ])
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null())
+            .status();
+    }
+}
+
+/// Paired Bluetooth audio device, as `bluetoothctl` reports it
+#[derive(Clone, Debug)]
+pub(crate) struct BluetoothDevice {
+    pub(crate) address: String,
+    pub(crate) name: String,
+    pub(crate) connected: bool,
+}
+
+/// Bluetooth audio devices through `bluetoothctl`, which talks to bluez over
+/// D-Bus, and routing through `pactl`: a device that connects gets its sink
+/// made the default, and the player's stream is moved there. That only
+/// works when the output goes through PulseAudio or PipeWire (the default
+/// device), not a raw ALSA one. Everything here blocks for seconds, so it
+/// runs on worker threads.
+///
+/// Both tools have to be installed: `bluetoothctl` comes with bluez (the
+/// `bluez-utils` package on some distributions), `pactl` with PulseAudio or
+/// `pipewire-pulse`. What `bluetoothctl` prints is meant for people, not a
+/// stable interface, so the parsing below sticks to the `Device`, `Paired:`,
+/// `Connected:`, `Icon:` and `UUID:` lines bluez has printed for years.
+pub(crate) struct Bluetooth;
+
+impl Bluetooth {
+    /// How long a connected device's sink may take to show up in `pactl`
+    const SINK_WAIT: Duration = Duration::from_secs(5);
+
+    /// Paired devices that are audio devices
+    pub(crate) fn devices() -> Result<Vec<BluetoothDevice>, String> {
+        let mut devices = Vec::new();
+        for line in Self::run(&["devices", "Paired"])?.lines() {
+            let mut words = line.splitn(3, ' ');
+            let (Some("Device"), Some(address), Some(name)) =
+                (words.next(), words.next(), words.next())
+            else {
+                continue;
+            };
+            let info = Self::run(&["info", address])?;
+            let field = |key: &str| {
+                info.lines()
+                    .filter_map(|line| line.trim().strip_prefix(key))
+                    .map(str::trim)
+                    .collect::<Vec<_>>()
+            };
+            let audio = field("Icon:").iter().any(|icon| icon.starts_with("audio-"))
+                || field("UUID:")
+                    .iter()
+                    .any(|uuid| uuid.starts_with("Audio Sink"));
+            // Older bluez lists every known device, paired or not
+            if !audio || field("Paired:").first() != Some(&"yes") {
+                continue;
+            }
+            devices.push(BluetoothDevice {
+                address: address.to_string(),
+                name: name.to_string(),
+                connected: field("Connected:").first() == Some(&"yes"),
+            });
+        }
+        Ok(devices)
+    }
+
+    /// Connects `address` and sends the player's output to it
+    pub(crate) fn connect(address: &str) -> Result<(), String> {
+        let output = Self::run(&["connect", address])?;
+        if !output.contains("Connection successful") {
+            return Err(Self::failure(&output));
+        }
+        Self::route_output(address)
+    }
+
+    pub(crate) fn disconnect(address: &str) -> Result<(), String> {
+        let output = Self::run(&["disconnect", address])?;
+        if !output.contains("Successful disconnected") {
+            return Err(Self::failure(&output));
+        }
+        Ok(())
+    }
+
+    /// Makes the device's sink (`bluez_output.XX_XX…` on PipeWire,
+    /// `bluez_sink.XX_XX…` on PulseAudio) the default and moves the
+    /// player's stream to it
+    fn route_output(address: &str) -> Result<(), String> {
+        let id = address.replace(':', "_");
+        let started = Instant::now();
+        let sink = loop {
+            let sinks = Self::pactl(&["list", "short", "sinks"])?;
+            let sink = sinks
+                .lines()
+                .filter_map(|line| line.split('\t').nth(1))
+                .find(|name| name.starts_with("bluez_") && name.contains(&id))
+                .map(str::to_string);
+            match sink {
+                Some(sink) => break sink,
+                None if started.elapsed() < Self::SINK_WAIT => {
+                    thread::sleep(Duration::from_millis(500))
+                }
+                None => return Err("nessuna uscita audio per il dispositivo".to_string()),
+            }
+        };
+        Self::pactl(&["set-default-sink", &sink])?;
+        let pid = process::id().to_string();
+        if let Ok(Some((index, _))) = SystemVolume::query(&pid) {
+            Self::pactl(&["move-sink-input", &index.to_string(), &sink])?;
+        }
+        Ok(())
+    }
+
+    fn run(args: &[&str]) -> Result<String, String> {
+        let output = process::Command::new("bluetoothctl")
+            .args(args)
+            .stdin(process::Stdio::null())
+            .stderr(process::Stdio::null())
+            .output()
+            .map_err(|e| Self::missing("bluetoothctl", "bluez", e))?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn pactl(args: &[&str]) -> Result<String, String> {
+        let output = process::Command::new("pactl")
+            .args(args)
+            .stdin(process::Stdio::null())
+            .output()
+            .map_err(|e| Self::missing("pactl", "PulseAudio o pipewire-pulse", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Names the package to install when `tool` isn't there
+    fn missing(tool: &str, package: &str, error: io::Error) -> String {
+        if error.kind() == io::ErrorKind::NotFound {
+            format!("{} non trovato: installa {}", tool, package)
+        } else {
+            format!("{} non disponibile: {}", tool, error)
+        }
+    }
+
+    /// bluetoothctl's last line says what went wrong
+    fn failure(output: &str) -> String {
+        output
+            .lines()
+            .map(str::trim)
+            .rfind(|line| !line.is_empty())
+            .unwrap_or("operazione non riuscita")
+            .to_string()
+    }
+}
+
+/// Central audio playback manager
+pub struct AudioPlayer {
+    settings: Settings,
+    output: AudioOutput,
+    tap: Option<PcmTap>,
+    /// Other devices playing along, in the same order as `zone_feeds`
+    zones: Vec<Zone>,
+    zone_feeds: ZoneFeeds,
+    /// Stream to an Icecast mount, with `--icecast`
+    broadcast: Option<Broadcast>,
+    broadcast_feed: BroadcastFeed,
+    /// Everything playing on the output mixer
+    streams: Vec<MixerStream>,
+    next_stream_id: u64,
+    volume: f32,
+    max_volume: f32,
+    /// Volume kept in the system mixer instead of applied here
+    system_volume: Option<SystemVolume>,
+    audio_buffer: Arc<Mutex<CaptureBuffer>>,
+    capture_enabled: Arc<AtomicBool>,
+    /// Frames of the primary stream played so far, see `SampleCapturer`
+    frames_played: Arc<AtomicU64>,
+    /// Track to follow the main one without a gap, see `Gapless`
+    next_source: NextSource,
+    next_crossfade: Arc<AtomicBool>,
+    next_switched: Arc<AtomicBool>,
+    next_track: Option<(PathBuf, Option<Duration>)>,
+    sample_rate: u32,
+    channels: u16,
+    /// Channels of the file itself, before any downmix
+    source_channels: u16,
+    /// Bits per sample of the file itself, None when not known or lossy
+    source_bits: Option<u8>,
+    /// Equalizer preset, read by the playing track's equalizer
+    eq_preset: Arc<AtomicUsize>,
+    /// Tempo of the metronome, as f32 bits
+    metronome_bpm: Arc<AtomicU32>,
+    /// The music is lowered for an announcement, see `set_ducked`
+    ducked: bool,
+    /// Duck factor of the music's `Gain` nodes (f32 bits)
+    duck: Arc<AtomicU32>,
+    /// Frequency band played alone, see `BandSolo`
+    band_solo: Arc<AtomicU64>,
+    /// Meter of the main track playing, see `TruePeakMeter`
+    true_peak: Option<(PathBuf, Arc<TruePeak>)>,
+    /// Meter of the track queued with `queue_next`
+    next_true_peak: Arc<TruePeak>,
+    /// Readings of the tracks that stopped playing, for `take_true_peaks`
+    finished_peaks: Vec<(PathBuf, TruePeakReading)>,
+    is_playing: Arc<Mutex<bool>>,
+    total_duration: Option<Duration>,
+    duration_cache: HashMap<PathBuf, Option<Duration>>,
+    /// Frame scan of a file with no cached duration, see `lookup_duration`
+    duration_job: Option<(PathBuf, mpsc::Receiver<Option<Duration>>)>,
+    /// Track of the primary stream, for a duration that arrives late
+    primary_path: Option<PathBuf>,
+}
+
+impl AudioPlayer {
+    pub fn new(settings: &Settings) -> Result<Self, Box<dyn std::error::Error>> {
+        let tap = settings
+            .tap_path
+            .clone()
+            .map(|path| PcmTap::start(path, settings.tap_format));
+        let zone_feeds = ZoneFeeds::default();
+        let broadcast_feed = BroadcastFeed::default();
+        let output = AudioOutput::open(
+            settings,
+            None,
+            tap.as_ref().map(|t| t.sender.clone()),
+            zone_feeds.clone(),
+            broadcast_feed.clone(),
+        )
+        .map_err(|e| format!("Errore inizializzazione audio: {}", e))?;
+        let broadcast = settings.icecast.clone().map(|target| {
+            Broadcast::start(
+                target,
+                settings.icecast_codec,
+                settings.icecast_bitrate,
+                output.sample_rate,
+                output.channels,
+            )
+        });
+        *broadcast_feed.lock().unwrap() = broadcast.as_ref().map(|b| b.sender.clone());
+        Ok(Self {
+            settings: settings.clone(),
+            output,
+            tap,
+            zones: Vec::new(),
+            zone_feeds,
+            broadcast,
+            broadcast_feed,
+            streams: Vec::new(),
+            next_stream_id: 0,
+            volume: settings
+                .volume
+                .min(settings.max_startup_volume)
+                .min(settings.max_volume),
+            max_volume: settings.max_volume,
+            system_volume: settings.system_volume.then(SystemVolume::start),
+            audio_buffer: Arc::new(Mutex::new(CaptureBuffer::default())),
+            capture_enabled: Arc::new(AtomicBool::new(settings.visualizer)),
+            frames_played: Arc::new(AtomicU64::new(0)),
+            next_source: NextSource::default(),
+            next_crossfade: Arc::new(AtomicBool::new(false)),
+            next_switched: Arc::new(AtomicBool::new(false)),
+            next_track: None,
+            sample_rate: 44100,
+            channels: 2,
+            source_channels: 2,
+            source_bits: None,
+            eq_preset: Arc::new(AtomicUsize::new(0)),
+            metronome_bpm: Arc::new(AtomicU32::new(120f32.to_bits())),
+            ducked: false,
+            duck: Arc::new(AtomicU32::new(1f32.to_bits())),
+            band_solo: Arc::new(AtomicU64::new(0)),
+            true_peak: None,
+            next_true_peak: Arc::default(),
+            finished_peaks: Vec::new(),
+            is_playing: Arc::new(Mutex::new(false)),
+            total_duration: None,
+            duration_cache: HashMap::new(),
+            duration_job: None,
+            primary_path: None,
+        })
+    }
+
+    pub fn play(&mut self, path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        self.play_primary(path, StreamRole::Main, 1.0)
+    }
+
+    /// Replaces the primary stream (main track or preview) with `path`
+    fn play_primary(
+        &mut self,
+        path: &PathBuf,
+        role: StreamRole,
+        gain: f32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop();
+        self.audio_buffer.lock().unwrap().clear();
+
+        let source = Self::open_source(path)?;
+        self.total_duration = self
+            .lookup_duration(path)
+            .or_else(|| source.total_duration());
+        self.start_primary(role, path, source, gain)
+    }
+
+    /// Plays a test signal as the primary stream, through the same EQ,
+    /// downmix and capture as a track
+    pub(crate) fn play_generator(
+        &mut self,
+        signal: Signal,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop();
+        self.audio_buffer.lock().unwrap().clear();
+
+        let source = Generator::new(signal, self.output.sample_rate);
+        self.total_duration = source.total_duration();
+        self.start_primary(
+            StreamRole::Generator,
+            Path::new(&signal.label()),
+            Box::new(source),
+            1.0,
+        )
+    }
+
+    /// Plays headerless PCM from `path` (`-` for stdin) as the main stream
+    pub(crate) fn play_raw(
+        &mut self,
+        path: &Path,
+        layout: PcmLayout,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop();
+        self.audio_buffer.lock().unwrap().clear();
+
+        let source = RawPcmSource::open(path, layout)?;
+        self.total_duration = None;
+        self.start_primary(StreamRole::Main, path, Box::new(source), 1.0)
+    }
+
+    /// Plays what `decoder` writes for `path` (an audio CD track, say) as the
+    /// main stream
+    pub(crate) fn play_external(
+        &mut self,
+        decoder: &ExternalDecoder,
+        path: &Path,
+        duration: Option<Duration>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop();
+        self.audio_buffer.lock().unwrap().clear();
+
+        let source = RawPcmSource::spawn(decoder, path)?;
+        self.total_duration = duration;
+        self.start_primary(StreamRole::Main, path, Box::new(source), 1.0)
+    }
+
+    /// Plays one of the tunes of a game music file as the main stream
+    pub(crate) fn play_subtune(
+        &mut self,
+        tune: &Chiptune,
+        subtune: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop();
+        self.audio_buffer.lock().unwrap().clear();
+
+        let source = Self::open_chiptune(tune, subtune)?;
+        self.total_duration = source.total_duration();
+        self.start_primary(StreamRole::Main, &tune.path, source, 1.0)
+    }
+
+    fn start_primary(
+        &mut self,
+        role: StreamRole,
+        path: &Path,
+        source: Box<dyn Source<Item = f32> + Send>,
+        gain: f32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.sample_rate = source.sample_rate();
+        self.source_channels = source.channels();
+        self.source_bits = source_bit_depth(path);
+        self.primary_path = Some(path.to_path_buf());
+
+        if self.settings.exclusive && self.output.sample_rate != self.sample_rate {
+            self.output.close();
+            let tap = self.tap.as_ref().map(|t| t.sender.clone());
+            self.output = AudioOutput::open(
+                &self.settings,
+                Some(self.sample_rate),
+                tap,
+                self.zone_feeds.clone(),
+                self.broadcast_feed.clone(),
+            )
+            .map_err(|e| format!("Errore riapertura uscita audio: {}", e))?;
+            self.sync_zones();
+            self.sync_broadcast();
+        }
+
+        // A slot of its own, so nothing queued for the stream before follows
+        self.next_source = NextSource::default();
+        self.next_crossfade = Arc::new(AtomicBool::new(false));
+        self.next_switched = Arc::new(AtomicBool::new(false));
+        self.next_track = None;
+        let source: Box<dyn Source<Item = f32> + Send> = if role == StreamRole::Main {
+            let meter = Arc::<TruePeak>::default();
+            self.true_peak = Some((path.to_path_buf(), meter.clone()));
+            Box::new(TruePeakMeter::new(source, meter))
+        } else {
+            source
+        };
+        let source = Gapless::new(
+            self.downmixed(source),
+            self.next_source.clone(),
+            self.next_crossfade.clone(),
+            self.next_switched.clone(),
+            self.frames_played.clone(),
+        );
+        let source = Equalizer::new(source, self.eq_preset.clone());
+        self.channels = source.channels();
+        self.audio_buffer.lock().unwrap().reset(
+            self.settings.capture_window,
+            self.sample_rate,
+            self.channels,
+        );
+
+        let capturer = SampleCapturer::new(
+            source,
+            self.audio_buffer.clone(),
+            self.capture_enabled.clone(),
+            self.frames_played.clone(),
+        );
+        // After the capture: the analyzer keeps showing the whole spectrum
+        let solo = BandSolo::new(capturer, self.band_solo.clone());
+        self.add_stream(role, path, Box::new(solo), gain);
+
+        *self.is_playing.lock().unwrap() = true;
+        self.output.set_dither_bypass(self.is_bit_perfect());
+
+        Ok(())
+    }
+
+    /// Puts `source` on a new sink of the output mixer; returns the stream id
+    fn add_stream(
+        &mut self,
+        role: StreamRole,
+        path: &Path,
+        source: Box<dyn Source<Item = f32> + Send>,
+        gain: f32,
+    ) -> u64 {
+        // Finished side streams are dropped; the primary one stays, as its
+        // emptiness is how the end of the track is detected
+        self.streams
+            .retain(|stream| stream.role.is_primary() || !stream.sink.empty());
+
+        // The sink's own volume stays at 1: it would step, and multiply
+        // with whatever the node applies
+        let level = Arc::new(AtomicU32::new(self.stream_level(gain).to_bits()));
+        let node = Gain::new(source, level.clone());
+        let sink = self.output.new_sink();
+        // The music fades under an announcement at the ducking pace
+        if role.is_primary() {
+            sink.append(node.ducked(self.duck.clone(), &self.settings.ducking));
+        } else {
+            sink.append(node);
+        }
+        sink.play();
+
+        self.next_stream_id += 1;
+        self.streams.push(MixerStream {
+            id: self.next_stream_id,
+            role,
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            sink,
+            gain,
+            level,
+        });
+        self.next_stream_id
+    }
+
+    /// Opens `path` to play from `start` right after the main track, in the
+    /// same stream, or over its last `overlap` once `start_crossfade` is
+    /// called. Only a track in the format of the playing one can follow it
+    /// there.
+    pub(crate) fn queue_next(
+        &mut self,
+        path: &PathBuf,
+        start: Duration,
+        overlap: Duration,
+        curve: FadeCurve,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let source = Self::open_source(path)?;
+        if source.sample_rate() != self.sample_rate || source.channels() != self.source_channels {
+            return Err("formato diverso dalla traccia in corso".into());
+        }
+        let duration = self
+            .lookup_duration(path)
+            .or_else(|| source.total_duration());
+        self.next_true_peak = Arc::default();
+        let source = TruePeakMeter::new(source, self.next_true_peak.clone());
+        let mut source = self.downmixed(Box::new(source));
+        let start = if !start.is_zero()
+            && duration.is_none_or(|duration| start < duration)
+            && source.try_seek(start).is_ok()
+        {
+            start
+        } else {
+            Duration::ZERO
+        };
+        let frames = |time: Duration| (time.as_secs_f64() * self.sample_rate as f64) as u64;
+        *self.next_source.lock().unwrap() = Some(Follower {
+            source,
+            start: frames(start),
+            overlap: frames(overlap),
+            curve,
+        });
+        self.next_track = Some((path.clone(), duration));
+        Ok(())
+    }
+
+    /// Starts the queued track now, fading the main one out under it
+    pub(crate) fn start_crossfade(&self) {
+        if self.next_track.is_some() {
+            self.next_crossfade.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// The track queued with `queue_next`, until it starts or is cancelled
+    pub(crate) fn next_track(&self) -> Option<&Path> {
+        self.next_track.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    /// Drops the queued track, unless the stream already went on to it:
+    /// `take_transition` still reports that one
+    pub(crate) fn cancel_next(&mut self) {
+        let mut slot = self.next_source.lock().unwrap();
+        self.next_crossfade.store(false, Ordering::Relaxed);
+        if slot.take().is_some() || !self.next_switched.load(Ordering::Relaxed) {
+            self.next_track = None;
+        }
+    }
+
+    /// Once the main track ran into the queued one, makes that the playing
+    /// track and returns its path. The gain set for the track before goes:
+    /// the new one starts at its own level, as it would in a stream of its own.
+    pub(crate) fn take_transition(&mut self) -> Option<PathBuf> {
+        if !self.next_switched.swap(false, Ordering::Relaxed) {
+            return None;
+        }
+        let (path, duration) = self.next_track.take()?;
+        self.total_duration = duration;
+        self.primary_path = Some(path.clone());
+        self.finish_true_peak();
+        self.true_peak = Some((path.clone(), self.next_true_peak.clone()));
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if let Some(stream) = self.streams.iter_mut().find(|s| s.role.is_primary()) {
+            stream.name = name;
+        }
+        self.set_primary_gain(1.0);
+        Some(path)
+    }
+
+    /// Moves the primary stream to `position`
+    pub fn seek(&mut self, position: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        let stream = self
+            .primary_stream()
+            .ok_or("nessuna traccia in riproduzione")?;
+        stream
+            .sink
+            .try_seek(position)
+            .map_err(|e| format!("seek non supportato: {}", e))?;
+        // What the analyzer holds is from before the jump
+        self.audio_buffer.lock().unwrap().clear();
+        Ok(())
+    }
+
+    /// Moves the primary stream `by` ahead, stopping at the end of the
+    /// track, and returns the new position
+    pub fn seek_forward(&mut self, by: Duration) -> Result<Duration, Box<dyn std::error::Error>> {
+        let mut position = self.get_position().unwrap_or_default() + by;
+        if let Some(total) = self.total_duration {
+            position = position.min(total);
+        }
+        self.seek(position)?;
+        Ok(position)
+    }
+
+    /// Moves the primary stream `by` back, at most to its start, and
+    /// returns the new position
+    pub fn seek_backward(&mut self, by: Duration) -> Result<Duration, Box<dyn std::error::Error>> {
+        let position = self.get_position().unwrap_or_default().saturating_sub(by);
+        self.seek(position)?;
+        Ok(position)
+    }
+
+    /// Playback speed of the primary stream; 1.0 is normal speed
+    pub(crate) fn set_speed(&self, speed: f32) {
+        if let Some(stream) = self.primary_stream() {
+            stream.sink.set_speed(speed);
+        }
+    }
+
+    /// Position in the primary stream from the frames it has played, which
+    /// follows seeks, pauses and speed changes
+    pub fn get_position(&self) -> Option<Duration> {
+        self.primary_stream()?;
+        let frames = self.frames_played.load(Ordering::Relaxed);
+        Some(Duration::from_secs_f64(
+            frames as f64 / self.sample_rate.max(1) as f64,
+        ))
+    }
+
+    fn primary_stream(&self) -> Option<&MixerStream> {
+        self.streams.iter().find(|stream| stream.role.is_primary())
+    }
+
+    pub(crate) fn streams(&self) -> &[MixerStream] {
+        &self.streams
+    }
+
+    pub(crate) fn set_primary_gain(&mut self, gain: f32) {
+        if let Some(id) = self.primary_stream().map(|stream| stream.id) {
+            self.set_stream_gain(id, gain);
+        }
+    }
+
+    pub(crate) fn set_stream_gain(&mut self, id: u64, gain: f32) {
+        let Some(index) = self.streams.iter().position(|s| s.id == id) else {
+            return;
+        };
+        let gain = gain.max(0.0);
+        let level = self.stream_level(gain);
+        let stream = &mut self.streams[index];
+        stream.gain = gain;
+        stream.set_level(level);
+        self.output.set_dither_bypass(self.is_bit_perfect());
+    }
+
+    fn stop_streams(&mut self, stop: impl Fn(&MixerStream) -> bool) {
+        self.streams.retain(|stream| {
+            if stop(stream) {
+                stream.sink.stop();
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Picks the decoder for `path`: DSD containers are converted to PCM here,
+    /// everything else goes through rodio's decoder
+    pub(crate) fn open_source(
+        path: &PathBuf,
+    ) -> Result<Box<dyn Source<Item = f32> + Send>, Box<dyn std::error::Error>> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+
+        if let Some((archive, inner)) =
+            Archive::locate(path).filter(|(_, inner)| !inner.as_os_str().is_empty())
+        {
+            let data = archive.read(&inner)?;
+            if Chiptune::is_chiptune(path) {
+                let tune = Chiptune::from_data(path, data)?;
+                return Self::open_chiptune(&tune, tune.start);
+            }
+            if ext == "mp3" {
+                return Ok(Box::new(Mp3Source::new(Box::new(io::Cursor::new(data)))?));
+            }
+            Ok(Box::new(
+                Decoder::new(io::Cursor::new(data))?.convert_samples::<f32>(),
+            ))
+        } else if let Some(decoder) = ExternalDecoders::get().for_path(path) {
+            Ok(Box::new(RawPcmSource::spawn(decoder, path)?))
+        } else if Chiptune::is_chiptune(path) {
+            let tune = Chiptune::open(path)?;
+            Self::open_chiptune(&tune, tune.start)
+        } else if ext == "dsf" || ext == "dff" {
+            Ok(Box::new(DsdSource::open(path)?))
+        } else if ext == "mp3" {
+            Ok(Box::new(Mp3Source::new(Box::new(File::open(path)?))?))
+        } else {
+            let file = File::open(path)?;
+            Ok(Box::new(
+                Decoder::new(BufReader::new(file))?.convert_samples::<f32>(),
+            ))
+        }
+    }
+
+    /// The emulator for most formats, `sidplayfp` for SID
+    fn open_chiptune(
+        tune: &Chiptune,
+        subtune: usize,
+    ) -> Result<Box<dyn Source<Item = f32> + Send>, Box<dyn std::error::Error>> {
+        if let Some(source) = tune.source(subtune) {
+            return Ok(Box::new(source));
+        }
+        let decoder = tune.sid_decoder(subtune).ok_or("brano non riproducibile")?;
+        let length = tune.subtunes[subtune].length;
+        Ok(Box::new(
+            RawPcmSource::spawn(&decoder, &tune.path)?.with_duration(length),
+        ))
+    }
+
+    pub(crate) fn eq_preset(&self) -> usize {
+        self.eq_preset.load(Ordering::Relaxed)
+    }
+
+    /// Fades the music down to the `--duck-depth` level for an
+    /// announcement, or back up. Only the control socket asks for it.
+    #[cfg(unix)]
+    pub(crate) fn set_ducked(&mut self, ducked: bool) {
+        self.ducked = ducked;
+        let factor = if ducked {
+            self.settings.ducking.depth
+        } else {
+            1.0
+        };
+        self.duck.store(factor.to_bits(), Ordering::Relaxed);
+        self.output.set_dither_bypass(self.is_bit_perfect());
+    }
+
+    pub(crate) fn is_ducked(&self) -> bool {
+        self.ducked
+    }
+
+    /// Plays only the frequencies between the two edges, in Hz, or all of
+    /// them again
+    pub(crate) fn set_band_solo(&mut self, band: Option<(f32, f32)>) {
+        self.band_solo.store(pack_band(band), Ordering::Relaxed);
+        self.output.set_dither_bypass(self.is_bit_perfect());
+    }
+
+    pub(crate) fn band_solo(&self) -> Option<(f32, f32)> {
+        unpack_band(self.band_solo.load(Ordering::Relaxed))
+    }
+
+    /// True peak of the main track so far
+    pub(crate) fn true_peak(&self) -> Option<TruePeakReading> {
+        self.true_peak.as_ref().map(|(_, meter)| meter.reading())
+    }
+
+    /// Final readings of the main tracks played since the last call
+    pub(crate) fn take_true_peaks(&mut self) -> Vec<(PathBuf, TruePeakReading)> {
+        std::mem::take(&mut self.finished_peaks)
+    }
+
+    fn finish_true_peak(&mut self) {
+        if let Some((path, meter)) = self.true_peak.take() {
+            self.finished_peaks.push((path, meter.reading()));
+        }
+    }
+
+    pub(crate) fn set_eq_preset(&mut self, preset: usize) {
+        self.eq_preset
+            .store(preset % EQ_PRESETS.len(), Ordering::Relaxed);
+        self.output.set_dither_bypass(self.is_bit_perfect());
+    }
+
+    pub(crate) fn device_name(&self) -> &str {
+        &self.output.device_name
+    }
+
+    /// Names of the backend's output devices
+    pub(crate) fn output_devices(&self) -> Vec<String> {
+        self.settings
+            .backend
+            .host()
+            .ok()
+            .and_then(|host| host.output_devices().ok())
+            .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Reopens the output on the device called `name`. Everything playing is
+    /// stopped, since its sinks belong to the old output's mixer.
+    pub(crate) fn switch_device(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut settings = self.settings.clone();
+        settings.device = Some(name.to_string());
+        let rate = settings.exclusive.then_some(self.sample_rate);
+        let metronome = self.metronome_gain();
+        self.stop_streams(|_| true);
+        *self.is_playing.lock().unwrap() = false;
+        self.output.close();
+        let tap = self.tap.as_ref().map(|t| t.sender.clone());
+        let (zones, broadcast) = (self.zone_feeds.clone(), self.broadcast_feed.clone());
+        let result = match AudioOutput::open(
+            &settings,
+            rate,
+            tap.clone(),
+            zones.clone(),
+            broadcast.clone(),
+        ) {
+            Ok(output) => {
+                self.output = output;
+                self.settings = settings;
+                Ok(())
+            }
+            Err(e) => {
+                // Fall back to the device we had, so there is still an output
+                self.output = AudioOutput::open(&self.settings, rate, tap, zones, broadcast)
+                    .map_err(|e| format!("Errore riapertura uscita audio: {}", e))?;
+                Err(e)
+            }
+        };
+        // The new main device can't also be a zone
+        if let Some(index) = self
+            .zones
+            .iter()
+            .position(|zone| zone.device_name == self.output.device_name)
+        {
+            self.remove_zone(index);
+        }
+        self.sync_zones();
+        self.sync_broadcast();
+        // The click track isn't tied to a file, so it simply carries on
+        if let Some(gain) = metronome {
+            self.start_metronome(gain);
+        }
+        result
+    }
+
+    /// Opens the output again after it was lost (device unplugged, system
+    /// resumed from suspend). A named device that is gone gives way to the
+    /// default one. Like `switch_device`, everything playing is stopped.
+    pub(crate) fn reopen(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let rate = self.settings.exclusive.then_some(self.sample_rate);
+        let metronome = self.metronome_gain();
+        self.stop_streams(|_| true);
+        *self.is_playing.lock().unwrap() = false;
+        self.output.close();
+        let tap = self.tap.as_ref().map(|t| t.sender.clone());
+        let (zones, broadcast) = (self.zone_feeds.clone(), self.broadcast_feed.clone());
+        self.output = match AudioOutput::open(
+            &self.settings,
+            rate,
+            tap.clone(),
+            zones.clone(),
+            broadcast.clone(),
+        ) {
+            Ok(output) => output,
+            Err(_) if self.settings.device.is_some() => {
+                self.settings.device = None;
+                AudioOutput::open(&self.settings, rate, tap, zones, broadcast)?
+            }
+            Err(e) => return Err(e),
+        };
+        self.sync_zones();
+        self.sync_broadcast();
+        if let Some(gain) = metronome {
+            self.start_metronome(gain);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    /// Adds `name` as a zone, or removes it if it is one. True if added.
+    pub(crate) fn toggle_zone(&mut self, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(index) = self.zones.iter().position(|zone| zone.device_name == name) {
+            self.remove_zone(index);
+            return Ok(false);
+        }
+        if name == self.output.device_name {
+            return Err("è già l'uscita principale".into());
+        }
+        let (zone, feed) = Zone::open(
+            self.settings.backend,
+            name,
+            self.output.sample_rate,
+            self.output.channels,
+            1.0,
+        )?;
+        self.zones.push(zone);
+        self.zone_feeds.lock().unwrap().push(feed);
+        Ok(true)
+    }
+
+    fn remove_zone(&mut self, index: usize) {
+        self.zone_feeds.lock().unwrap().remove(index);
+        self.zones.remove(index);
+    }
+
+    /// Reopens the zones left at another rate or channel count by a new
+    /// main output; one that can't follow is dropped
+    fn sync_zones(&mut self) {
+        let (rate, channels) = (self.output.sample_rate, self.output.channels);
+        let mut index = 0;
+        while index < self.zones.len() {
+            let zone = &self.zones[index];
+            if zone.sample_rate == rate && zone.channels == channels {
+                index += 1;
+                continue;
+            }
+            let (name, volume) = (zone.device_name.clone(), zone.volume());
+            match Zone::open(self.settings.backend, &name, rate, channels, volume) {
+                Ok((zone, feed)) => {
+                    self.zones[index] = zone;
+                    self.zone_feeds.lock().unwrap()[index] = feed;
+                    index += 1;
+                }
+                Err(_) => self.remove_zone(index),
+            }
+        }
+    }
+
+    pub(crate) fn broadcast(&self) -> Option<&Broadcast> {
+        self.broadcast.as_ref()
+    }
+
+    /// Starts the stream to the Icecast mount given with `--icecast`, or
+    /// stops it if it is running. True if started.
+    pub(crate) fn toggle_broadcast(&mut self) -> Result<bool, String> {
+        if self.broadcast.is_some() {
+            self.set_broadcast(None);
+            return Ok(false);
+        }
+        let target = self
+            .settings
+            .icecast
+            .clone()
+            .ok_or("nessun mount, avvia con --icecast")?;
+        self.set_broadcast(Some(Broadcast::start(
+            target,
+            self.settings.icecast_codec,
+            self.settings.icecast_bitrate,
+            self.output.sample_rate,
+            self.output.channels,
+        )));
+        Ok(true)
+    }
+
+    /// Restarts the encoder for a new main output at another rate or
+    /// channel count; the listeners hear a short gap
+    fn sync_broadcast(&mut self) {
+        let (rate, channels) = (self.output.sample_rate, self.output.channels);
+        let restarted = self
+            .broadcast
+            .as_ref()
+            .filter(|b| b.sample_rate != rate || b.channels != channels)
+            .map(|b| b.restart(rate, channels));
+        if restarted.is_some() {
+            self.set_broadcast(restarted);
+        }
+    }
+
+    fn set_broadcast(&mut self, broadcast: Option<Broadcast>) {
+        *self.broadcast_feed.lock().unwrap() = broadcast.as_ref().map(|b| b.sender.clone());
+        self.broadcast = broadcast;
+    }
+
+    /// Folds multichannel sources to stereo when the output has fewer channels,
+    /// instead of leaving the extra channels to rodio's channel conversion
+    fn downmixed(
+        &self,
+        source: Box<dyn Source<Item = f32> + Send>,
+    ) -> Box<dyn Source<Item = f32> + Send> {
+        let channels = source.channels();
+        if !self.settings.downmix.enabled || channels <= 2 || channels <= self.output.channels {
+            return source;
+        }
+        match Speaker::layout(channels) {
+            Some(layout) => Box::new(Downmix::new(source, layout, &self.settings.downmix)),
+            None => source,
+        }
+    }
+
+    /// Duration read from the file itself for formats where the decoder's
+    /// estimate is unreliable (VBR MP3). A frame scan may read the whole
+    /// file, so one not cached yet runs on a worker thread and the decoder's
+    /// estimate stands in until `poll_duration` takes the result.
+    fn lookup_duration(&mut self, path: &PathBuf) -> Option<Duration> {
+        let is_mp3 = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("mp3"));
+        if !is_mp3 {
+            return None;
+        }
+        if let Some(&duration) = self.duration_cache.get(path) {
+            return duration;
+        }
+
+        if self
+            .duration_job
+            .as_ref()
+            .is_none_or(|(scanned, _)| scanned != path)
+        {
+            let (tx, rx) = mpsc::channel();
+            let scanned = path.clone();
+            thread::spawn(move || {
+                let _ = tx.send(mp3_duration(&scanned).ok().flatten());
+            });
+            self.duration_job = Some((path.clone(), rx));
+        }
+        None
+    }
+
+    /// Takes the duration found by a frame scan, if it finished; true when
+    /// it is the playing track's, which then has a new length
+    pub(crate) fn poll_duration(&mut self) -> bool {
+        let duration = match &self.duration_job {
+            Some((_, job)) => match job.try_recv() {
+                Ok(duration) => duration,
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => None,
+            },
+            None => return false,
+        };
+        let Some((path, _)) = self.duration_job.take() else {
+            return false;
+        };
+        self.duration_cache.insert(path.clone(), duration);
+        let Some(duration) = duration else {
+            return false;
+        };
+        if let Some((next, next_duration)) = &mut self.next_track
+            && *next == path
+        {
+            *next_duration = Some(duration);
+        }
+        if self.primary_path.as_ref() != Some(&path) {
+            return false;
+        }
+        self.total_duration = Some(duration);
+        true
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, self.max_volume);
+        if let Some(system) = &self.system_volume {
+            let _ = system.requests.send(self.volume);
+        }
+        self.apply_volume();
+    }
+
+    fn apply_volume(&mut self) {
+        for stream in &self.streams {
+            stream.set_level(self.stream_level(stream.gain));
+        }
+        self.output.set_dither_bypass(self.is_bit_perfect());
+    }
+
+    /// Level of a stream's `Gain` node: the volume times its own gain
+    fn stream_level(&self, gain: f32) -> f32 {
+        self.stream_volume() * gain
+    }
+
+    /// Gain applied to the streams: none when the system mixer does it
+    fn stream_volume(&self) -> f32 {
+        if self.system_volume.is_some() {
+            1.0
+        } else {
+            self.volume
+        }
+    }
+
+    /// Takes over volume changes made in the system mixer. Returns false when
+    /// the mixer can't be reached, and the volume went back to being the
+    /// player's own.
+    pub(crate) fn sync_system_volume(&mut self) -> bool {
+        let Some(system) = &self.system_volume else {
+            return true;
+        };
+        let mut changed = None;
+        for change in system.changes.try_iter() {
+            match change {
+                Some(volume) => changed = Some(volume),
+                None => {
+                    self.system_volume = None;
+                    self.apply_volume();
+                    return false;
+                }
+            }
+        }
+        if let Some(volume) = changed {
+            if volume > self.max_volume {
+                self.set_volume(volume);
+            } else {
+                self.volume = volume;
+            }
+        }
+        true
+    }
+
+    pub(crate) fn increase_volume(&mut self) {
+        self.set_volume(self.volume + 0.05);
+    }
+
+    pub(crate) fn decrease_volume(&mut self) {
+        self.set_volume(self.volume - 0.05);
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub(crate) fn get_max_volume(&self) -> f32 {
+        self.max_volume
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.primary_stream()
+            .is_some_and(|stream| !stream.sink.empty() && !stream.sink.is_paused())
+    }
+
+    /// Starts `path` on a sink of its own, mixed over whatever is playing.
+    /// Effects aren't captured for the spectrum and can overlap freely.
+    pub(crate) fn play_sfx(&mut self, path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        let source = self.downmixed(Self::open_source(path)?);
+        self.add_stream(StreamRole::Sfx, path, source, 1.0);
+        Ok(())
+    }
+
+    pub(crate) fn stop_sfx(&mut self) {
+        self.stop_streams(|stream| stream.role == StreamRole::Sfx);
+    }
+
+    /// Gain of the main track or preview, if one is playing
+    pub(crate) fn primary_gain(&self) -> Option<f32> {
+        self.primary_stream().map(|stream| stream.gain)
+    }
+
+    /// Gain of the metronome stream, if it is running
+    pub(crate) fn metronome_gain(&self) -> Option<f32> {
+        self.streams
+            .iter()
+            .find(|stream| stream.role == StreamRole::Metronome)
+            .map(|stream| stream.gain)
+    }
+
+    pub(crate) fn start_metronome(&mut self, gain: f32) {
+        let source = Metronome::new(self.metronome_bpm.clone());
+        self.add_stream(
+            StreamRole::Metronome,
+            Path::new("click"),
+            Box::new(source),
+            gain,
+        );
+    }
+
+    pub(crate) fn stop_metronome(&mut self) {
+        self.stop_streams(|stream| stream.role == StreamRole::Metronome);
+    }
+
+    pub(crate) fn metronome_bpm(&self) -> f32 {
+        f32::from_bits(self.metronome_bpm.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set_metronome_bpm(&self, bpm: f32) {
+        self.metronome_bpm.store(bpm.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Plays `path` from `start` (a fraction of its length) with the sink
+    /// attenuated by `gain`. Formats that can't seek preview from the start.
+    pub(crate) fn preview(
+        &mut self,
+        path: &PathBuf,
+        start: f32,
+        gain: f32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.play_primary(path, StreamRole::Preview, gain)?;
+        if let (Some(stream), Some(total)) = (self.primary_stream(), self.total_duration) {
+            let _ = stream.sink.try_seek(total.mul_f32(start));
+        }
+        Ok(())
+    }
+
+    /// Stops the main track (or preview); sound effects keep playing
+    pub fn stop(&mut self) {
+        self.stop_streams(|stream| stream.role.is_primary());
+        self.cancel_next();
+        self.finish_true_peak();
+        *self.is_playing.lock().unwrap() = false;
+    }
+
+    /// Holds the primary stream where it is; side streams carry on
+    pub fn pause(&mut self) {
+        if let Some(stream) = self.primary_stream() {
+            stream.sink.pause();
+            *self.is_playing.lock().unwrap() = false;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let Some(stream) = self.primary_stream() {
+            stream.sink.play();
+            *self.is_playing.lock().unwrap() = true;
+        }
+    }
+
+    /// A track is loaded and held by `pause`
+    pub fn is_paused(&self) -> bool {
+        self.primary_stream()
+            .is_some_and(|stream| stream.sink.is_paused() && !stream.sink.empty())
+    }
+
+    pub fn get_total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    /// Latest `count` frames mixed down to mono, newest first
+    pub fn get_audio_samples(&self, count: usize) -> Vec<f32> {
+        let channels = self.channels.max(1) as usize;
+        let mut capture = self.audio_buffer.lock().unwrap();
+        capture.mark_read();
+        let buffer = &capture.samples;
+        let frames = buffer.len() / channels;
+        (frames.saturating_sub(count)..frames)
+            .rev()
+            .map(|frame| {
+                let start = frame * channels;
+                (start..start + channels).map(|i| buffer[i]).sum::<f32>() / channels as f32
+            })
+            .collect()
+    }
+
+    /// Latest `count` frames of the left and right channels, newest first
+    /// (mono sources give the same samples twice)
+    pub(crate) fn get_channel_samples(&self, count: usize) -> [Vec<f32>; 2] {
+        let channels = self.channels.max(1) as usize;
+        let right = if channels > 1 { 1 } else { 0 };
+        let mut capture = self.audio_buffer.lock().unwrap();
+        capture.mark_read();
+        let buffer = &capture.samples;
+        let frames = buffer.len() / channels;
+        let range = frames.saturating_sub(count)..frames;
+        [
+            range.clone().rev().map(|f| buffer[f * channels]).collect(),
+            range.rev().map(|f| buffer[f * channels + right]).collect(),
+        ]
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Turns sample capture for the analyzer on or off, also for the track
+    /// already playing
+    pub(crate) fn set_capture_enabled(&self, enabled: bool) {
+        self.capture_enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.audio_buffer.lock().unwrap().clear();
+        }
+    }
+
+    pub(crate) fn get_buffer_latency(&self) -> Option<Duration> {
+        self.output.buffer_latency()
+    }
+
+    /// Last and peak output callback duration, with the time it had available
+    pub(crate) fn callback_timing(&self) -> (Duration, Duration, Duration) {
+        self.output.timing.take()
+    }
+
+    /// Output callbacks run since the output was opened
+    pub(crate) fn callback_count(&self) -> u64 {
+        self.output.timing.calls.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn capture_stats(&self) -> CaptureStats {
+        self.audio_buffer.lock().unwrap().stats()
+    }
+
+    /// True when samples reach the device untouched: same rate and channel
+    /// count as the source (no resampling or remixing), unity gain, and a
+    /// device at least as wide as the file's samples (no requantization)
+    pub(crate) fn is_bit_perfect(&self) -> bool {
+        self.bit_perfect_depth().is_some()
+    }
+
+    /// Bits per sample carried untouched to the device, see `is_bit_perfect`
+    pub(crate) fn bit_perfect_depth(&self) -> Option<u8> {
+        let bits = self
+            .source_bits
+            .filter(|&bits| bits <= self.output.bits())?;
+        let untouched = self
+            .primary_stream()
+            .is_some_and(|stream| stream.gain >= 1.0)
+            && self.output.sample_rate == self.sample_rate
+            && self.output.channels == self.source_channels
+            && self.stream_volume() >= 1.0
+            && self.eq_preset() == 0
+            && !self.is_ducked()
+            && self.band_solo().is_none();
+        untouched.then_some(bits)
+    }
+
+    /// Short description of the current signal path for the info panel
+    pub(crate) fn technical_info(&self) -> String {
+        let rate = if self.output.sample_rate == self.sample_rate {
+            format!("{} Hz", self.sample_rate)
+        } else {
+            format!("{} → {} Hz", self.sample_rate, self.output.sample_rate)
+        };
+        let layout = if self.source_channels == self.channels {
+            Speaker::layout_name(self.channels)
+        } else {
+            format!("{} → stereo", Speaker::layout_name(self.source_channels))
+        };
+        let mut info = format!(
+            "{} | {} | {} | {}",
+            rate, layout, self.output.sample_format, self.output.device_name
+        );
+        if let Some(dither) = self.output.active_dither() {
+            info.push_str(&format!(" | Dither: {}", dither));
+        }
+        if let Some(tap) = &self.tap {
+            info.push_str(&format!(
+                " | Tap: {} ({} {} Hz {} ch)",
+                tap.path.display(),
+                tap.format.label(),
+                self.output.sample_rate,
+                self.output.channels
+            ));
+        }
+        if let Some(broadcast) = &self.broadcast {
+            info.push_str(&format!(
+                " | Icecast: {} ({} {} kbps)",
+                broadcast.target.label(),
+                broadcast.codec.label(),
+                broadcast.bitrate
+            ));
+        }
+        info
+    }
+
+    pub(crate) fn take_output_error(&self) -> Option<String> {
+        self.output.take_error()
+    }
+}
+
+/// Describes the player to PipeWire and PulseAudio. Their ALSA plugins take
+/// the client properties from the environment, so pavucontrol and helvum
+/// show its name and icon, and role-based routing (a sink for music, ducking
+/// during calls) applies. Must run before the output is opened and before
+/// any thread starts; variables the user set are left alone.
+pub(crate) fn set_stream_properties(role: &str) {
+    // PipeWire's policy spells roles capitalized ("Music"), PulseAudio lowercase
+    let mut pipewire_role = role.to_lowercase();
+    if let Some(first) = pipewire_role.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+    let properties = [
+        ("application.name", "Rust Player"),
+        ("application.id", "rust-player"),
+        ("application.icon_name", "audio-x-generic"),
+        ("media.name", "Rust Player"),
+    ];
+    let list = |role: &str| {
+        properties
+            .iter()
+            .chain([("media.role", role)].iter())
+            .map(|(key, value)| format!("{}=\"{}\"", key, value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let variables = [
+        ("PIPEWIRE_ALSA", format!("{{ {} }}", list(&pipewire_role))),
+        ("PULSE_PROP", list(&role.to_lowercase())),
+    ];
+    for (variable, value) in variables {
+        if std::env::var_os(variable).is_none() {
+            // SAFETY: called at startup, before any other thread exists
+            unsafe { std::env::set_var(variable, value) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fade_curves_run_from_silence_to_unity() {
+        for curve in [FadeCurve::Linear, FadeCurve::EqualPower, FadeCurve::SCurve] {
+            assert_eq!(curve.gain(0.0), 0.0);
+            assert!((curve.gain(1.0) - 1.0).abs() < 1e-6);
+            assert_eq!(curve.gain(-0.5), curve.gain(0.0));
+            assert_eq!(curve.gain(1.5), curve.gain(1.0));
+            let steps: Vec<f32> = (0..=10).map(|i| curve.gain(i as f32 / 10.0)).collect();
+            assert!(steps.windows(2).all(|pair| pair[0] <= pair[1]));
+        }
+    }
+
+    #[test]
+    fn equal_power_keeps_the_sum_of_powers() {
+        for i in 0..=10 {
+            let progress = i as f32 / 10.0;
+            let fade_in = FadeCurve::EqualPower.gain(progress);
+            let fade_out = FadeCurve::EqualPower.gain(1.0 - progress);
+            assert!((fade_in * fade_in + fade_out * fade_out - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn fade_curve_names_and_cycle() {
+        assert_eq!(FadeCurve::parse("linear"), Some(FadeCurve::Linear));
+        assert_eq!(FadeCurve::parse("equal-power"), Some(FadeCurve::EqualPower));
+        assert_eq!(FadeCurve::parse("s-curve"), Some(FadeCurve::SCurve));
+        assert_eq!(FadeCurve::parse("log"), None);
+        let mut curve = FadeCurve::Linear;
+        for _ in 0..3 {
+            curve = curve.next();
+        }
+        assert_eq!(curve, FadeCurve::Linear);
+    }
+
+    #[test]
+    fn mp3_header_of_an_mpeg1_frame() {
+        // 128 kbps, 44.1 kHz, joint stereo
+        let header = Mp3FrameHeader::parse([0xFF, 0xFB, 0x90, 0x40]).unwrap();
+        assert_eq!(header.sample_rate, 44100);
+        assert_eq!(header.frame_len, 417);
+        assert_eq!(header.samples_per_frame(), 1152);
+        assert_eq!(header.xing_offset(), 36);
+
+        let padded = Mp3FrameHeader::parse([0xFF, 0xFB, 0x92, 0xC0]).unwrap();
+        assert_eq!(padded.frame_len, 418);
+        assert_eq!(padded.xing_offset(), 21);
+    }
+
+    #[test]
+    fn mp3_header_of_an_mpeg2_frame() {
+        // 80 kbps, 22.05 kHz, mono
+        let header = Mp3FrameHeader::parse([0xFF, 0xF3, 0x90, 0xC0]).unwrap();
+        assert_eq!(header.sample_rate, 22050);
+        assert_eq!(header.frame_len, 261);
+        assert_eq!(header.samples_per_frame(), 576);
+        assert_eq!(header.xing_offset(), 13);
+    }
+
+    #[test]
+    fn mp3_header_rejects_other_data() {
+        // No sync word
+        assert!(Mp3FrameHeader::parse([0x49, 0x44, 0x33, 0x04]).is_none());
+        // Layer II
+        assert!(Mp3FrameHeader::parse([0xFF, 0xFD, 0x90, 0x40]).is_none());
+        // Free and bad bitrate
+        assert!(Mp3FrameHeader::parse([0xFF, 0xFB, 0x00, 0x40]).is_none());
+        assert!(Mp3FrameHeader::parse([0xFF, 0xFB, 0xF0, 0x40]).is_none());
+        // Reserved sample rate and MPEG version
+        assert!(Mp3FrameHeader::parse([0xFF, 0xFB, 0x9C, 0x40]).is_none());
+        assert!(Mp3FrameHeader::parse([0xFF, 0xEB, 0x90, 0x40]).is_none());
+    }
+
+    fn stereo(samples: &[f32]) -> Box<dyn Source<Item = f32> + Send> {
+        Box::new(rodio::buffer::SamplesBuffer::new(
+            2,
+            44100,
+            samples.to_vec(),
+        ))
+    }
+
+    fn gapless(current: &[f32]) -> (Gapless, NextSource) {
+        let next = NextSource::default();
+        let gapless = Gapless::new(
+            stereo(current),
+            next.clone(),
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+        );
+        (gapless, next)
+    }
+
+    fn follower(samples: &[f32]) -> Follower {
+        Follower {
+            source: stereo(samples),
+            start: 0,
+            overlap: 0,
+            curve: FadeCurve::Linear,
+        }
+    }
+
+    #[test]
+    fn gapless_goes_on_to_the_next_track() {
+        let (gapless, next) = gapless(&[1.0, 2.0]);
+        *next.lock().unwrap() = Some(follower(&[3.0, 4.0]));
+        assert_eq!(gapless.collect::<Vec<_>>(), [1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn gapless_waits_out_a_busy_slot() {
+        let (mut gapless, next) = gapless(&[1.0, 2.0]);
+        let mut slot = next.lock().unwrap();
+        *slot = Some(follower(&[3.0, 4.0]));
+        let before: Vec<f32> = gapless.by_ref().take(5).collect();
+        assert_eq!(before, [1.0, 2.0, 0.0, 0.0, 0.0]);
+        drop(slot);
+        // Silence up to the end of the frame, then the next track in place
+        assert_eq!(gapless.collect::<Vec<_>>(), [0.0, 3.0, 4.0]);
+    }
+
+    /// Last sample of the first 30 ms through a `Gain` node, from unity
+    /// towards the level and duck factor given
+    fn gain_after_30ms(level: f32, duck: f32) -> f32 {
+        let unity = || Arc::new(AtomicU32::new(1f32.to_bits()));
+        let (level_target, duck_target) = (unity(), unity());
+        let gain = Gain::new(stereo(&[1.0; 2 * 4410]), level_target.clone())
+            .ducked(duck_target.clone(), &DuckSettings::default());
+        level_target.store(level.to_bits(), Ordering::Relaxed);
+        duck_target.store(duck.to_bits(), Ordering::Relaxed);
+        gain.take(2 * 1323).last().unwrap()
+    }
+
+    #[test]
+    fn gain_follows_the_volume_quickly() {
+        assert!((gain_after_30ms(0.5, 1.0) - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn gain_ducks_at_the_ducking_pace() {
+        // 30 ms into a 300 ms attack
+        assert!((gain_after_30ms(1.0, 0.0) - 0.9).abs() < 1e-2);
+    }
+}
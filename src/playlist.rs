@@ -0,0 +1,308 @@
+//! Playlists: M3U files found in the config folder and the music root,
+//! and the playlists tab listing them.
+
+use ratatui::widgets::ListState;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::app::{RadioEdit, config_dir};
+use crate::history::DailyMix;
+
+/// M3U playlist: one path per line, `#` lines are comments or extended info.
+/// Relative paths are resolved against the playlist's folder. A
+/// `#RADIOEDIT:INTRO,OUTRO` line overrides `--radio-edit` while the playlist
+/// is open (`#RADIOEDIT:0,0` turns it off).
+pub(crate) struct Playlist {
+    pub(crate) path: PathBuf,
+    pub(crate) tracks: Vec<PathBuf>,
+    pub(crate) radio_edit: Option<RadioEdit>,
+}
+
+impl Playlist {
+    const EXTENSIONS: [&str; 2] = ["m3u", "m3u8"];
+
+    pub(crate) fn load(path: &Path) -> io::Result<Self> {
+        let base = path.parent().unwrap_or(Path::new("."));
+        let content = fs::read_to_string(path)?;
+        let lines = content.lines().map(str::trim);
+        let radio_edit = lines
+            .clone()
+            .filter_map(|line| line.strip_prefix("#RADIOEDIT:"))
+            .find_map(RadioEdit::parse);
+        let tracks = lines
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| base.join(line))
+            .collect();
+        Ok(Self {
+            path: path.to_path_buf(),
+            tracks,
+            radio_edit,
+        })
+    }
+
+    pub(crate) fn save(&self) -> io::Result<()> {
+        let mut content = String::from("#EXTM3U\n");
+        if let Some(edit) = self.radio_edit {
+            content += &format!(
+                "#RADIOEDIT:{},{}\n",
+                edit.intro.as_secs(),
+                edit.outro.as_secs()
+            );
+        }
+        for track in &self.tracks {
+            content += &format!("{}\n", track.display());
+        }
+        fs::write(&self.path, content)
+    }
+
+    pub(crate) fn name(path: &Path) -> String {
+        path.file_stem()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Where new playlists are created: `<config>/playlists`
+    pub(crate) fn default_dir() -> Option<PathBuf> {
+        Some(config_dir()?.join("playlists"))
+    }
+
+    /// Saved playlists: the daily mix, the config folder, then the music root
+    pub(crate) fn find_all(music_root: &Path) -> Vec<PathBuf> {
+        let mut found: Vec<PathBuf> = DailyMix::path()
+            .filter(|p| p.is_file())
+            .into_iter()
+            .collect();
+        for dir in Self::default_dir()
+            .into_iter()
+            .chain(std::iter::once(music_root.to_path_buf()))
+        {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut playlists: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| {
+                    path.extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| Self::EXTENSIONS.contains(&e.to_lowercase().as_str()))
+                })
+                .collect();
+            playlists.sort();
+            found.extend(playlists);
+        }
+        found
+    }
+}
+
+/// State of the playlists tab
+#[derive(Default)]
+pub(crate) struct Playlists {
+    /// Saved playlists, see `Playlist::find_all`
+    pub(crate) found: Vec<PathBuf>,
+    pub(crate) state: ListState,
+    /// Playlist whose tracks are listed in the tab
+    pub(crate) open: Option<Playlist>,
+    pub(crate) track_state: ListState,
+    /// Where [P] adds tracks: the last playlist opened or created
+    pub(crate) target: Option<PathBuf>,
+}
+
+impl Playlists {
+    /// Looks for the playlists again, keeping the cursor in the list
+    pub(crate) fn refresh(&mut self, music_root: &Path) {
+        self.found = Playlist::find_all(music_root);
+        let selected = self.state.selected().unwrap_or(0);
+        self.state.select(if self.found.is_empty() {
+            None
+        } else {
+            Some(selected.min(self.found.len() - 1))
+        });
+    }
+
+    /// Playlist under the cursor (or the open one)
+    pub(crate) fn highlighted(&self) -> Option<PathBuf> {
+        match &self.open {
+            Some(playlist) => Some(playlist.path.clone()),
+            None => self
+                .state
+                .selected()
+                .and_then(|i| self.found.get(i))
+                .cloned(),
+        }
+    }
+
+    /// Track under the cursor in the open playlist
+    pub(crate) fn highlighted_track(&self) -> Option<PathBuf> {
+        let playlist = self.open.as_ref()?;
+        self.track_state
+            .selected()
+            .and_then(|i| playlist.tracks.get(i))
+            .cloned()
+    }
+
+    pub(crate) fn move_cursor(&mut self, down: bool) {
+        let (state, len) = match &self.open {
+            Some(playlist) => (&mut self.track_state, playlist.tracks.len()),
+            None => (&mut self.state, self.found.len()),
+        };
+        if len == 0 {
+            return;
+        }
+        let i = state.selected().unwrap_or(0);
+        state.select(Some(if down {
+            (i + 1) % len
+        } else {
+            (i + len - 1) % len
+        }));
+    }
+
+    /// Lists the playlist's tracks, and makes it the one [P] adds to
+    pub(crate) fn open(&mut self, playlist: Playlist) {
+        self.track_state
+            .select((!playlist.tracks.is_empty()).then_some(0));
+        self.target = Some(playlist.path.clone());
+        self.open = Some(playlist);
+    }
+
+    /// Puts the cursor on a playlist just created, and makes it the one [P]
+    /// adds to
+    pub(crate) fn created(&mut self, path: PathBuf) {
+        self.state
+            .select(self.found.iter().position(|p| *p == path));
+        self.target = Some(path);
+    }
+
+    pub(crate) fn renamed(&mut self, path: &Path, new_path: &Path) {
+        if let Some(playlist) = &mut self.open
+            && playlist.path == path
+        {
+            playlist.path = new_path.to_path_buf();
+        }
+        if self.target.as_deref() == Some(path) {
+            self.target = Some(new_path.to_path_buf());
+        }
+    }
+
+    pub(crate) fn deleted(&mut self, path: &Path) {
+        self.open = None;
+        if self.target.as_deref() == Some(path) {
+            self.target = None;
+        }
+    }
+
+    /// Shows the playlist again if it is the open one, after tracks were
+    /// added to it
+    pub(crate) fn changed(&mut self, playlist: Playlist) {
+        if self
+            .open
+            .as_ref()
+            .is_some_and(|open| open.path == playlist.path)
+        {
+            self.open = Some(playlist);
+        }
+    }
+
+    /// Moves the highlighted track of the open playlist one place, saving
+    /// right away
+    pub(crate) fn move_track(&mut self, down: bool) -> io::Result<()> {
+        let Some(playlist) = &mut self.open else {
+            return Ok(());
+        };
+        let Some(i) = self.track_state.selected() else {
+            return Ok(());
+        };
+        let target = if down { i + 1 } else { i.wrapping_sub(1) };
+        if target >= playlist.tracks.len() {
+            return Ok(());
+        }
+        playlist.tracks.swap(i, target);
+        self.track_state.select(Some(target));
+        playlist.save()
+    }
+
+    /// Takes the highlighted track out of the open playlist, saving right away
+    pub(crate) fn remove_track(&mut self) -> io::Result<()> {
+        let Some(playlist) = &mut self.open else {
+            return Ok(());
+        };
+        let Some(i) = self
+            .track_state
+            .selected()
+            .filter(|&i| i < playlist.tracks.len())
+        else {
+            return Ok(());
+        };
+        playlist.tracks.remove(i);
+        let len = playlist.tracks.len();
+        self.track_state.select((len > 0).then(|| i.min(len - 1)));
+        playlist.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn scratch(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("playlist-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn playlists_keep_tracks_and_radio_edit() {
+        let path = scratch("viaggio.m3u");
+        fs::write(
+            &path,
+            "#EXTM3U\n#RADIOEDIT:5,10\nuno.mp3\n\n# commento\n/musica/due.flac\n",
+        )
+        .unwrap();
+        let playlist = Playlist::load(&path).unwrap();
+        let base = path.parent().unwrap();
+        assert_eq!(
+            playlist.tracks,
+            [base.join("uno.mp3"), PathBuf::from("/musica/due.flac")]
+        );
+        assert_eq!(
+            playlist.radio_edit,
+            Some(RadioEdit {
+                intro: Duration::from_secs(5),
+                outro: Duration::from_secs(10),
+            })
+        );
+
+        playlist.save().unwrap();
+        let again = Playlist::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(again.tracks, playlist.tracks);
+        assert_eq!(again.radio_edit, playlist.radio_edit);
+        assert_eq!(
+            Playlist::name(&path),
+            format!("playlist-{}-viaggio", std::process::id())
+        );
+    }
+
+    #[test]
+    fn removing_the_last_track_moves_the_cursor_up() {
+        let path = scratch("corta.m3u");
+        let mut playlists = Playlists::default();
+        playlists.open(Playlist {
+            path: path.clone(),
+            tracks: vec![PathBuf::from("/a.mp3"), PathBuf::from("/b.mp3")],
+            radio_edit: None,
+        });
+        assert_eq!(playlists.target.as_ref(), Some(&path));
+
+        playlists.move_cursor(false);
+        assert_eq!(playlists.highlighted_track(), Some(PathBuf::from("/b.mp3")));
+        playlists.remove_track().unwrap();
+        assert_eq!(playlists.highlighted_track(), Some(PathBuf::from("/a.mp3")));
+        playlists.remove_track().unwrap();
+        assert_eq!(playlists.track_state.selected(), None);
+        fs::remove_file(&path).unwrap();
+
+        playlists.deleted(&path);
+        assert!(playlists.open.is_none() && playlists.target.is_none());
+    }
+}
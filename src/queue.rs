@@ -0,0 +1,209 @@
+//! Tracks lined up to play next, from any folder. Playback takes from the
+//! front of the queue before walking the browser's list.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Default)]
+pub(crate) struct Queue {
+    tracks: Vec<PathBuf>,
+    /// Tracks taken in this round, for going round again with repeat all
+    /// or `--end-of-queue repeat`
+    played: Vec<PathBuf>,
+    /// Tracks guests asked for, oldest first, waiting to be let in
+    requests: Vec<PathBuf>,
+}
+
+impl Queue {
+    pub(crate) fn tracks(&self) -> &[PathBuf] {
+        &self.tracks
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, track: PathBuf) {
+        self.tracks.push(track);
+    }
+
+    /// Takes the next track to play
+    pub(crate) fn pop(&mut self) -> Option<PathBuf> {
+        let track = (!self.tracks.is_empty()).then(|| self.tracks.remove(0))?;
+        self.played.push(track.clone());
+        Some(track)
+    }
+
+    /// Whether `track` is the last one the queue gave
+    pub(crate) fn is_current(&self, track: &Path) -> bool {
+        self.played.last().is_some_and(|played| played == track)
+    }
+
+    /// The track `pop` would give, or with `wrap` the first of the round
+    /// once the queue ran out
+    pub(crate) fn upcoming(&self, wrap: bool) -> Option<&PathBuf> {
+        self.tracks
+            .first()
+            .or_else(|| wrap.then(|| self.played.first()).flatten())
+    }
+
+    /// Lines up the tracks of the round again once the queue ran out;
+    /// false when there is nothing to go round
+    pub(crate) fn rewind(&mut self) -> bool {
+        if !self.tracks.is_empty() || self.played.is_empty() {
+            return false;
+        }
+        self.tracks = std::mem::take(&mut self.played);
+        true
+    }
+
+    /// Forgets the tracks of the round, so a later one starts afresh
+    pub(crate) fn end_round(&mut self) {
+        self.played.clear();
+    }
+
+    pub(crate) fn remove(&mut self, index: usize) -> Option<PathBuf> {
+        (index < self.tracks.len()).then(|| self.tracks.remove(index))
+    }
+
+    /// Swaps the track at `index` with its neighbour and returns where it
+    /// went, or `None` at either end
+    pub(crate) fn move_track(&mut self, index: usize, down: bool) -> Option<usize> {
+        let target = if down {
+            index + 1
+        } else {
+            index.checked_sub(1)?
+        };
+        if target >= self.tracks.len() {
+            return None;
+        }
+        self.tracks.swap(index, target);
+        Some(target)
+    }
+
+    pub(crate) fn requests(&self) -> &[PathBuf] {
+        &self.requests
+    }
+
+    /// Holds a guest's track until `approve` or `reject`; one already
+    /// waiting isn't listed twice. Guests come through the control socket.
+    #[cfg(unix)]
+    pub(crate) fn request(&mut self, track: PathBuf) {
+        if !self.requests.contains(&track) {
+            self.requests.push(track);
+        }
+    }
+
+    /// Moves the oldest request to the end of the queue
+    pub(crate) fn approve(&mut self) -> Option<PathBuf> {
+        let track = self.reject()?;
+        self.tracks.push(track.clone());
+        Some(track)
+    }
+
+    /// Drops the oldest request
+    pub(crate) fn reject(&mut self) -> Option<PathBuf> {
+        (!self.requests.is_empty()).then(|| self.requests.remove(0))
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.tracks.clear();
+        self.played.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(tracks: &[&str]) -> Queue {
+        let mut queue = Queue::default();
+        for track in tracks {
+            queue.push(PathBuf::from(track));
+        }
+        queue
+    }
+
+    #[test]
+    fn pop_takes_from_the_front_and_remembers_the_track() {
+        let mut queue = queue(&["a", "b"]);
+        assert_eq!(queue.pop(), Some(PathBuf::from("a")));
+        assert!(queue.is_current(Path::new("a")));
+        assert_eq!(queue.tracks(), [PathBuf::from("b")]);
+        assert_eq!(queue.pop(), Some(PathBuf::from("b")));
+        assert!(queue.is_current(Path::new("b")));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn upcoming_wraps_only_when_asked() {
+        let mut queue = queue(&["a", "b"]);
+        assert_eq!(queue.upcoming(false), Some(&PathBuf::from("a")));
+        queue.pop();
+        queue.pop();
+        assert_eq!(queue.upcoming(false), None);
+        assert_eq!(queue.upcoming(true), Some(&PathBuf::from("a")));
+    }
+
+    #[test]
+    fn rewind_lines_up_the_round_again() {
+        let mut queue = queue(&["a", "b"]);
+        queue.pop();
+        assert!(!queue.rewind(), "tracks still waiting");
+        queue.pop();
+        assert!(queue.rewind());
+        assert_eq!(queue.tracks(), [PathBuf::from("a"), PathBuf::from("b")]);
+        assert!(!queue.is_current(Path::new("b")));
+    }
+
+    #[test]
+    fn end_round_leaves_nothing_to_rewind() {
+        let mut queue = queue(&["a"]);
+        queue.pop();
+        queue.end_round();
+        assert!(!queue.rewind());
+        assert_eq!(queue.upcoming(true), None);
+    }
+
+    #[test]
+    fn move_track_stops_at_either_end() {
+        let mut queue = queue(&["a", "b", "c"]);
+        assert_eq!(queue.move_track(0, false), None);
+        assert_eq!(queue.move_track(2, true), None);
+        assert_eq!(queue.move_track(0, true), Some(1));
+        assert_eq!(
+            queue.tracks(),
+            [PathBuf::from("b"), PathBuf::from("a"), PathBuf::from("c")]
+        );
+    }
+
+    #[test]
+    fn remove_and_clear() {
+        let mut queue = queue(&["a", "b"]);
+        assert_eq!(queue.remove(5), None);
+        assert_eq!(queue.remove(0), Some(PathBuf::from("a")));
+        assert_eq!(queue.len(), 1);
+        queue.pop();
+        queue.clear();
+        assert!(queue.is_empty());
+        assert!(!queue.rewind());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn requests_wait_for_approval_in_order() {
+        let mut queue = queue(&["a"]);
+        queue.request(PathBuf::from("b"));
+        queue.request(PathBuf::from("c"));
+        queue.request(PathBuf::from("b"));
+        assert_eq!(queue.requests(), [PathBuf::from("b"), PathBuf::from("c")]);
+        assert_eq!(queue.approve(), Some(PathBuf::from("b")));
+        assert_eq!(queue.reject(), Some(PathBuf::from("c")));
+        assert_eq!(queue.approve(), None);
+        assert_eq!(queue.tracks(), [PathBuf::from("a"), PathBuf::from("b")]);
+    }
+}
@@ -0,0 +1,149 @@
+//! Session tabs: folders open side by side, each with its own queue and
+//! playback. The tab in front plays; the others wait frozen where they
+//! were left.
+
+use std::{path::PathBuf, time::Duration};
+
+use crate::app::{AlbumShuffle, Practice, RepeatMode};
+use crate::queue::Queue;
+
+/// Queue and playback of a session tab while it isn't in front: frozen at
+/// the position it was left at, and resumed from there when switched back to
+pub(crate) struct SessionTab {
+    pub(crate) name: String,
+    pub(crate) dir: PathBuf,
+    pub(crate) highlighted: Option<usize>,
+    pub(crate) track: Option<PathBuf>,
+    pub(crate) position: Duration,
+    pub(crate) playing: bool,
+    pub(crate) repeat: RepeatMode,
+    pub(crate) shuffle: bool,
+    pub(crate) album_shuffle: Option<AlbumShuffle>,
+    pub(crate) ab_loop: Option<(Duration, Option<Duration>)>,
+    pub(crate) practice: Option<Practice>,
+    pub(crate) queue: Queue,
+    pub(crate) play_history: Vec<PathBuf>,
+}
+
+impl SessionTab {
+    pub(crate) fn new(name: &str, dir: PathBuf) -> Self {
+        Self {
+            name: name.to_string(),
+            dir,
+            highlighted: None,
+            track: None,
+            position: Duration::ZERO,
+            playing: false,
+            repeat: RepeatMode::Off,
+            shuffle: false,
+            album_shuffle: None,
+            ab_loop: None,
+            practice: None,
+            queue: Queue::default(),
+            play_history: Vec::new(),
+        }
+    }
+}
+
+/// The open session tabs and the one in front. The entry of the active tab
+/// is only written when switching away from it.
+pub(crate) struct Sessions {
+    tabs: Vec<SessionTab>,
+    active: usize,
+}
+
+impl Sessions {
+    /// The first tab, on the folder the player was started in
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self {
+            tabs: vec![SessionTab::new("principale", dir)],
+            active: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub(crate) fn active(&self) -> usize {
+        self.active
+    }
+
+    pub(crate) fn active_name(&self) -> &str {
+        &self.tabs[self.active].name
+    }
+
+    pub(crate) fn position(&self, name: &str) -> Option<usize> {
+        self.tabs.iter().position(|tab| tab.name == name)
+    }
+
+    /// Adds a tab behind the others, returning its index
+    pub(crate) fn push(&mut self, tab: SessionTab) -> usize {
+        self.tabs.push(tab);
+        self.tabs.len() - 1
+    }
+
+    pub(crate) fn replace(&mut self, index: usize, tab: SessionTab) {
+        self.tabs[index] = tab;
+    }
+
+    /// Stores the active tab as `frozen` and takes out the one at `index`
+    /// to bring to the front; its entry keeps only the name
+    pub(crate) fn switch(&mut self, frozen: SessionTab, index: usize) -> SessionTab {
+        self.tabs[self.active] = frozen;
+        self.active = index;
+        self.take_active()
+    }
+
+    /// Drops the active tab and takes out the next one to bring to the
+    /// front. There must be another tab.
+    pub(crate) fn close_active(&mut self) -> SessionTab {
+        self.tabs.remove(self.active);
+        self.active %= self.tabs.len();
+        self.take_active()
+    }
+
+    fn take_active(&mut self) -> SessionTab {
+        let tab = &mut self.tabs[self.active];
+        let placeholder = SessionTab::new(&tab.name, PathBuf::new());
+        std::mem::replace(tab, placeholder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switching_keeps_the_frozen_tab_and_the_name_of_the_active_one() {
+        let mut sessions = Sessions::new(PathBuf::from("/musica"));
+        let index = sessions.push(SessionTab::new("lavoro", PathBuf::from("/lavoro")));
+        let mut frozen = SessionTab::new("principale", PathBuf::from("/musica/jazz"));
+        frozen.position = Duration::from_secs(42);
+
+        let tab = sessions.switch(frozen, index);
+        assert_eq!(tab.dir, PathBuf::from("/lavoro"));
+        assert_eq!(sessions.active(), 1);
+        assert_eq!(sessions.active_name(), "lavoro");
+
+        let tab = sessions.switch(SessionTab::new("lavoro", PathBuf::new()), 0);
+        assert_eq!(tab.dir, PathBuf::from("/musica/jazz"));
+        assert_eq!(tab.position, Duration::from_secs(42));
+    }
+
+    #[test]
+    fn closing_the_last_tab_moves_to_the_first() {
+        let mut sessions = Sessions::new(PathBuf::from("/musica"));
+        let index = sessions.push(SessionTab::new("lavoro", PathBuf::from("/lavoro")));
+        sessions.switch(
+            SessionTab::new("principale", PathBuf::from("/musica")),
+            index,
+        );
+
+        let tab = sessions.close_active();
+        assert_eq!(tab.dir, PathBuf::from("/musica"));
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions.active(), 0);
+        assert_eq!(sessions.position("lavoro"), None);
+    }
+}
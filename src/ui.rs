@@ -0,0 +1,2111 @@
+//! Drawing the terminal interface.
+
+use ratatui::{
+    Frame,
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    symbols::border,
+    text::{Line, Span},
+    widgets::{
+        Block, Borders, Clear, Gauge, LineGauge, List, ListItem, ListState, Paragraph, Wrap,
+    },
+};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::app::{App, RepeatMode, SFX_SLOTS, SubtunePicker, Tab};
+use crate::broadcast::BroadcastStatus;
+use crate::browser::ArchiveKind;
+use crate::fft::{FrequencyScale, GapReport, TiltOverlay, Waveform};
+use crate::history::ListeningHeatmap;
+use crate::keymap::LEADER_BINDINGS;
+use crate::player::{EQ_PRESETS, TruePeakReading};
+use crate::playlist::Playlist;
+
+/// A track list handed to a phone: an M3U with `#EXTINF` names and paths
+/// relative to the list's folder, as a QR code. As many tracks as fit a
+/// code the terminal can show are included.
+pub(crate) struct QrView {
+    title: String,
+    /// Modules per side, quiet zone included
+    width: usize,
+    /// Row by row, true for dark
+    dark: Vec<bool>,
+}
+
+impl QrView {
+    /// Light modules around the code; scanners manage with less than the
+    /// standard four, and the terminal has little room
+    const QUIET_ZONE: usize = 2;
+    /// Tracks considered; more never fit a code anyway
+    pub(crate) const MAX_TRACKS: usize = 200;
+
+    /// `tracks` as (path, display name), the first of `total`; `max_width`
+    /// and `max_height` in terminal cells, two modules per row
+    pub(crate) fn new(
+        name: &str,
+        base: &Path,
+        tracks: &[(PathBuf, String)],
+        total: usize,
+        max_width: usize,
+        max_height: usize,
+    ) -> Result<Self, String> {
+        let fits = max_width.min(max_height * 2);
+        let mut count = tracks.len().min(Self::MAX_TRACKS);
+        while count > 0 {
+            let mut m3u = String::from("#EXTM3U\n");
+            for (path, title) in &tracks[..count] {
+                let path = path.strip_prefix(base).unwrap_or(path);
+                m3u += &format!("#EXTINF:-1,{}\n{}\n", title, path.display());
+            }
+            if let Ok(code) = qrcode::QrCode::with_error_correction_level(&m3u, qrcode::EcLevel::L)
+                && code.width() + 2 * Self::QUIET_ZONE <= fits
+            {
+                return Ok(Self::from_code(name, count, total, &code));
+            }
+            count = if count > 20 { count * 3 / 4 } else { count - 1 };
+        }
+        Err("il terminale è troppo piccolo per il codice QR".to_string())
+    }
+
+    fn from_code(name: &str, count: usize, total: usize, code: &qrcode::QrCode) -> Self {
+        let inner = code.width();
+        let width = inner + 2 * Self::QUIET_ZONE;
+        let colors = code.to_colors();
+        let dark = (0..width * width)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                let inside = |v: usize| (Self::QUIET_ZONE..Self::QUIET_ZONE + inner).contains(&v);
+                inside(x)
+                    && inside(y)
+                    && colors[(y - Self::QUIET_ZONE) * inner + x - Self::QUIET_ZONE]
+                        == qrcode::Color::Dark
+            })
+            .collect();
+        let title = if count < total {
+            format!(" 📱 {}: {} tracce di {} ", name, count, total)
+        } else {
+            format!(" 📱 {}: {} tracce ", name, count)
+        };
+        Self { title, width, dark }
+    }
+
+    /// Half blocks, light modules drawn in white so the code reads the same
+    /// on dark and light terminals
+    fn lines(&self) -> Vec<String> {
+        (0..self.width)
+            .step_by(2)
+            .map(|y| {
+                (0..self.width)
+                    .map(|x| {
+                        let top = !self.dark[y * self.width + x];
+                        let bottom = y + 1 < self.width && !self.dark[(y + 1) * self.width + x];
+                        match (top, bottom) {
+                            (true, true) => '█',
+                            (true, false) => '▀',
+                            (false, true) => '▄',
+                            (false, false) => ' ',
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Rating 0-100 as five stars
+fn rating_stars(rating: u8) -> String {
+    let full = ((rating as usize + 10) / 20).min(5);
+    "★".repeat(full) + &"☆".repeat(5 - full)
+}
+
+/// Color scheme of the whole interface. Panels are drawn with the default
+/// colors and the theme remaps them on the finished frame, so every panel
+/// follows it without knowing about it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum Theme {
+    #[default]
+    Default,
+    /// Bright colors only, dim text made white, for low vision
+    HighContrast,
+    /// Okabe-Ito colors, told apart with deuteranopia and protanopia:
+    /// greens become blues, reds vermillion, so no state is red against green
+    Colorblind,
+}
+
+impl Theme {
+    pub(crate) fn parse(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Theme::Default),
+            "high-contrast" => Some(Theme::HighContrast),
+            "colorblind" | "deuteranopia" | "protanopia" => Some(Theme::Colorblind),
+            _ => None,
+        }
+    }
+
+    /// 256-color palette entries, which nearly every terminal supports
+    fn map(&self, color: Color) -> Color {
+        match (self, color) {
+            (Theme::Default, color) => color,
+            (Theme::HighContrast, Color::DarkGray | Color::Gray) => Color::White,
+            (Theme::HighContrast, Color::Blue) => Color::LightCyan,
+            (Theme::HighContrast, Color::Red) => Color::LightRed,
+            (Theme::HighContrast, Color::Green) => Color::LightGreen,
+            (Theme::HighContrast, Color::Yellow) => Color::LightYellow,
+            (Theme::HighContrast, Color::Magenta) => Color::LightMagenta,
+            (Theme::HighContrast, Color::Cyan) => Color::LightCyan,
+            (Theme::Colorblind, Color::Green) => Color::Indexed(33),
+            (Theme::Colorblind, Color::LightGreen) => Color::Indexed(75),
+            (Theme::Colorblind, Color::Red) => Color::Indexed(202),
+            (Theme::Colorblind, Color::LightRed) => Color::Indexed(208),
+            (Theme::Colorblind, Color::Yellow | Color::LightYellow) => Color::Indexed(227),
+            (Theme::Colorblind, Color::Magenta | Color::LightMagenta) => Color::Indexed(175),
+            (_, color) => color,
+        }
+    }
+
+    fn apply(&self, buffer: &mut Buffer) {
+        if *self == Theme::Default {
+            return;
+        }
+        for cell in buffer.content.iter_mut() {
+            // Highlighted rows: a dark gray bar is too faint to find
+            if *self == Theme::HighContrast && cell.bg == Color::DarkGray {
+                cell.set_bg(Color::White);
+                cell.set_fg(Color::Black);
+                continue;
+            }
+            cell.set_fg(self.map(cell.fg));
+            cell.set_bg(self.map(cell.bg));
+        }
+    }
+}
+
+/// Colors of the spectrum bars, from the bottom of a bar to its top
+#[derive(Clone, Debug)]
+pub(crate) struct SpectrumTheme {
+    pub(crate) low: Color,
+    pub(crate) mid: Color,
+    pub(crate) high: Color,
+    /// Fractions of the panel height where the mid and high colors start
+    pub(crate) mid_threshold: f32,
+    pub(crate) high_threshold: f32,
+    /// Blend smoothly between the colors (only on truecolor terminals)
+    pub(crate) gradient: bool,
+    /// Draw each zone with its own shade character, so the level reads
+    /// without telling the colors apart
+    pub(crate) textured: bool,
+}
+
+impl Default for SpectrumTheme {
+    fn default() -> Self {
+        Self {
+            low: Color::Green,
+            mid: Color::Yellow,
+            high: Color::Red,
+            mid_threshold: 1.0 / 3.0,
+            high_threshold: 2.0 / 3.0,
+            gradient: false,
+            textured: false,
+        }
+    }
+}
+
+impl SpectrumTheme {
+    /// Color of a bar cell at `level` (0.0 bottom - 1.0 top of the panel)
+    fn color_at(&self, level: f32, truecolor: bool) -> Color {
+        if self.gradient
+            && truecolor
+            && let (Some(low), Some(mid), Some(high)) = (
+                Self::rgb(self.low),
+                Self::rgb(self.mid),
+                Self::rgb(self.high),
+            )
+        {
+            return if level < self.mid_threshold {
+                Self::blend(low, mid, level / self.mid_threshold)
+            } else {
+                let span = (self.high_threshold - self.mid_threshold).max(f32::EPSILON);
+                Self::blend(mid, high, (level - self.mid_threshold) / span)
+            };
+        }
+
+        if level > self.high_threshold {
+            self.high
+        } else if level > self.mid_threshold {
+            self.mid
+        } else {
+            self.low
+        }
+    }
+
+    /// Bar character at `level`; paused bars are hatched
+    fn bar_char(&self, level: f32, playing: bool) -> &'static str {
+        match (self.textured, playing) {
+            (false, true) => "█",
+            (false, false) => "▒",
+            (true, false) => "░",
+            (true, true) if level > self.high_threshold => "█",
+            (true, true) if level > self.mid_threshold => "▓",
+            (true, true) => "▒",
+        }
+    }
+
+    fn blend(from: (u8, u8, u8), to: (u8, u8, u8), t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color::Rgb(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+    }
+
+    /// RGB value of a color, using the xterm palette for named colors
+    fn rgb(color: Color) -> Option<(u8, u8, u8)> {
+        match color {
+            Color::Rgb(r, g, b) => Some((r, g, b)),
+            Color::Black => Some((0, 0, 0)),
+            Color::Red => Some((205, 0, 0)),
+            Color::Green => Some((0, 205, 0)),
+            Color::Yellow => Some((205, 205, 0)),
+            Color::Blue => Some((0, 0, 238)),
+            Color::Magenta => Some((205, 0, 205)),
+            Color::Cyan => Some((0, 205, 205)),
+            Color::Gray => Some((229, 229, 229)),
+            Color::DarkGray => Some((127, 127, 127)),
+            Color::LightRed => Some((255, 0, 0)),
+            Color::LightGreen => Some((0, 255, 0)),
+            Color::LightYellow => Some((255, 255, 0)),
+            Color::LightBlue => Some((92, 92, 255)),
+            Color::LightMagenta => Some((255, 0, 255)),
+            Color::LightCyan => Some((0, 255, 255)),
+            Color::White => Some((255, 255, 255)),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn ui(f: &mut Frame, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(f.area());
+
+    match app.tab {
+        Tab::Browser => render_file_browser(f, app, chunks[0]),
+        Tab::Playlists => render_playlists(f, app, chunks[0]),
+    }
+    if app.screen_reader {
+        render_screen_reader_status(f, app, chunks[1]);
+    } else {
+        render_player_info(f, app, chunks[1]);
+    }
+    if app.profiler.visible {
+        render_profiler(f, app);
+    }
+    if app.leader_pending {
+        render_leader_hint(f, app);
+    }
+    if let Some(picker) = &app.subtune_picker {
+        render_subtune_picker(f, picker);
+    }
+    app.theme.apply(f.buffer_mut());
+    // After the theme: a QR code needs its black and white untouched
+    if let Some(qr) = &app.qr_view {
+        render_qr(f, qr);
+    }
+}
+
+/// Screen reader layout: one plain sentence per line, no icons, meters or
+/// color-only cues. The position moves in 30 s steps, so the text changes
+/// when the state does rather than on every frame.
+fn render_screen_reader_status(f: &mut Frame, app: &App, area: Rect) {
+    let state = if app.generator.is_some() {
+        "generatore"
+    } else if app.preview.is_some() {
+        "anteprima"
+    } else if app.is_playing {
+        "in riproduzione"
+    } else if app.selected_track.is_some() {
+        "in pausa"
+    } else {
+        "fermo"
+    };
+    let mut lines = vec![
+        Line::from(format!("Stato: {}", state)),
+        Line::from(format!(
+            "Traccia: {}",
+            app.track_info
+                .tags
+                .full_name()
+                .or_else(|| app.selected_track_name.clone())
+                .unwrap_or_else(|| "nessuna".to_string())
+        )),
+    ];
+    if app.selected_track.is_some() {
+        let step = Duration::from_secs(30);
+        let position = step * (app.current_time.as_secs() / step.as_secs()) as u32;
+        lines.push(Line::from(format!(
+            "Posizione: circa {} di {}",
+            App::format_duration(position),
+            App::format_duration(app.total_time)
+        )));
+    }
+    lines.push(Line::from(format!(
+        "Volume: {}%",
+        (app.audio_player.get_volume() * 100.0).round()
+    )));
+    if app.sessions.len() > 1 {
+        lines.push(Line::from(format!(
+            "Sessione: {}, {} di {}",
+            app.sessions.active_name(),
+            app.sessions.active() + 1,
+            app.sessions.len()
+        )));
+    }
+
+    let mut modes = Vec::new();
+    match app.repeat {
+        RepeatMode::Off => {}
+        RepeatMode::RepeatAll => modes.push("ripeti tutto".to_string()),
+        RepeatMode::RepeatOne => modes.push("ripeti traccia".to_string()),
+    }
+    if app.shuffle {
+        modes.push("shuffle".to_string());
+    }
+    if app.album_shuffle.is_some() {
+        modes.push("album casuali".to_string());
+    }
+    if app.preview_mode {
+        modes.push("anteprima".to_string());
+    }
+    if app.audio_player.eq_preset() != 0 {
+        modes.push(format!(
+            "equalizzatore {}",
+            EQ_PRESETS[app.audio_player.eq_preset()].0
+        ));
+    }
+    if let Some(practice) = app.practice {
+        modes.push(format!(
+            "pratica, passaggio {} di {}",
+            practice.done + 1,
+            practice.repetitions
+        ));
+    } else if matches!(app.ab_loop, Some((_, Some(_)))) {
+        modes.push("loop A-B".to_string());
+    }
+    if app.audio_player.metronome_gain().is_some() {
+        modes.push(format!(
+            "metronomo {:.0} BPM",
+            app.audio_player.metronome_bpm()
+        ));
+    }
+    if app.party_mode {
+        modes.push("party".to_string());
+    }
+    if !modes.is_empty() {
+        lines.push(Line::from(format!("Modalità: {}", modes.join(", "))));
+    }
+
+    if let Some(action) = app.pending_confirmation {
+        lines.push(Line::from(app.confirmation_prompt(action)));
+    }
+    if let Some(input) = &app.input {
+        let prompt = input.purpose.prompt();
+        let prompt = prompt.split_once(' ').map_or(prompt, |(_, label)| label);
+        lines.push(Line::from(format!(
+            "{}: {} (Invio conferma, Esc annulla)",
+            prompt, input.text
+        )));
+    }
+    if let Some((message, _)) = &app.toast {
+        lines.push(Line::from(format!("Avviso: {}", message)));
+    }
+    if let Some(error) = &app.error_message {
+        lines.push(Line::from(format!("Errore: {}", error)));
+    }
+    lines.push(Line::from(
+        "Tasti: Spazio play o pausa, Invio seleziona, n e p traccia, frecce ±5 s (Maiusc ±30 s), + e - volume, q esci",
+    ));
+
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: true }), area);
+}
+
+/// Debug overlay in the top right corner with the latest frame timings
+fn render_profiler(f: &mut Frame, app: &App) {
+    let area = f.area();
+    let width = 34.min(area.width);
+    let height = 10.min(area.height);
+    let overlay = Rect::new(area.x + area.width - width, area.y, width, height);
+
+    let millis = |d: Duration| format!("{:>7.3} ms", d.as_secs_f64() * 1000.0);
+    let (last, peak, budget) = app.audio_player.callback_timing();
+    let load = if budget.is_zero() {
+        0.0
+    } else {
+        peak.as_secs_f64() / budget.as_secs_f64() * 100.0
+    };
+    let capture = app.audio_player.capture_stats();
+    let label = Style::default().fg(Color::DarkGray);
+    let row = |name: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{:<14}", name), label),
+            Span::raw(value),
+        ])
+    };
+
+    let lines = vec![
+        row("Callback", millis(last)),
+        row("  picco", format!("{} ({:.0}%)", millis(peak), load)),
+        row("FFT", millis(app.profiler.fft)),
+        row("Render", millis(app.profiler.render)),
+        row("Latenza tasti", millis(app.profiler.event_latency)),
+        row(
+            "Buffer",
+            format!(
+                "{} / {} KiB",
+                capture.used_bytes / 1024,
+                capture.allocated_bytes / 1024
+            ),
+        ),
+        row("  riemp.", format!("{:.0}%", capture.fill * 100.0)),
+        row(
+            "  frame",
+            format!("{} ({} persi)", capture.written, capture.overruns),
+        ),
+    ];
+
+    f.render_widget(Clear, overlay);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title(" Profiler "),
+        ),
+        overlay,
+    );
+}
+
+/// Popup showing the QR code of a track list
+fn render_qr(f: &mut Frame, qr: &QrView) {
+    let area = f.area();
+    let lines = qr.lines();
+    let width = (qr.width as u16 + 2).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let overlay = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let code = Style::default().fg(Color::White).bg(Color::Black);
+    f.render_widget(Clear, overlay);
+    f.render_widget(
+        Paragraph::new(
+            lines
+                .into_iter()
+                .map(|line| Line::from(Span::styled(line, code)))
+                .collect::<Vec<_>>(),
+        )
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(qr.title.clone())
+                .title_bottom(" un tasto qualsiasi chiude "),
+        ),
+        overlay,
+    );
+}
+
+/// Popup listing the tunes of a game music file with their lengths
+fn render_subtune_picker(f: &mut Frame, picker: &SubtunePicker) {
+    let area = f.area();
+    let tune = &picker.tune;
+    let width = 60.min(area.width);
+    let height = (tune.subtunes.len() as u16 + 2).min(area.height);
+    let overlay = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    // Keep the selection in view when the list is taller than the popup
+    let rows = height.saturating_sub(2) as usize;
+    let first = (picker.selected + 1).saturating_sub(rows);
+    let lines: Vec<Line> = tune
+        .subtunes
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(rows)
+        .map(|(i, subtune)| {
+            let style = if i == picker.selected {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            Line::from(Span::styled(
+                format!(
+                    "{} {:>3}. {}  {}",
+                    if i == tune.start { "★" } else { " " },
+                    i + 1,
+                    App::format_duration(subtune.length),
+                    subtune
+                        .title
+                        .clone()
+                        .unwrap_or_else(|| format!("Traccia {}", i + 1))
+                ),
+                style,
+            ))
+        })
+        .collect();
+    let title = match (&tune.game, &tune.author) {
+        (Some(game), Some(author)) => format!(" 🎮 {} — {} ", game, author),
+        (Some(game), None) => format!(" 🎮 {} ", game),
+        _ => " 🎮 Brani ".to_string(),
+    };
+
+    f.render_widget(Clear, overlay);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title(title)
+                .title_bottom(" [↑↓] Scegli [Invio] Riproduci [Esc] Chiudi "),
+        ),
+        overlay,
+    );
+}
+
+/// Popup listing what can follow the leader key
+fn render_leader_hint(f: &mut Frame, app: &App) {
+    let area = f.area();
+    let columns = 2;
+    let rows = LEADER_BINDINGS.len().div_ceil(columns) as u16;
+    let width = 64.min(area.width);
+    let height = (rows + 2).min(area.height);
+    let overlay = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + area.height - height,
+        width,
+        height,
+    );
+
+    let key = Style::default().fg(Color::Yellow);
+    let lines: Vec<Line> = LEADER_BINDINGS
+        .chunks(columns)
+        .map(|pair| {
+            Line::from(
+                pair.iter()
+                    .flat_map(|(k, _, label)| {
+                        [
+                            Span::styled(format!(" {} ", k), key),
+                            Span::raw(format!("{:<27}", label)),
+                        ]
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+
+    f.render_widget(Clear, overlay);
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title(format!(" {} … (Esc annulla) ", app.keymap.leader)),
+        ),
+        overlay,
+    );
+}
+
+fn render_file_browser(f: &mut Frame, app: &mut App, area: Rect) {
+    let sfx_slots = if app.sfx_board {
+        app.sfx_slots()
+    } else {
+        Vec::new()
+    };
+    // Only the rows on screen are built, so huge folders cost the same as small ones
+    let height = area.height.saturating_sub(2) as usize;
+    let selected = app.list_state.selected().filter(|&i| i < app.items.len());
+    let mut offset = app
+        .list_state
+        .offset()
+        .min(app.items.len().saturating_sub(1));
+    if let Some(selected) = selected {
+        if selected < offset {
+            offset = selected;
+        } else if height > 0 && selected >= offset + height {
+            offset = selected + 1 - height;
+        }
+    }
+    *app.list_state.offset_mut() = offset;
+    app.visible_items = offset..(offset + height).min(app.items.len());
+
+    let items: Vec<ListItem> = app.items[app.visible_items.clone()]
+        .iter()
+        .map(|path| {
+            let slot = sfx_slots
+                .iter()
+                .position(|p| p == path)
+                .map(|i| format!("[{}] ", (i + 1) % SFX_SLOTS))
+                .unwrap_or_default();
+            let name = if path.file_name() == Some(std::ffi::OsStr::new("..")) {
+                "📁 ..".to_string()
+            } else if app.is_folder(path) {
+                format!(
+                    "{} {}",
+                    if ArchiveKind::of(path).is_some() {
+                        "📦"
+                    } else {
+                        "📁"
+                    },
+                    path.file_name()
+                        .map(|n| n.to_string_lossy())
+                        .unwrap_or_default()
+                )
+            } else {
+                format!(
+                    "{}🎵 {}{}",
+                    slot,
+                    path.file_name()
+                        .map(|n| n.to_string_lossy())
+                        .unwrap_or_default(),
+                    if app.notes.get(path).is_some() {
+                        " 📝"
+                    } else {
+                        ""
+                    }
+                )
+            };
+            // Filled in by the background scan; nothing is probed while drawing
+            let details = app
+                .file_info
+                .get(path)
+                .map(|info| {
+                    let mut columns: Vec<String> = info.tags.artist.iter().cloned().collect();
+                    columns.extend(info.duration.map(App::format_duration));
+                    columns.join(" · ")
+                })
+                .filter(|details| !details.is_empty())
+                .map(|details| format!("  {}", details))
+                .unwrap_or_default();
+            let integrity = match app.integrity.get(path) {
+                Some(integrity) if !integrity.is_ok() => Span::styled(
+                    format!("  ⚠ {}", integrity.problems[0]),
+                    Style::default().fg(Color::Red),
+                ),
+                Some(integrity) => Span::styled(
+                    if integrity.md5_verified {
+                        "  ✔ MD5"
+                    } else {
+                        "  ✔"
+                    },
+                    Style::default().fg(Color::Green),
+                ),
+                None => Span::raw(""),
+            };
+            ListItem::new(Line::from(vec![
+                Span::raw(name),
+                Span::styled(details, Style::default().fg(Color::DarkGray)),
+                integrity,
+            ]))
+        })
+        .collect();
+
+    let title = if app.sfx_board {
+        format!(
+            " 🔊 SFX board: {}  [1-0] Play [b] Esci ",
+            app.current_dir.display()
+        )
+    } else {
+        format!(" 📂 {}  [Tab] Playlist ", app.current_dir.display())
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .style(Style::default().fg(Color::Cyan)),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+
+    let mut window_state = ListState::default().with_selected(selected.map(|i| i - offset));
+    f.render_stateful_widget(list, area, &mut window_state);
+}
+
+/// Playlists tab: the saved playlists, or the tracks of the open one
+fn render_playlists(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(4)])
+        .split(area);
+
+    let (title, items, state) = match &app.playlists.open {
+        Some(playlist) => {
+            let items: Vec<ListItem> = playlist
+                .tracks
+                .iter()
+                .enumerate()
+                .map(|(i, track)| {
+                    let name = track
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let style = if track.exists() {
+                        Style::default()
+                    } else {
+                        Style::default().fg(Color::DarkGray)
+                    };
+                    ListItem::new(format!("{:>3}. 🎵 {}", i + 1, name)).style(style)
+                })
+                .collect();
+            (
+                format!(
+                    " 📜 {} ({}) ",
+                    Playlist::name(&playlist.path),
+                    playlist.tracks.len()
+                ),
+                items,
+                &mut app.playlists.track_state,
+            )
+        }
+        None => {
+            let items: Vec<ListItem> = app
+                .playlists
+                .found
+                .iter()
+                .map(|path| {
+                    let marker = if app.playlists.target.as_ref() == Some(path) {
+                        " ◀ [P]"
+                    } else {
+                        ""
+                    };
+                    ListItem::new(format!("📜 {}{}", Playlist::name(path), marker))
+                })
+                .collect();
+            (
+                " 📜 Playlist  [Tab] File ".to_string(),
+                items,
+                &mut app.playlists.state,
+            )
+        }
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .style(Style::default().fg(Color::Cyan)),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("▶ ");
+    f.render_stateful_widget(list, chunks[0], state);
+
+    let help = if app.playlists.open.is_some() {
+        vec![
+            Line::from("[Enter] Play | [J/K] Sposta | [X] Togli"),
+            Line::from("[Esc] Torna alle playlist"),
+        ]
+    } else {
+        vec![
+            Line::from("[Enter] Apri | [C] Nuova | [R] Rinomina"),
+            Line::from("[U] Duplica | [D] Elimina | [P] Aggiungi"),
+        ]
+    };
+    let help = Paragraph::new(help).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::DarkGray)),
+    );
+    f.render_widget(help, chunks[1]);
+}
+
+fn render_player_info(f: &mut Frame, app: &App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(8),
+            Constraint::Length(10),
+        ])
+        .split(area);
+
+    // Tags when there are any, else the file name
+    let tags = &app.track_info.tags;
+    let track_name = tags.full_name().unwrap_or_else(|| {
+        app.selected_track_name
+            .clone()
+            .unwrap_or_else(|| "Nessuna traccia selezionata".to_string())
+    });
+    let details: Vec<String> = [
+        tags.track.map(|track| format!("Traccia {}", track)),
+        tags.year.map(|year| year.to_string()),
+        app.track_info.codec.clone(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let title = Paragraph::new(vec![
+        Line::from(track_name),
+        Line::from(Span::styled(
+            details.join(" · "),
+            Style::default()
+                .fg(Color::DarkGray)
+                .remove_modifier(Modifier::BOLD),
+        )),
+    ])
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_set(border::ROUNDED)
+            .title(" 🎵 Traccia Corrente ")
+            .style(Style::default().fg(Color::Green)),
+    )
+    .style(Style::default().add_modifier(Modifier::BOLD));
+    f.render_widget(title, chunks[0]);
+
+    let progress = if app.total_time.as_secs() > 0 {
+        (app.current_time.as_secs_f64() / app.total_time.as_secs_f64() * 100.0).min(100.0) as u16
+    } else {
+        0
+    };
+
+    let time_label = if app.total_time.as_secs() > 0 {
+        format!(
+            "{} / {}",
+            App::format_duration(app.current_time),
+            App::format_duration(app.total_time)
+        )
+    } else {
+        format!("{} / --:--", App::format_duration(app.current_time))
+    };
+
+    let gauge = Gauge::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" ⏱️  Progresso "),
+        )
+        .gauge_style(Style::default().fg(Color::Yellow).bg(Color::Black))
+        .percent(progress)
+        .label(time_label);
+    f.render_widget(gauge, chunks[1]);
+    render_section_ticks(f, app, chunks[1]);
+
+    render_volume_control(f, app, chunks[2]);
+    if app.mixer_view {
+        render_mixer(f, app, chunks[3]);
+    } else if app.zones_view {
+        render_zones(f, app, chunks[3]);
+    } else if app.bluetooth_view {
+        render_bluetooth(f, app, chunks[3]);
+    } else if app.cd_view {
+        render_cd(f, app, chunks[3]);
+    } else if app.queue_view {
+        render_queue(f, app, chunks[3]);
+    } else if app.skip_view {
+        render_skip_view(f, app, chunks[3]);
+    } else if let Some(heatmap) = &app.stats_view {
+        render_heatmap(f, heatmap, app.keymap.leader, chunks[3]);
+    } else if let Some(report) = &app.gap_report {
+        render_gap_report(f, report, chunks[3]);
+    } else if let [first, second] = app.waveforms.as_slice() {
+        render_waveforms(f, first, second, chunks[3]);
+    } else if app.lyrics_view {
+        render_lyrics(f, app, chunks[3]);
+    } else if app.visualizer_enabled {
+        render_histogram(f, app, chunks[3]);
+    } else {
+        render_track_details(f, app, chunks[3]);
+    }
+
+    let status = if app.is_playing {
+        "▶️  Playing"
+    } else if app.selected_track.is_some() {
+        "⏸️  Paused"
+    } else {
+        "⏹️  Stopped"
+    };
+
+    let repeat_status = match app.repeat {
+        RepeatMode::RepeatOne => format!(" | 🔂 Ripeti: {}", app.repeat.label()),
+        mode => format!(" | 🔁 Ripeti: {}", mode.label()),
+    };
+
+    let shuffle_status = if app.shuffle {
+        " | 🔀 Shuffle: ON"
+    } else {
+        ""
+    };
+
+    let album_status = match &app.album_shuffle {
+        Some(shuffle) => format!(
+            " | 💿 Album shuffle: {}/{}",
+            shuffle.played.len(),
+            shuffle.albums.len()
+        ),
+        None => String::new(),
+    };
+
+    let buffer_status = match app.audio_player.get_buffer_latency() {
+        Some(latency) => format!(" | Buffer: {} ms", latency.as_millis()),
+        None => " | Buffer: default".to_string(),
+    };
+
+    let mut technical_line = vec![Span::styled(
+        app.audio_player.technical_info() + &buffer_status,
+        Style::default().fg(Color::DarkGray),
+    )];
+    if let Some(bits) = app.audio_player.bit_perfect_depth() {
+        technical_line.push(Span::styled(
+            format!(" | ✓ bit-perfect {} bit", bits),
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(reading) = app.audio_player.true_peak() {
+        technical_line.push(Span::styled(
+            format!(" | {}", format_true_peak(reading)),
+            Style::default().fg(true_peak_color(reading)),
+        ));
+    }
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(
+                status,
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(
+                repeat_status,
+                Style::default().fg(if app.repeat == RepeatMode::Off {
+                    Color::DarkGray
+                } else {
+                    Color::Green
+                }),
+            ),
+            Span::styled(shuffle_status, Style::default().fg(Color::Magenta)),
+            Span::styled(album_status, Style::default().fg(Color::Green)),
+            Span::styled(
+                if app.preview_mode {
+                    " | 👂 Anteprima"
+                } else {
+                    ""
+                },
+                Style::default().fg(Color::LightBlue),
+            ),
+            Span::styled(
+                match app.audio_player.zones().len() {
+                    0 => String::new(),
+                    zones => format!(" | 🔊 Zone: {}", zones),
+                },
+                Style::default().fg(Color::LightGreen),
+            ),
+            Span::styled(
+                if app.audio_player.is_ducked() {
+                    " | 🔉 Annuncio"
+                } else {
+                    ""
+                },
+                Style::default().fg(Color::LightYellow),
+            ),
+            Span::styled(
+                match app.queue.len() {
+                    0 => String::new(),
+                    queued => format!(" | 📋 Coda: {}", queued),
+                },
+                Style::default().fg(Color::LightCyan),
+            ),
+            Span::styled(
+                match app.audio_player.broadcast().map(|b| b.status()) {
+                    None => String::new(),
+                    Some(BroadcastStatus::Connecting) => " | 📡 Connessione...".to_string(),
+                    Some(BroadcastStatus::OnAir) => " | 📡 In onda".to_string(),
+                    Some(BroadcastStatus::Error(e)) => format!(" | 📡 {}", e),
+                },
+                Style::default().fg(Color::LightRed),
+            ),
+            Span::styled(
+                match (app.audio_player.eq_preset(), app.eq_auto) {
+                    (0, None) => String::new(),
+                    (preset, None) => format!(" | 🎚 EQ: {}", EQ_PRESETS[preset].0),
+                    (preset, Some(_)) => format!(" | 🎚 EQ: {} (auto)", EQ_PRESETS[preset].0),
+                },
+                Style::default().fg(Color::LightYellow),
+            ),
+            Span::styled(
+                match (app.ab_loop, app.practice) {
+                    (Some((a, Some(b))), Some(practice)) => format!(
+                        " | 🎯 {}–{} {}/{} @ {:.0}%",
+                        App::format_duration(a),
+                        App::format_duration(b),
+                        practice.done + 1,
+                        practice.repetitions,
+                        practice.speed() * 100.0
+                    ),
+                    (Some((a, Some(b))), None) => format!(
+                        " | 🔁 {}–{}",
+                        App::format_duration(a),
+                        App::format_duration(b)
+                    ),
+                    (Some((a, None)), _) => format!(" | 🔁 A {}", App::format_duration(a)),
+                    (None, _) => String::new(),
+                },
+                Style::default().fg(Color::LightGreen),
+            ),
+            Span::styled(
+                app.generator
+                    .map(|signal| format!(" | 〰 {}", signal.label()))
+                    .unwrap_or_default(),
+                Style::default().fg(Color::LightCyan),
+            ),
+            Span::styled(
+                match app.audio_player.metronome_gain() {
+                    Some(_) => format!(
+                        " | 🥁 {:.0} BPM{}",
+                        app.audio_player.metronome_bpm(),
+                        if app.metronome_sync { " (auto)" } else { "" }
+                    ),
+                    None => String::new(),
+                },
+                Style::default().fg(Color::LightGreen),
+            ),
+            Span::styled(
+                app.alarms
+                    .pending()
+                    .map(|alarm| format!(" | ⏰ {:02}:{:02}", alarm.hour, alarm.minute))
+                    .collect::<String>(),
+                Style::default().fg(Color::LightRed),
+            ),
+            Span::styled(
+                if app.sessions.len() > 1 {
+                    format!(
+                        " | 🗂 {} ({}/{})",
+                        app.sessions.active_name(),
+                        app.sessions.active() + 1,
+                        app.sessions.len()
+                    )
+                } else {
+                    String::new()
+                },
+                Style::default().fg(Color::LightBlue),
+            ),
+            Span::styled(
+                if app.macros.is_recording() {
+                    " | ⏺ REC"
+                } else {
+                    ""
+                },
+                Style::default()
+                    .fg(Color::LightRed)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(
+                if app.active_radio_edit().is_some() {
+                    " | 📻 Radio edit"
+                } else {
+                    ""
+                },
+                Style::default().fg(Color::LightYellow),
+            ),
+            Span::styled(
+                if app.party_mode { " | 🎉 Party" } else { "" },
+                Style::default()
+                    .fg(Color::LightMagenta)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ]),
+        Line::from(technical_line),
+        Line::from(
+            "Controls: [Space] Play/Pause | [↑↓/jk] Navigate | [Enter] Select | [←→] Seek | [{}] Sezioni | [G] ReplayGain | [M] MusicBrainz | [H/I] Storico | [y/w] Copia",
+        ),
+        Line::from(
+            "          [+/-] Volume | [N] Next | [P] Previous | [r] Ripeti | [s] Shuffle | [a] Coda | [E/e] Note | [o/O] Cartella/Editor | [Canc/u] Elimina/Annulla | [F12] Profiler | [Q] Quit",
+        ),
+        Line::from(
+            "          [A] Album shuffle | [L] Party mode | [f] Scala spettro | [v] Visual. | [l] Testo | [h] Anteprima | [z] Mixer | [g] Continuità | [d] Uscita | [x] EQ | [t] Sveglia | [i/T] A–B/Pratica | [B/N] Metronomo/BPM | [W] Generatore | [V] Confronta | [Z] Verifica | [@] Macro | [S] Sessione | [\\] Altri comandi",
+        ),
+    ];
+
+    if let Some(action) = app.pending_confirmation {
+        lines.push(Line::from(vec![Span::styled(
+            app.confirmation_prompt(action),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )]));
+    }
+
+    if let Some(input) = &app.input {
+        let prompt = input.purpose.prompt();
+        lines.push(Line::from(vec![Span::styled(
+            format!("{}: {}█  [Enter] OK [Esc] Annulla", prompt, input.text),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )]));
+    }
+
+    if let Some((message, _)) = &app.toast {
+        lines.push(Line::from(vec![Span::styled(
+            message.clone(),
+            Style::default().fg(Color::Green),
+        )]));
+    }
+
+    if let Some(error) = &app.error_message {
+        lines.push(Line::from(vec![Span::styled(
+            format!("⚠️  {}", error),
+            Style::default().fg(Color::Red),
+        )]));
+    }
+
+    let controls = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" 🎮 Controlli ")
+            .style(Style::default().fg(Color::Magenta)),
+    );
+    f.render_widget(controls, chunks[4]);
+}
+
+/// Marks where each section of the playing track starts on the progress
+/// bar, leaving the time label readable
+fn render_section_ticks(f: &mut Frame, app: &App, area: Rect) {
+    let Some((track, sections)) = &app.sections else {
+        return;
+    };
+    if app.selected_track.as_ref() != Some(track) || app.total_time.is_zero() {
+        return;
+    }
+    let inner = Block::default().borders(Borders::ALL).inner(area);
+    if inner.width == 0 || inner.height == 0 {
+        return;
+    }
+    let total = app.total_time.as_secs_f64();
+    for start in sections {
+        let offset = (start.as_secs_f64() / total * inner.width as f64) as u16;
+        if offset >= inner.width {
+            continue;
+        }
+        if let Some(cell) = f.buffer_mut().cell_mut((inner.x + offset, inner.y))
+            && cell.symbol() == " "
+        {
+            cell.set_char('│');
+        }
+    }
+}
+
+fn render_volume_control(f: &mut Frame, app: &App, area: Rect) {
+    let volume_percent = (app.audio_player.get_volume() * 100.0) as u16;
+    let volume_icon = if volume_percent == 0 {
+        "🔇"
+    } else if volume_percent < 33 {
+        "🔈"
+    } else if volume_percent < 66 {
+        "🔉"
+    } else {
+        "🔊"
+    };
+
+    let max_percent = (app.audio_player.get_max_volume() * 100.0).round() as u16;
+    let volume_label = if max_percent < 100 {
+        format!("{} {}% (max {}%)", volume_icon, volume_percent, max_percent)
+    } else {
+        format!("{} {}%", volume_icon, volume_percent)
+    };
+
+    let gauge = Gauge::default()
+        .block(Block::default().borders(Borders::ALL).title(" 🔊 Volume "))
+        .gauge_style(Style::default().fg(Color::Cyan).bg(Color::Black))
+        .percent(volume_percent)
+        .label(volume_label);
+    f.render_widget(gauge, area);
+}
+
+/// Mixer panel: one line per stream on the output mixer with its gain
+fn render_mixer(f: &mut Frame, app: &App, area: Rect) {
+    let label = Style::default().fg(Color::DarkGray);
+    let mut lines = vec![Line::from(vec![
+        Span::styled("Master     ", label),
+        Span::raw(format!(
+            "{:>3}%",
+            (app.audio_player.get_volume() * 100.0).round()
+        )),
+    ])];
+
+    let streams = app.audio_player.streams();
+    if streams.is_empty() {
+        lines.push(Line::from(Span::styled("Nessuno stream attivo", label)));
+    }
+    for (i, stream) in streams.iter().enumerate() {
+        let state = if stream.sink.empty() {
+            "■"
+        } else if stream.sink.is_paused() {
+            "⏸"
+        } else {
+            "▶"
+        };
+        let filled = ((stream.gain * 10.0).round() as usize).min(10);
+        let style = if i == app.mixer_selected {
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::White)
+        };
+        lines.push(Line::from(Span::styled(
+            format!(
+                "{} {:<9} [{}{}] {:>3}%  {}",
+                state,
+                stream.role.label(),
+                "█".repeat(filled),
+                "░".repeat(10 - filled),
+                (stream.gain * 100.0).round(),
+                stream.name
+            ),
+            style,
+        )));
+    }
+
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" 🎚️  Mixer [,/.] Seleziona [[/]] Volume ")
+            .style(Style::default().fg(Color::Blue)),
+    );
+    f.render_widget(panel, area);
+}
+
+/// Zones panel: every output device, marking the main one and the zones
+/// playing along with their volume
+fn render_zones(f: &mut Frame, app: &App, area: Rect) {
+    let label = Style::default().fg(Color::DarkGray);
+    let mut lines = Vec::new();
+    if app.zones.devices.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "Nessun dispositivo di uscita",
+            label,
+        )));
+    }
+    let zones = app.audio_player.zones();
+    for (i, name) in app.zones.devices.iter().enumerate() {
+        let zone = zones.iter().find(|zone| &zone.device_name == name);
+        let state = if name == app.audio_player.device_name() {
+            "principale".to_string()
+        } else if let Some(zone) = zone {
+            let filled = ((zone.volume() * 10.0).round() as usize).min(10);
+            format!(
+                "[{}{}] {:>3}%",
+                "█".repeat(filled),
+                "░".repeat(10 - filled),
+                (zone.volume() * 100.0).round()
+            )
+        } else {
+            "—".to_string()
+        };
+        let style = if i == app.zones.selected {
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else if zone.is_some() {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default().fg(Color::White)
+        };
+        lines.push(Line::from(Span::styled(
+            format!(
+                "{} {:<17} {}",
+                if zone.is_some() { "🔊" } else { "  " },
+                state,
+                name
+            ),
+            style,
+        )));
+    }
+
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" 🔊 Zone [,/.] Seleziona [Invio] Attiva [[/]] Volume ")
+            .style(Style::default().fg(Color::Blue)),
+    );
+    f.render_widget(panel, area);
+}
+
+/// Bluetooth panel: the paired audio devices, connected ones marked
+fn render_bluetooth(f: &mut Frame, app: &App, area: Rect) {
+    let label = Style::default().fg(Color::DarkGray);
+    let mut lines = Vec::new();
+    if app.bluetooth_job.is_some() {
+        lines.push(Line::from(Span::styled("⏳ In corso…", label)));
+    } else if app.bluetooth_devices.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "Nessun dispositivo audio associato",
+            label,
+        )));
+    }
+    for (i, device) in app.bluetooth_devices.iter().enumerate() {
+        let style = if i == app.bluetooth_selected {
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else if device.connected {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default().fg(Color::White)
+        };
+        lines.push(Line::from(Span::styled(
+            format!(
+                "{} {:<12} {}  {}",
+                if device.connected { "🎧" } else { "  " },
+                if device.connected { "connesso" } else { "—" },
+                device.name,
+                device.address
+            ),
+            style,
+        )));
+    }
+
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" 🎧 Bluetooth [,/.] Seleziona [Invio] Connetti/Disconnetti ")
+            .style(Style::default().fg(Color::Blue)),
+    );
+    f.render_widget(panel, area);
+}
+
+/// CD panel: the disc's tracks with their lengths, the playing one marked
+fn render_cd(f: &mut Frame, app: &App, area: Rect) {
+    let label = Style::default().fg(Color::DarkGray);
+    let mut lines = Vec::new();
+    if app.cd_job.is_some() {
+        lines.push(Line::from(Span::styled("⏳ Lettura del disco…", label)));
+    } else if app.cd.is_none() {
+        lines.push(Line::from(Span::styled("Nessun disco audio", label)));
+    }
+    if app.rip_job.is_some() {
+        lines.push(Line::from(Span::styled(
+            "💿 Rip in FLAC in corso…",
+            Style::default().fg(Color::Magenta),
+        )));
+    }
+    let tracks = app.cd.iter().flat_map(|cd| &cd.tracks);
+    for (i, track) in tracks.enumerate() {
+        let playing = app.cd_playing == Some(i);
+        let style = if i == app.cd_selected {
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else if playing {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default().fg(Color::White)
+        };
+        lines.push(Line::from(Span::styled(
+            format!(
+                "{} {:>2}. {}  {}",
+                if playing { "▶" } else { " " },
+                track.number,
+                App::format_duration(track.duration()),
+                track.name()
+            ),
+            style,
+        )));
+    }
+
+    let title = app
+        .cd
+        .as_ref()
+        .map_or("CD audio".to_string(), |cd| cd.title());
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(
+                " 💿 {} [,/.] Seleziona [Invio] Riproduci [R] Rippa ",
+                title
+            ))
+            .style(Style::default().fg(Color::Blue)),
+    );
+    f.render_widget(panel, area);
+}
+
+/// Queue panel: the tracks to play next, in order
+fn render_queue(f: &mut Frame, app: &App, area: Rect) {
+    let mut lines = Vec::new();
+    // Guests' requests first: they wait on whoever is at the player
+    if !app.queue.requests().is_empty() {
+        lines.push(Line::from(Span::styled(
+            "🙋 Richieste ospiti: [A] approva la prima, [N] la rifiuta",
+            Style::default().fg(Color::Magenta),
+        )));
+        for track in app.queue.requests() {
+            let name = track
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            lines.push(Line::from(Span::styled(
+                format!("   · {}", name),
+                Style::default().fg(Color::Magenta),
+            )));
+        }
+        lines.push(Line::from(""));
+    }
+    if app.queue.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "Coda vuota: [a] aggiunge la traccia o la cartella evidenziata",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    for (i, track) in app.queue.tracks().iter().enumerate() {
+        let style = if i == app.queue_selected {
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::White)
+        };
+        let name = track
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let folder = track
+            .parent()
+            .and_then(|folder| folder.file_name())
+            .map(|folder| folder.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        lines.push(Line::from(vec![
+            Span::styled(format!("{:>3}. {}", i + 1, name), style),
+            Span::styled(
+                format!("  {}", folder),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]));
+    }
+
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" 📋 Coda [,/.] Seleziona [J/K] Sposta [X] Togli [C] Svuota [Invio] Riproduci ")
+            .style(Style::default().fg(Color::Blue)),
+    );
+    f.render_widget(panel, area);
+}
+
+/// Two loudness envelopes stacked on the same time scale: RMS solid, peaks
+/// shaded above it. Closed with the same key.
+fn render_waveforms(f: &mut Frame, first: &Waveform, second: &Waveform, area: Rect) {
+    let difference = first.envelope_difference(second);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " 〰 Confronto  Δ durata {:+.3} s · Δ envelope medio {:.1} dB  [V] Chiudi ",
+            second.duration.as_secs_f64() - first.duration.as_secs_f64(),
+            difference
+        ))
+        .style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let halves = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)])
+        .split(inner);
+    let length = first.duration.max(second.duration);
+    for (waveform, area, color) in [
+        (first, halves[0], Color::LightGreen),
+        (second, halves[1], Color::LightMagenta),
+    ] {
+        let (peak, rms) = waveform.levels();
+        let mut lines = vec![Line::from(vec![
+            Span::styled(waveform.name.clone(), Style::default().fg(color)),
+            Span::styled(
+                format!(
+                    "  {} · picco {:.1} dBFS · RMS {:.1} dBFS",
+                    App::format_duration(waveform.duration),
+                    peak,
+                    rms
+                ),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ])];
+
+        let rows = area.height.saturating_sub(1) as usize;
+        let columns = waveform.columns(area.width as usize, length);
+        let height = |db: f32| {
+            ((db - Waveform::FLOOR_DB) / -Waveform::FLOOR_DB * rows as f32).round() as usize
+        };
+        for row in (0..rows).rev() {
+            let text: String = columns
+                .iter()
+                .map(|column| match column {
+                    Some((_, rms)) if height(*rms) > row => '█',
+                    Some((peak, _)) if height(*peak) > row => '░',
+                    _ => ' ',
+                })
+                .collect();
+            lines.push(Line::from(Span::styled(text, Style::default().fg(color))));
+        }
+        f.render_widget(Paragraph::new(lines), area);
+    }
+}
+
+/// Result of the album transition check, closed with the same key
+/// Most skipped tracks first, as many as fit
+fn render_skip_view(f: &mut Frame, app: &App, area: Rect) {
+    let mut skipped: Vec<(&PathBuf, u32)> = app.skips.iter().map(|(p, &n)| (p, n)).collect();
+    skipped.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let lines: Vec<Line> = if skipped.is_empty() {
+        vec![Line::from(Span::styled(
+            "Nessuna traccia saltata",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        skipped
+            .into_iter()
+            .take(area.height.saturating_sub(2) as usize)
+            .map(|(path, count)| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:>4}× ", count),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    Span::raw(
+                        path.file_name()
+                            .map(|n| n.to_string_lossy().into_owned())
+                            .unwrap_or_default(),
+                    ),
+                ])
+            })
+            .collect()
+    };
+    let leader = app.keymap.leader;
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(
+                " ⏭ Tracce saltate  [{}U] Azzera  [{}u] Chiudi ",
+                leader, leader
+            ))
+            .style(Style::default().fg(Color::Cyan)),
+    );
+    f.render_widget(panel, area);
+}
+
+/// Day × hour grid of listening time, two columns per hour, shaded against
+/// the busiest hour
+fn render_heatmap(f: &mut Frame, heatmap: &ListeningHeatmap, leader: char, area: Rect) {
+    const SHADES: [&str; 5] = ["··", "░░", "▒▒", "▓▓", "██"];
+    let label = Style::default().fg(Color::DarkGray);
+    let busiest = heatmap
+        .peak()
+        .map_or(Duration::ZERO, |(day, hour)| heatmap.time[day][hour]);
+
+    let mut header = String::from("    ");
+    for hour in (0..24).step_by(3) {
+        header += &format!("{:<6}", hour);
+    }
+    let mut lines = vec![Line::from(Span::styled(header, label))];
+    for (day, hours) in heatmap.time.iter().enumerate() {
+        let mut spans = vec![Span::styled(
+            format!("{} ", ListeningHeatmap::DAYS[day]),
+            label,
+        )];
+        for time in hours {
+            let shade = if busiest.is_zero() || time.is_zero() {
+                0
+            } else {
+                // Any listening at all shows, however little
+                1 + (time.as_secs_f64() / busiest.as_secs_f64() * 3.0).round() as usize
+            };
+            spans.push(Span::styled(
+                SHADES[shade.min(4)],
+                Style::default().fg(Color::Green),
+            ));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    lines.push(Line::from(""));
+    let total = heatmap.total().as_secs();
+    let mut summary = format!(
+        "{} ascolti, {}h {:02}m in totale",
+        heatmap.plays,
+        total / 3600,
+        total / 60 % 60
+    );
+    if let Some((day, hour)) = heatmap.peak() {
+        let peak = heatmap.time[day][hour].as_secs();
+        summary += &format!(
+            " | Picco: {} {:02}:00 ({}h {:02}m)",
+            ListeningHeatmap::DAYS[day],
+            hour,
+            peak / 3600,
+            peak / 60 % 60
+        );
+    }
+    lines.push(Line::from(Span::styled(summary, label)));
+
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(
+                " 📊 Ascolti per giorno e ora  [{}y] Chiudi ",
+                leader
+            ))
+            .style(Style::default().fg(Color::Cyan)),
+    );
+    f.render_widget(panel, area);
+}
+
+fn render_gap_report(f: &mut Frame, report: &GapReport, area: Rect) {
+    let label = Style::default().fg(Color::DarkGray);
+    let mut lines = Vec::new();
+    if report.issues.is_empty() {
+        lines.push(Line::from(Span::styled(
+            format!("✔ Tutti i {} passaggi sono continui", report.transitions),
+            Style::default().fg(Color::Green),
+        )));
+    } else {
+        lines.push(Line::from(Span::styled(
+            format!(
+                "{} passaggi su {} non sono continui:",
+                report.issues.len(),
+                report.transitions
+            ),
+            Style::default().fg(Color::Yellow),
+        )));
+        for issue in &report.issues {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("✘ {} → {}: ", issue.from, issue.to),
+                    Style::default().fg(Color::Red),
+                ),
+                Span::raw(issue.problem.clone()),
+            ]));
+        }
+        lines.push(Line::from(Span::styled(
+            "Attiva il crossfade o ripara il rip (encoder gapless, tracce ritagliate)",
+            label,
+        )));
+    }
+
+    let panel = Paragraph::new(lines).wrap(Wrap { trim: true }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" 🔍 Continuità: {}  [g] Chiudi ", report.album))
+            .style(Style::default().fg(Color::Cyan)),
+    );
+    f.render_widget(panel, area);
+}
+
+/// Lyrics panel: synced lyrics keep the current line highlighted and centered
+fn render_lyrics(f: &mut Frame, app: &App, area: Rect) {
+    let label = Style::default().fg(Color::DarkGray);
+    let mut title = " 🎤 Testo [Y] Cerca online ".to_string();
+    let mut lines = Vec::new();
+
+    match &app.lyrics {
+        Some(lyrics) => {
+            title = format!(" 🎤 Testo ({}) [Y] Cerca online ", lyrics.source);
+            let current = lyrics.current_line(app.current_time);
+            let visible = area.height.saturating_sub(2) as usize;
+            let first = current
+                .map(|line| line.saturating_sub(visible / 2))
+                .unwrap_or(0);
+            for (i, (_, text)) in lyrics.lines.iter().enumerate().skip(first).take(visible) {
+                let style = if Some(i) == current {
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(Color::White)
+                };
+                lines.push(Line::from(Span::styled(text.clone(), style)));
+            }
+        }
+        None if app.lyrics_job.is_some() => {
+            lines.push(Line::from(Span::styled("Ricerca testo in corso...", label)));
+        }
+        None => {
+            lines.push(Line::from(Span::styled(
+                "Nessun testo trovato (.lrc/.txt accanto al file o in cache)",
+                label,
+            )));
+        }
+    }
+
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .style(Style::default().fg(Color::Blue)),
+    );
+    f.render_widget(panel, area);
+}
+
+/// Static replacement for the spectrum when the visualizer is off
+fn render_track_details(f: &mut Frame, app: &App, area: Rect) {
+    let label = Style::default().fg(Color::DarkGray);
+    let mut lines = Vec::new();
+
+    if let Some(track) = &app.selected_track {
+        let folder = track
+            .parent()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        let duration = if app.total_time.as_secs() > 0 {
+            App::format_duration(app.total_time)
+        } else {
+            "--:--".to_string()
+        };
+        lines.push(Line::from(vec![
+            Span::styled("File:     ", label),
+            Span::raw(app.selected_track_name.clone().unwrap_or_default()),
+        ]));
+        lines.push(Line::from(vec![
+            Span::styled("Cartella: ", label),
+            Span::raw(folder),
+        ]));
+        lines.push(Line::from(vec![
+            Span::styled("Durata:   ", label),
+            Span::raw(duration),
+        ]));
+        lines.push(Line::from(vec![
+            Span::styled("Formato:  ", label),
+            Span::raw(app.audio_player.technical_info()),
+        ]));
+        let mut plays = format!("{}", app.current_plays);
+        if let Some(rating) = app.stats.get(track).1 {
+            plays += &format!("  {}", rating_stars(rating));
+        }
+        lines.push(Line::from(vec![
+            Span::styled("Ascolti:  ", label),
+            Span::raw(plays),
+        ]));
+        if let Some(reading) = app.true_peaks.get(track) {
+            lines.push(Line::from(vec![
+                Span::styled("Picco:    ", label),
+                Span::styled(
+                    format_true_peak(reading),
+                    Style::default().fg(true_peak_color(reading)),
+                ),
+            ]));
+        }
+        if let Some(note) = app.notes.get(track) {
+            lines.push(Line::from(vec![
+                Span::styled("Nota:     ", label),
+                Span::styled(note.to_string(), Style::default().fg(Color::Yellow)),
+            ]));
+        }
+        let cover = match (&app.cover, &app.cover_job) {
+            (Some(cover), _) => cover.label(),
+            (None, Some(_)) => "download in corso...".to_string(),
+            (None, None) => "nessuna".to_string(),
+        };
+        lines.push(Line::from(vec![
+            Span::styled("Copertina:", label),
+            Span::raw(format!(" {}", cover)),
+        ]));
+    } else {
+        lines.push(Line::from(Span::styled(
+            "Nessuna traccia selezionata",
+            label,
+        )));
+    }
+
+    let details = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" ℹ️  Dettagli Traccia (visualizzatore spento) ")
+            .style(Style::default().fg(Color::Blue)),
+    );
+    f.render_widget(details, area);
+}
+
+/// True peak with the headroom left under full scale, and the clips if any
+fn format_true_peak(reading: TruePeakReading) -> String {
+    let mut text = format!(
+        "TP {:+.1} dBTP, margine {:.1} dB",
+        reading.peak_db,
+        (-reading.peak_db).max(0.0)
+    );
+    if reading.clips > 0 {
+        text += &format!(", {} clip", reading.clips);
+    }
+    text
+}
+
+/// Red once it clips, yellow within 1 dB of full scale (what streaming
+/// services ask to leave for their encoders)
+fn true_peak_color(reading: TruePeakReading) -> Color {
+    if reading.clips > 0 || reading.peak_db >= 0.0 {
+        Color::Red
+    } else if reading.peak_db > -1.0 {
+        Color::Yellow
+    } else {
+        Color::DarkGray
+    }
+}
+
+/// Row of frequency markers aligned with the bars; markers that would
+/// overlap the previous one are skipped
+fn frequency_axis_labels(scale: FrequencyScale, width: usize) -> String {
+    const MARKERS: [(f32, &str); 9] = [
+        (60.0, "60"),
+        (125.0, "125"),
+        (250.0, "250"),
+        (500.0, "500"),
+        (1000.0, "1k"),
+        (2000.0, "2k"),
+        (4000.0, "4k"),
+        (8000.0, "8k"),
+        (16000.0, "16k"),
+    ];
+
+    let mut row = vec![' '; width];
+    let mut next_free = 0;
+    for (freq, text) in MARKERS {
+        let x = (scale.position_of(freq) * width as f32) as usize;
+        let x = x.min(width.saturating_sub(text.len()));
+        if x < next_free || x + text.len() > width {
+            continue;
+        }
+        for (offset, c) in text.chars().enumerate() {
+            row[x + offset] = c;
+        }
+        next_free = x + text.len() + 1;
+    }
+    row.into_iter().collect()
+}
+
+/// Frequency as on the axis: Hz below 1 kHz, kHz with one decimal above
+fn format_frequency(freq: f32) -> String {
+    if freq < 1000.0 {
+        format!("{:.0}", freq)
+    } else {
+        format!("{:.1}k", freq / 1000.0)
+    }
+}
+
+fn render_histogram(f: &mut Frame, app: &App, area: Rect) {
+    let solo = app
+        .audio_player
+        .band_solo()
+        .map(|(low, high)| {
+            format!(
+                " · 🎧 Solo {}–{} Hz [,/.]",
+                format_frequency(low),
+                format_frequency(high)
+            )
+        })
+        .unwrap_or_default();
+    let tilt = match (app.tilt_overlay, app.spectral_slope()) {
+        (TiltOverlay::Off, _) => String::new(),
+        (overlay, slope) => format!(
+            " · 🌸 {}{}{}",
+            overlay.label(),
+            slope
+                .map(|slope| format!(", pendenza {:+.1} dB/ott (rosa -3)", slope))
+                .unwrap_or_default(),
+            if overlay == TiltOverlay::Deviation {
+                ", ±12 dB"
+            } else {
+                ""
+            }
+        ),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " 📊 Analisi Spettro Audio (FFT Real-Time) · {}{}{}{} ",
+            app.frequency_scale.label(),
+            if app.stereo_spectrum {
+                " · L▲ R▼"
+            } else {
+                ""
+            },
+            solo,
+            tilt
+        ))
+        .style(Style::default().fg(Color::Blue));
+
+    let mut inner = block.inner(area);
+    f.render_widget(block, area);
+
+    if inner.height < 2 || inner.width < 2 {
+        return;
+    }
+
+    let bar_width = (inner.width as usize / app.histogram.len()).max(1);
+
+    // Top row holds the energy meter when there is room for it
+    if inner.height >= 6 {
+        let meter_area = Rect { height: 1, ..inner };
+        inner.y += 1;
+        inner.height -= 1;
+        let db = app.energy * 60.0 - 60.0;
+        let meter = LineGauge::default()
+            .filled_style(
+                Style::default().fg(app.spectrum_theme.color_at(app.energy, app.truecolor)),
+            )
+            .unfilled_style(Style::default().fg(Color::DarkGray))
+            .ratio(app.energy.clamp(0.0, 1.0) as f64)
+            .label(format!("Energia {:>4.0} dB ", db));
+        f.render_widget(meter, meter_area);
+    }
+
+    // Bottom row holds the frequency axis when there is room for it
+    if inner.height >= 4 {
+        inner.height -= 1;
+        let axis_area = Rect {
+            y: inner.y + inner.height,
+            height: 1,
+            ..inner
+        };
+        let axis_width = (bar_width * app.histogram.len()).min(inner.width as usize);
+        let axis = Paragraph::new(frequency_axis_labels(app.frequency_scale, axis_width))
+            .style(Style::default().fg(Color::DarkGray));
+        f.render_widget(axis, axis_area);
+    }
+
+    // Left channel grows up from the middle, right channel grows down
+    let top = Rect {
+        height: inner.height / 2,
+        ..inner
+    };
+    let bottom = Rect {
+        y: inner.y + top.height,
+        height: inner.height - top.height,
+        ..inner
+    };
+    if app.tilt_overlay == TiltOverlay::Deviation {
+        // Above the pink slope grows up from the middle, below it down
+        let deviations = app.pink_deviations();
+        let scaled = |sign: f32| -> Vec<f32> {
+            deviations
+                .iter()
+                .map(|dev| dev.map_or(0.0, |dev| (sign * dev / 12.0).clamp(0.0, 1.0)))
+                .collect()
+        };
+        render_bars(f, app, top, &scaled(1.0), bar_width, true);
+        render_bars(f, app, bottom, &scaled(-1.0), bar_width, false);
+    } else if app.stereo_spectrum {
+        render_bars(f, app, top, &app.histogram, bar_width, true);
+        render_bars(f, app, bottom, &app.histogram_right, bar_width, false);
+        if app.tilt_overlay == TiltOverlay::Reference {
+            render_pink_reference(f, app, top, bar_width);
+        }
+    } else {
+        render_bars(f, app, inner, &app.histogram, bar_width, true);
+        if app.tilt_overlay == TiltOverlay::Reference {
+            render_pink_reference(f, app, inner, bar_width);
+        }
+    }
+}
+
+/// Draws the pink noise slope across the bars growing up in `inner`
+fn render_pink_reference(f: &mut Frame, app: &App, inner: Rect, bar_width: usize) {
+    let height = inner.height as usize;
+    for (i, reference) in app.pink_reference_heights().into_iter().enumerate() {
+        let x_pos = inner.x + (i * bar_width) as u16;
+        if x_pos >= inner.x + inner.width {
+            break;
+        }
+        let Some(reference) = reference else {
+            continue;
+        };
+        let row = ((reference * height as f32) as usize).min(height.saturating_sub(1));
+        let width = bar_width.min((inner.x + inner.width - x_pos) as usize) as u16;
+        let line =
+            Paragraph::new("─".repeat(width as usize)).style(Style::default().fg(Color::Magenta));
+        f.render_widget(
+            line,
+            Rect {
+                x: x_pos,
+                y: inner.y + inner.height - 1 - row as u16,
+                width,
+                height: 1,
+            },
+        );
+    }
+}
+
+/// Draws one bar per value, growing up from the bottom of `area` or down from its top
+fn render_bars(
+    f: &mut Frame,
+    app: &App,
+    inner: Rect,
+    values: &[f32],
+    bar_width: usize,
+    upward: bool,
+) {
+    let height = inner.height as usize;
+
+    for (i, &amplitude) in values.iter().enumerate() {
+        let bar_height = (amplitude * height as f32) as usize;
+        let bar_height = bar_height.min(height);
+
+        let x_pos = inner.x + (i * bar_width) as u16;
+
+        if x_pos >= inner.x + inner.width {
+            break;
+        }
+
+        for y in 0..bar_height {
+            let y_pos = if upward {
+                inner.y + inner.height - 1 - y as u16
+            } else {
+                inner.y + y as u16
+            };
+
+            // Bars outside a soloed band are greyed out
+            let color = if app.band_solo.is_some_and(|bar| bar != i) {
+                Color::DarkGray
+            } else {
+                app.spectrum_theme
+                    .color_at(y as f32 / height as f32, app.truecolor)
+            };
+
+            let bar_char = app
+                .spectrum_theme
+                .bar_char(y as f32 / height as f32, app.is_playing);
+
+            let bar = Paragraph::new(
+                bar_char.repeat(bar_width.min((inner.width - (x_pos - inner.x)) as usize)),
+            )
+            .style(Style::default().fg(color));
+
+            let bar_area = Rect {
+                x: x_pos,
+                y: y_pos,
+                width: bar_width.min((inner.x + inner.width - x_pos) as usize) as u16,
+                height: 1,
+            };
+
+            f.render_widget(bar, bar_area);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Zones view: the output devices that can play along with the main one,
+//! see `AudioPlayer::toggle_zone`.
+
+/// Devices listed by the zones view and the one under the cursor
+#[derive(Default)]
+pub(crate) struct ZonesView {
+    /// Output devices when the view was opened
+    pub(crate) devices: Vec<String>,
+    pub(crate) selected: usize,
+}
+
+impl ZonesView {
+    pub(crate) fn new(devices: Vec<String>) -> Self {
+        Self {
+            devices,
+            selected: 0,
+        }
+    }
+
+    pub(crate) fn selected_device(&self) -> Option<&str> {
+        self.devices.get(self.selected).map(String::as_str)
+    }
+
+    /// Moves the cursor one device, wrapping around
+    pub(crate) fn select(&mut self, forward: bool) {
+        let count = self.devices.len();
+        if count > 0 {
+            self.selected = if forward {
+                (self.selected + 1) % count
+            } else {
+                (self.selected + count - 1) % count
+            };
+        }
+    }
+}